@@ -0,0 +1,224 @@
+elrond_wasm::imports!();
+
+use crate::{
+    liquidation::{POSITION_HEALTHY_ERR_MSG, POSITION_LIQUIDATED_ERR_MSG},
+    math::BASE_PRECISION,
+    model::{Auction, BidResultType},
+};
+
+static NO_AUCTION_ERR_MSG: &[u8] = b"No auction for position";
+
+#[elrond_wasm::module]
+pub trait AuctionModule:
+    crate::liquidation::LiquidationModule
+    + crate::math::MathModule
+    + crate::ongoing_operation::OngoingOperationModule
+    + crate::price_aggregator_proxy::PriceAggregatorModule
+    + crate::staking_rewards::StakingRewardsModule
+    + crate::tokens::TokensModule
+    + crate::treasury::TreasuryModule
+    + crate::common_storage::CommonStorageModule
+{
+    /// Puts the collateral of an unhealthy position up for a Dutch auction.
+    /// The price starts above the oracle price, and decreases every round, until it reaches the min price.
+    /// The position's debt keeps accruing interest while the auction runs.
+    #[endpoint(startAuction)]
+    fn start_auction(&self, borrow_token_nonce: u64) {
+        self.require_no_ongoing_operation();
+        self.require_not_auctioned(borrow_token_nonce);
+        require!(!self.auction_params().is_empty(), "Auction params not set");
+
+        let borrow_position = self.get_borrow_position(borrow_token_nonce);
+        require!(
+            borrow_position.borrowed_amount > 0u32,
+            POSITION_LIQUIDATED_ERR_MSG
+        );
+
+        self.accrue_interest();
+        self.update_global_lender_rewards();
+        let staked_token_value_in_dollars = self.get_staked_token_value_in_dollars();
+        let total_debt = self.get_borrow_position_total_debt(&borrow_position);
+        let health_factor = self.get_borrow_position_health_factor(
+            &borrow_position,
+            &staked_token_value_in_dollars,
+            &total_debt,
+        );
+        require!(health_factor < BASE_PRECISION, POSITION_HEALTHY_ERR_MSG);
+
+        let auction_params = self.auction_params().get();
+        let start_price_multiplier =
+            BigUint::from(BASE_PRECISION) + &auction_params.start_price_premium;
+        let start_price = &staked_token_value_in_dollars * &start_price_multiplier / BASE_PRECISION;
+        let min_price =
+            &staked_token_value_in_dollars * &auction_params.min_price_percentage / BASE_PRECISION;
+
+        self.emit_start_auction_event(
+            &self.blockchain().get_caller(),
+            borrow_token_nonce,
+            &start_price,
+            &min_price,
+        );
+
+        self.auction(borrow_token_nonce).set(&Auction {
+            start_round: self.blockchain().get_block_round(),
+            start_price,
+            min_price,
+        });
+    }
+
+    /// Buys liquid staking tokens at the current auction price.
+    /// Proceeds are split between the borrowed amount and the accrued interest,
+    /// in the same proportion as in the position's current debt.
+    /// Once the debt is covered, the auction ends and the unsold collateral can be claimed
+    /// by the Borrow SFT holders through `claimLiquidationSurplus`.
+    #[payable("*")]
+    #[endpoint]
+    fn bid(&self, borrow_token_nonce: u64) -> BidResultType<Self::Api> {
+        self.require_no_ongoing_operation();
+
+        let (payment_amount, payment_token) = self.call_value().payment_token_pair();
+        let stablecoin_token_id = self.stablecoin_token_id().get();
+        require!(
+            payment_token == stablecoin_token_id,
+            "May only pay with stablecoins"
+        );
+
+        let auction_mapper = self.auction(borrow_token_nonce);
+        require!(!auction_mapper.is_empty(), NO_AUCTION_ERR_MSG);
+
+        self.accrue_interest();
+        self.update_global_lender_rewards();
+        let mut borrow_position = self.get_borrow_position(borrow_token_nonce);
+        let current_price = self.get_current_auction_price(&auction_mapper.get());
+        let total_debt = self.get_borrow_position_total_debt(&borrow_position);
+
+        let mut amount_paid = if payment_amount > total_debt {
+            total_debt.clone()
+        } else {
+            payment_amount.clone()
+        };
+        let mut amount_bought = self.compute_staking_token_amount(&current_price, &amount_paid);
+        if amount_bought > borrow_position.liquid_staking_amount {
+            amount_bought = borrow_position.liquid_staking_amount.clone();
+            amount_paid = self.compute_staking_position_value(&current_price, &amount_bought);
+        }
+        require!(amount_bought > 0u32, "Bid amount too low");
+
+        // the principal goes back to the pool, the accrued interest is added to the reserves
+        let principal_repaid = &borrow_position.borrowed_amount * &amount_paid / &total_debt;
+        let extra_reserves = &amount_paid - &principal_repaid;
+        borrow_position.borrowed_amount -= &principal_repaid;
+        let collateral_bought = self.take_position_collateral(&mut borrow_position, &amount_bought);
+
+        // no collateral left to back the remaining debt, so it's written off,
+        // and the principal that is lost is recorded as bad debt
+        let mut debt_removed = amount_paid.clone();
+        let mut principal_written_off = BigUint::zero();
+        if borrow_position.liquid_staking_amount == 0u32 {
+            debt_removed = total_debt;
+            principal_written_off = borrow_position.borrowed_amount.clone();
+            borrow_position.borrowed_amount = BigUint::zero();
+        }
+
+        self.borrowed_amount().update(|borrowed_amount| {
+            *borrowed_amount -= &principal_repaid;
+            *borrowed_amount -= &principal_written_off;
+        });
+        self.decrease_total_debt(&debt_removed);
+        self.add_to_reserves(extra_reserves);
+        self.add_bad_debt(principal_written_off.clone());
+
+        if borrow_position.borrowed_amount == 0u32 {
+            auction_mapper.clear();
+        }
+
+        if borrow_position.liquid_staking_amount == 0u32 {
+            self.borrow_position(borrow_token_nonce).clear();
+        } else {
+            self.borrow_position(borrow_token_nonce)
+                .set(&borrow_position);
+        }
+
+        let caller = self.blockchain().get_caller();
+        self.emit_bid_event(
+            &caller,
+            borrow_token_nonce,
+            &amount_paid,
+            &amount_bought,
+            &principal_written_off,
+        );
+
+        let liquid_staking_payments = self.send_position_collateral(&caller, &collateral_bought);
+
+        let extra_stablecoins_paid = payment_amount - amount_paid;
+        let extra_stablecoins_payment = if extra_stablecoins_paid > 0u32 {
+            self.send_stablecoins(&caller, extra_stablecoins_paid)
+        } else {
+            EsdtTokenPayment::new(stablecoin_token_id, 0, BigUint::zero())
+        };
+
+        (liquid_staking_payments, extra_stablecoins_payment).into()
+    }
+
+    /// Ends the auction of a position that is healthy again, or that was not covered
+    /// after `max_rounds` rounds. The position can then be repaid or liquidated again.
+    #[endpoint(cancelAuction)]
+    fn cancel_auction(&self, borrow_token_nonce: u64) {
+        self.require_no_ongoing_operation();
+
+        let auction_mapper = self.auction(borrow_token_nonce);
+        require!(!auction_mapper.is_empty(), NO_AUCTION_ERR_MSG);
+
+        let auction = auction_mapper.get();
+        let rounds_passed = self.blockchain().get_block_round() - auction.start_round;
+        let auction_params = self.auction_params().get();
+        if rounds_passed <= auction_params.max_rounds {
+            self.accrue_interest();
+            self.update_global_lender_rewards();
+            let borrow_position = self.get_borrow_position(borrow_token_nonce);
+            let staked_token_value_in_dollars = self.get_staked_token_value_in_dollars();
+            let total_debt = self.get_borrow_position_total_debt(&borrow_position);
+            let health_factor = self.get_borrow_position_health_factor(
+                &borrow_position,
+                &staked_token_value_in_dollars,
+                &total_debt,
+            );
+            require!(
+                health_factor >= BASE_PRECISION,
+                "Position is still unhealthy"
+            );
+        }
+
+        auction_mapper.clear();
+
+        self.auction_cancelled_event(
+            &self.blockchain().get_caller(),
+            borrow_token_nonce,
+            self.blockchain().get_block_epoch(),
+        );
+    }
+
+    #[view(getAuctionPrice)]
+    fn get_auction_price(&self, borrow_token_nonce: u64) -> BigUint {
+        let auction_mapper = self.auction(borrow_token_nonce);
+        require!(!auction_mapper.is_empty(), NO_AUCTION_ERR_MSG);
+
+        self.get_current_auction_price(&auction_mapper.get())
+    }
+
+    fn get_current_auction_price(&self, auction: &Auction<Self::Api>) -> BigUint {
+        let current_round = self.blockchain().get_block_round();
+        let rounds_passed = current_round - auction.start_round;
+
+        let auction_params = self.auction_params().get();
+        let price_decrease =
+            &auction.start_price * &auction_params.price_decrease_per_round * rounds_passed
+                / BASE_PRECISION;
+        let min_price_with_decrease = &auction.min_price + &price_decrease;
+        if auction.start_price <= min_price_with_decrease {
+            return auction.min_price.clone();
+        }
+
+        &auction.start_price - &price_decrease
+    }
+}
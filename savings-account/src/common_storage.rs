@@ -1,5 +1,9 @@
 elrond_wasm::imports!();
 
+use crate::model::{
+    Auction, AuctionParams, BorrowPosition, LiquidationParams, PoolParams, TimeBasis,
+};
+
 #[elrond_wasm::module]
 pub trait CommonStorageModule {
     #[view(getStablecoinReserves)]
@@ -10,7 +14,81 @@ pub trait CommonStorageModule {
     #[storage_mapper("lentAmount")]
     fn lent_amount(&self) -> SingleValueMapper<BigUint>;
 
+    /// The extra amount locked lends count for in the lender rewards, on top of `lentAmount`
+    #[view(getLockBoostAmount)]
+    #[storage_mapper("lockBoostAmount")]
+    fn lock_boost_amount(&self) -> SingleValueMapper<BigUint>;
+
+    /// The lock boost amount of the locks that end in each epoch,
+    /// dropped from `lockBoostAmount` once that epoch has passed
+    #[storage_mapper("lockBoostAmountByEndEpoch")]
+    fn lock_boost_amount_by_end_epoch(&self) -> MapMapper<u64, BigUint>;
+
+    /// Lender rewards lost to the penalty, owed to each lender
+    #[view(getRewardsIou)]
+    #[storage_mapper("rewardsIou")]
+    fn rewards_iou(&self, holder: &ManagedAddress) -> SingleValueMapper<BigUint>;
+
+    /// The total of the rewards IOUs that were not claimed yet
+    #[view(getTotalRewardsIou)]
+    #[storage_mapper("totalRewardsIou")]
+    fn total_rewards_iou(&self) -> SingleValueMapper<BigUint>;
+
+    /// Stablecoins set aside from the reserves to pay the rewards IOUs
+    #[view(getRewardsIouFunds)]
+    #[storage_mapper("rewardsIouFunds")]
+    fn rewards_iou_funds(&self) -> SingleValueMapper<BigUint>;
+
+    /// Lent stablecoins lost to liquidations whose collateral did not cover the borrowed amount,
+    /// not yet paid back from the reserves
+    #[view(getBadDebt)]
+    #[storage_mapper("badDebt")]
+    fn bad_debt(&self) -> SingleValueMapper<BigUint>;
+
     #[view(getBorowedAmount)]
     #[storage_mapper("borrowedAmount")]
     fn borrowed_amount(&self) -> SingleValueMapper<BigUint>;
+
+    #[view(getTotalDebt)]
+    #[storage_mapper("totalDebt")]
+    fn total_debt(&self) -> SingleValueMapper<BigUint>;
+
+    #[view(getBorrowIndex)]
+    #[storage_mapper("borrowIndex")]
+    fn borrow_index(&self) -> SingleValueMapper<BigUint>;
+
+    #[storage_mapper("lastBorrowIndexUpdateEpoch")]
+    fn last_borrow_index_update_epoch(&self) -> SingleValueMapper<u64>;
+
+    #[storage_mapper("lastBorrowIndexUpdateTimestamp")]
+    fn last_borrow_index_update_timestamp(&self) -> SingleValueMapper<u64>;
+
+    #[view(getTimeBasis)]
+    #[storage_mapper("timeBasis")]
+    fn time_basis(&self) -> SingleValueMapper<TimeBasis>;
+
+    #[storage_mapper("poolParams")]
+    fn pool_params(&self) -> SingleValueMapper<PoolParams<Self::Api>>;
+
+    #[storage_mapper("liquidationParams")]
+    fn liquidation_params(&self) -> SingleValueMapper<LiquidationParams<Self::Api>>;
+
+    #[view(getLoadToValuePercentage)]
+    #[storage_mapper("loadToValuePercentage")]
+    fn loan_to_value_percentage(&self) -> SingleValueMapper<BigUint>;
+
+    #[view(getBorrowPosition)]
+    #[storage_mapper("borrowPosition")]
+    fn borrow_position(
+        &self,
+        borrow_token_nonce: u64,
+    ) -> SingleValueMapper<BorrowPosition<Self::Api>>;
+
+    #[view(getAuction)]
+    #[storage_mapper("auction")]
+    fn auction(&self, borrow_token_nonce: u64) -> SingleValueMapper<Auction<Self::Api>>;
+
+    #[view(getAuctionParams)]
+    #[storage_mapper("auctionParams")]
+    fn auction_params(&self) -> SingleValueMapper<AuctionParams<Self::Api>>;
 }
@@ -0,0 +1,399 @@
+elrond_wasm::imports!();
+
+use crate::{
+    math::BASE_PRECISION,
+    model::{
+        AuctionParams, LiquidationParams, ParamChange, PendingParamChange, PoolParams, TimeBasis,
+    },
+};
+
+pub type PendingParamChangesResultType<M> =
+    MultiValueEncoded<M, MultiValue2<u64, PendingParamChange<M>>>;
+
+#[elrond_wasm::module]
+pub trait ConfigModule:
+    crate::events::EventsModule
+    + crate::interest::InterestModule
+    + crate::math::MathModule
+    + crate::ongoing_operation::OngoingOperationModule
+    + crate::price_aggregator_proxy::PriceAggregatorModule
+    + crate::staking_rewards::StakingRewardsModule
+    + crate::common_storage::CommonStorageModule
+{
+    /// Queues new borrow rate parameters and reserve factor, which also drives the lenders' rate.
+    /// Returns the ID of the pending change.
+    #[only_owner]
+    #[endpoint(queuePoolParams)]
+    fn queue_pool_params(
+        &self,
+        base_borrow_rate: BigUint,
+        borrow_rate_under_opt_factor: BigUint,
+        borrow_rate_over_opt_factor: BigUint,
+        optimal_utilisation: BigUint,
+        reserve_factor: BigUint,
+    ) -> u64 {
+        let pool_params = PoolParams {
+            base_borrow_rate,
+            borrow_rate_under_opt_factor,
+            borrow_rate_over_opt_factor,
+            optimal_utilisation,
+            reserve_factor,
+        };
+        self.require_valid_pool_params(&pool_params);
+
+        self.queue_param_change(ParamChange::PoolParams(pool_params))
+    }
+
+    #[only_owner]
+    #[endpoint(queueLoanToValuePercentage)]
+    fn queue_loan_to_value_percentage(&self, loan_to_value_percentage: BigUint) -> u64 {
+        let liquidation_params = self.liquidation_params().get();
+        self.require_valid_loan_to_value_percentage(
+            &loan_to_value_percentage,
+            &liquidation_params.liquidation_threshold,
+        );
+
+        self.queue_param_change(ParamChange::LoanToValuePercentage(loan_to_value_percentage))
+    }
+
+    #[only_owner]
+    #[endpoint(queuePriceAggregatorAddress)]
+    fn queue_price_aggregator_address(&self, address: ManagedAddress) -> u64 {
+        require!(
+            self.blockchain().is_smart_contract(&address),
+            "Invalid price aggregator address"
+        );
+
+        self.queue_param_change(ParamChange::PriceAggregatorAddress(address))
+    }
+
+    #[only_owner]
+    #[endpoint(queueDelegationScAddress)]
+    fn queue_delegation_sc_address(&self, address: ManagedAddress) -> u64 {
+        require!(
+            self.blockchain().is_smart_contract(&address),
+            "Invalid Delegation SC address"
+        );
+
+        self.queue_param_change(ParamChange::DelegationScAddress(address))
+    }
+
+    #[only_owner]
+    #[endpoint(queueDexSwapScAddress)]
+    fn queue_dex_swap_sc_address(&self, address: ManagedAddress) -> u64 {
+        require!(
+            self.blockchain().is_smart_contract(&address),
+            "Invalid DEX Swap SC address"
+        );
+
+        self.queue_param_change(ParamChange::DexSwapScAddress(address))
+    }
+
+    /// Queues the Dutch auction params: the premium of the start price over the oracle price,
+    /// the price decrease per round, as a fraction of the start price,
+    /// the min price, as a fraction of the oracle price,
+    /// and the number of rounds after which an auction can be cancelled.
+    #[only_owner]
+    #[endpoint(queueAuctionParams)]
+    fn queue_auction_params(
+        &self,
+        start_price_premium: BigUint,
+        price_decrease_per_round: BigUint,
+        min_price_percentage: BigUint,
+        max_rounds: u64,
+    ) -> u64 {
+        let auction_params = AuctionParams {
+            start_price_premium,
+            price_decrease_per_round,
+            min_price_percentage,
+            max_rounds,
+        };
+        self.require_valid_auction_params(&auction_params);
+
+        self.queue_param_change(ParamChange::AuctionParams(auction_params))
+    }
+
+    /// Queues new liquidation params. The current loan-to-value percentage
+    /// must not be above the new liquidation threshold.
+    #[only_owner]
+    #[endpoint(queueLiquidationParams)]
+    fn queue_liquidation_params(
+        &self,
+        liquidation_threshold: BigUint,
+        close_factor: BigUint,
+        liquidation_bonus: BigUint,
+    ) -> u64 {
+        let liquidation_params = LiquidationParams {
+            liquidation_threshold,
+            close_factor,
+            liquidation_bonus,
+        };
+        self.require_valid_liquidation_params(&liquidation_params);
+        self.require_valid_loan_to_value_percentage(
+            &self.loan_to_value_percentage().get(),
+            &liquidation_params.liquidation_threshold,
+        );
+
+        self.queue_param_change(ParamChange::LiquidationParams(liquidation_params))
+    }
+
+    /// Queues a new delay for the param changes.
+    /// It only applies to the changes queued after it is executed.
+    #[only_owner]
+    #[endpoint(queueParamChangeDelay)]
+    fn queue_param_change_delay(&self, param_change_delay_epochs: u64) -> u64 {
+        require!(param_change_delay_epochs > 0, "Invalid param change delay");
+
+        self.queue_param_change(ParamChange::ParamChangeDelay(param_change_delay_epochs))
+    }
+
+    /// Queues a switch between counting interest and lender rewards per epoch or per second
+    #[only_owner]
+    #[endpoint(queueTimeBasis)]
+    fn queue_time_basis(&self, time_basis: TimeBasis) -> u64 {
+        self.queue_param_change(ParamChange::TimeBasis(time_basis))
+    }
+
+    /// Applies a queued change once its delay has passed.
+    /// Interest and lender rewards are accrued with the old pool params first.
+    #[only_owner]
+    #[endpoint(executeParamChange)]
+    fn execute_param_change(&self, change_id: u64) {
+        self.require_no_ongoing_operation();
+
+        let pending_change = self
+            .pending_param_changes()
+            .get(&change_id)
+            .unwrap_or_else(|| sc_panic!("Unknown param change"));
+        let current_epoch = self.blockchain().get_block_epoch();
+        require!(
+            current_epoch >= pending_change.unlock_epoch,
+            "Param change is still timelocked"
+        );
+
+        match &pending_change.param_change {
+            ParamChange::PoolParams(pool_params) => {
+                self.require_valid_pool_params(pool_params);
+
+                self.accrue_interest();
+                self.update_global_lender_rewards();
+
+                self.pool_params().set(pool_params);
+            }
+            ParamChange::LoanToValuePercentage(loan_to_value_percentage) => {
+                // the liquidation threshold might have changed in the meantime
+                let liquidation_params = self.liquidation_params().get();
+                self.require_valid_loan_to_value_percentage(
+                    loan_to_value_percentage,
+                    &liquidation_params.liquidation_threshold,
+                );
+
+                self.loan_to_value_percentage()
+                    .set(loan_to_value_percentage);
+            }
+            ParamChange::PriceAggregatorAddress(address) => {
+                self.price_aggregator_address().set(address);
+            }
+            ParamChange::DelegationScAddress(address) => {
+                self.delegation_sc_address().set(address);
+            }
+            ParamChange::DexSwapScAddress(address) => {
+                self.dex_swap_sc_address().set(address);
+            }
+            ParamChange::AuctionParams(auction_params) => {
+                self.auction_params().set(auction_params);
+            }
+            ParamChange::LiquidationParams(liquidation_params) => {
+                // the loan-to-value percentage might have changed in the meantime
+                self.require_valid_loan_to_value_percentage(
+                    &self.loan_to_value_percentage().get(),
+                    &liquidation_params.liquidation_threshold,
+                );
+
+                self.liquidation_params().set(liquidation_params);
+            }
+            ParamChange::ParamChangeDelay(param_change_delay_epochs) => {
+                self.param_change_delay_epochs()
+                    .set(param_change_delay_epochs);
+            }
+            ParamChange::TimeBasis(time_basis) => {
+                self.switch_time_basis(*time_basis);
+            }
+        }
+
+        self.pending_param_changes().remove(&change_id);
+
+        self.param_change_executed_event(change_id, current_epoch, &pending_change.param_change);
+    }
+
+    #[only_owner]
+    #[endpoint(cancelParamChange)]
+    fn cancel_param_change(&self, change_id: u64) {
+        let pending_change = self
+            .pending_param_changes()
+            .remove(&change_id)
+            .unwrap_or_else(|| sc_panic!("Unknown param change"));
+
+        self.param_change_cancelled_event(
+            change_id,
+            self.blockchain().get_block_epoch(),
+            &pending_change.param_change,
+        );
+    }
+
+    fn queue_param_change(&self, param_change: ParamChange<Self::Api>) -> u64 {
+        let change_id = self.last_param_change_id().update(|id| {
+            *id += 1;
+            *id
+        });
+        let current_epoch = self.blockchain().get_block_epoch();
+        let unlock_epoch = current_epoch + self.param_change_delay_epochs().get();
+
+        self.param_change_queued_event(change_id, unlock_epoch, &param_change);
+
+        let pending_change = PendingParamChange {
+            param_change,
+            queue_epoch: current_epoch,
+            unlock_epoch,
+        };
+        self.pending_param_changes()
+            .insert(change_id, pending_change);
+
+        change_id
+    }
+
+    /// Everything is accrued with the old time basis first
+    fn switch_time_basis(&self, time_basis: TimeBasis) {
+        self.accrue_interest();
+        self.update_global_lender_rewards();
+
+        let current_epoch = self.blockchain().get_block_epoch();
+        let current_timestamp = self.blockchain().get_block_timestamp();
+        self.last_borrow_index_update_epoch().set(current_epoch);
+        self.last_borrow_index_update_timestamp()
+            .set(current_timestamp);
+        self.last_rewards_update_epoch().set(current_epoch);
+        self.last_rewards_update_timestamp().set(current_timestamp);
+
+        self.time_basis().set(time_basis);
+    }
+
+    fn require_valid_pool_params(&self, pool_params: &PoolParams<Self::Api>) {
+        require!(
+            pool_params.base_borrow_rate <= BASE_PRECISION,
+            "Invalid base borrow rate"
+        );
+        require!(
+            pool_params.borrow_rate_under_opt_factor <= BASE_PRECISION
+                && pool_params.borrow_rate_over_opt_factor <= BASE_PRECISION,
+            "Invalid borrow rate factors"
+        );
+        require!(
+            pool_params.optimal_utilisation > 0u32
+                && pool_params.optimal_utilisation < BASE_PRECISION,
+            "Invalid optimal utilisation"
+        );
+        require!(
+            pool_params.reserve_factor <= BASE_PRECISION,
+            "Invalid reserve factor"
+        );
+    }
+
+    fn require_valid_liquidation_params(&self, liquidation_params: &LiquidationParams<Self::Api>) {
+        require!(
+            liquidation_params.liquidation_threshold > 0u32
+                && liquidation_params.liquidation_threshold < BASE_PRECISION,
+            "Invalid liquidation threshold"
+        );
+        require!(
+            liquidation_params.close_factor > 0u32
+                && liquidation_params.close_factor <= BASE_PRECISION,
+            "Invalid close factor"
+        );
+        require!(
+            liquidation_params.liquidation_bonus < BASE_PRECISION,
+            "Invalid liquidation bonus"
+        );
+    }
+
+    fn require_valid_auction_params(&self, auction_params: &AuctionParams<Self::Api>) {
+        require!(
+            auction_params.price_decrease_per_round > 0u32
+                && auction_params.price_decrease_per_round <= BASE_PRECISION,
+            "Invalid price decrease per round"
+        );
+        require!(
+            auction_params.min_price_percentage > 0u32
+                && auction_params.min_price_percentage <= BASE_PRECISION,
+            "Invalid min price percentage"
+        );
+        require!(auction_params.max_rounds > 0, "Invalid max rounds");
+    }
+
+    fn require_valid_loan_to_value_percentage(
+        &self,
+        loan_to_value_percentage: &BigUint,
+        liquidation_threshold: &BigUint,
+    ) {
+        require!(
+            *loan_to_value_percentage > 0u32 && *loan_to_value_percentage < BASE_PRECISION,
+            "Invalid loan to value percentage"
+        );
+        require!(
+            loan_to_value_percentage <= liquidation_threshold,
+            "Loan to value percentage above liquidation threshold"
+        );
+    }
+
+    /// Adds or updates a lock period lenders may choose at `lend` time.
+    /// The rewards of the locked tokens are multiplied by `boost`, where `BASE_PRECISION` means 1x.
+    /// Tokens that are already locked keep the boost they were created with.
+    #[only_owner]
+    #[endpoint(setLockPeriod)]
+    fn set_lock_period(&self, lock_epochs: u64, boost: u64) {
+        require!(lock_epochs > 0, "Invalid lock period");
+        require!(boost >= u64::from(BASE_PRECISION), "Invalid boost");
+
+        let _ = self.lock_periods().insert(lock_epochs, boost);
+    }
+
+    #[only_owner]
+    #[endpoint(removeLockPeriod)]
+    fn remove_lock_period(&self, lock_epochs: u64) {
+        let removed = self.lock_periods().remove(&lock_epochs);
+        require!(removed.is_some(), "Invalid lock period");
+    }
+
+    #[view(getLockPeriods)]
+    fn get_lock_periods(&self) -> MultiValueEncoded<MultiValue2<u64, u64>> {
+        let mut result = MultiValueEncoded::new();
+        for (lock_epochs, boost) in self.lock_periods().iter() {
+            result.push((lock_epochs, boost).into());
+        }
+
+        result
+    }
+
+    #[view(getPendingParamChanges)]
+    fn get_pending_param_changes(&self) -> PendingParamChangesResultType<Self::Api> {
+        let mut result = MultiValueEncoded::new();
+        for (change_id, pending_change) in self.pending_param_changes().iter() {
+            result.push((change_id, pending_change).into());
+        }
+
+        result
+    }
+
+    #[storage_mapper("lockPeriods")]
+    fn lock_periods(&self) -> MapMapper<u64, u64>;
+
+    #[view(getParamChangeDelayEpochs)]
+    #[storage_mapper("paramChangeDelayEpochs")]
+    fn param_change_delay_epochs(&self) -> SingleValueMapper<u64>;
+
+    #[storage_mapper("lastParamChangeId")]
+    fn last_param_change_id(&self) -> SingleValueMapper<u64>;
+
+    #[storage_mapper("pendingParamChanges")]
+    fn pending_param_changes(&self) -> MapMapper<u64, PendingParamChange<Self::Api>>;
+}
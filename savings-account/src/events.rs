@@ -0,0 +1,767 @@
+elrond_wasm::imports!();
+
+use crate::model::ParamChange;
+
+#[elrond_wasm::module]
+pub trait EventsModule:
+    crate::math::MathModule + crate::common_storage::CommonStorageModule
+{
+    fn emit_lend_event(&self, caller: &ManagedAddress, lend_nonce: u64, amount: &BigUint) {
+        let (borrow_rate, deposit_rate, utilisation) = self.get_current_rates();
+        self.lend_event(
+            caller,
+            lend_nonce,
+            amount,
+            self.blockchain().get_block_epoch(),
+            &borrow_rate,
+            &deposit_rate,
+            &utilisation,
+        );
+    }
+
+    fn emit_borrow_event(
+        &self,
+        caller: &ManagedAddress,
+        borrow_nonce: u64,
+        collateral_amount: &BigUint,
+        borrowed_amount: &BigUint,
+    ) {
+        let (borrow_rate, deposit_rate, utilisation) = self.get_current_rates();
+        self.borrow_event(
+            caller,
+            borrow_nonce,
+            collateral_amount,
+            borrowed_amount,
+            self.blockchain().get_block_epoch(),
+            &borrow_rate,
+            &deposit_rate,
+            &utilisation,
+        );
+    }
+
+    fn emit_borrow_more_event(
+        &self,
+        caller: &ManagedAddress,
+        old_borrow_nonce: u64,
+        new_borrow_nonce: u64,
+        borrowed_amount: &BigUint,
+    ) {
+        let (borrow_rate, deposit_rate, utilisation) = self.get_current_rates();
+        self.borrow_more_event(
+            caller,
+            old_borrow_nonce,
+            new_borrow_nonce,
+            borrowed_amount,
+            self.blockchain().get_block_epoch(),
+            &borrow_rate,
+            &deposit_rate,
+            &utilisation,
+        );
+    }
+
+    fn emit_repay_event(
+        &self,
+        caller: &ManagedAddress,
+        borrow_nonce: u64,
+        borrow_token_amount: &BigUint,
+        principal_repaid: &BigUint,
+        interest_paid: &BigUint,
+    ) {
+        let (borrow_rate, deposit_rate, utilisation) = self.get_current_rates();
+        self.repay_event(
+            caller,
+            borrow_nonce,
+            borrow_token_amount,
+            principal_repaid,
+            interest_paid,
+            self.blockchain().get_block_epoch(),
+            &borrow_rate,
+            &deposit_rate,
+            &utilisation,
+        );
+    }
+
+    fn emit_repay_interest_event(
+        &self,
+        caller: &ManagedAddress,
+        old_borrow_nonce: u64,
+        new_borrow_nonce: u64,
+        interest_paid: &BigUint,
+    ) {
+        let (borrow_rate, deposit_rate, utilisation) = self.get_current_rates();
+        self.repay_interest_event(
+            caller,
+            old_borrow_nonce,
+            new_borrow_nonce,
+            interest_paid,
+            self.blockchain().get_block_epoch(),
+            &borrow_rate,
+            &deposit_rate,
+            &utilisation,
+        );
+    }
+
+    fn emit_repay_debt_for_event(
+        &self,
+        caller: &ManagedAddress,
+        borrow_nonce: u64,
+        principal_repaid: &BigUint,
+        interest_paid: &BigUint,
+    ) {
+        let (borrow_rate, deposit_rate, utilisation) = self.get_current_rates();
+        self.repay_debt_for_event(
+            caller,
+            borrow_nonce,
+            principal_repaid,
+            interest_paid,
+            self.blockchain().get_block_epoch(),
+            &borrow_rate,
+            &deposit_rate,
+            &utilisation,
+        );
+    }
+
+    fn emit_lend_for_shares_event(
+        &self,
+        caller: &ManagedAddress,
+        amount: &BigUint,
+        shares: &BigUint,
+    ) {
+        let (borrow_rate, deposit_rate, utilisation) = self.get_current_rates();
+        self.lend_for_shares_event(
+            caller,
+            amount,
+            shares,
+            self.blockchain().get_block_epoch(),
+            &borrow_rate,
+            &deposit_rate,
+            &utilisation,
+        );
+    }
+
+    fn emit_withdraw_shares_event(
+        &self,
+        caller: &ManagedAddress,
+        amount: &BigUint,
+        shares: &BigUint,
+    ) {
+        let (borrow_rate, deposit_rate, utilisation) = self.get_current_rates();
+        self.withdraw_shares_event(
+            caller,
+            amount,
+            shares,
+            self.blockchain().get_block_epoch(),
+            &borrow_rate,
+            &deposit_rate,
+            &utilisation,
+        );
+    }
+
+    fn emit_withdraw_event(
+        &self,
+        caller: &ManagedAddress,
+        lend_nonce: u64,
+        amount: &BigUint,
+        rewards_amount: &BigUint,
+    ) {
+        let (borrow_rate, deposit_rate, utilisation) = self.get_current_rates();
+        self.withdraw_event(
+            caller,
+            lend_nonce,
+            amount,
+            rewards_amount,
+            self.blockchain().get_block_epoch(),
+            &borrow_rate,
+            &deposit_rate,
+            &utilisation,
+        );
+    }
+
+    fn emit_lender_claim_rewards_event(
+        &self,
+        caller: &ManagedAddress,
+        old_lend_nonce: u64,
+        new_lend_nonce: u64,
+        amount: &BigUint,
+        rewards_amount: &BigUint,
+    ) {
+        let (borrow_rate, deposit_rate, utilisation) = self.get_current_rates();
+        self.lender_claim_rewards_event(
+            caller,
+            old_lend_nonce,
+            new_lend_nonce,
+            amount,
+            rewards_amount,
+            self.blockchain().get_block_epoch(),
+            &borrow_rate,
+            &deposit_rate,
+            &utilisation,
+        );
+    }
+
+    fn emit_compound_rewards_event(
+        &self,
+        caller: &ManagedAddress,
+        old_lend_nonce: u64,
+        new_lend_nonce: u64,
+        amount: &BigUint,
+        rewards_amount: &BigUint,
+    ) {
+        let (borrow_rate, deposit_rate, utilisation) = self.get_current_rates();
+        self.compound_rewards_event(
+            caller,
+            old_lend_nonce,
+            new_lend_nonce,
+            amount,
+            rewards_amount,
+            self.blockchain().get_block_epoch(),
+            &borrow_rate,
+            &deposit_rate,
+            &utilisation,
+        );
+    }
+
+    fn emit_liquidate_event(
+        &self,
+        caller: &ManagedAddress,
+        borrow_nonce: u64,
+        debt_covered: &BigUint,
+        seized_amount: &BigUint,
+        principal_written_off: &BigUint,
+    ) {
+        let (borrow_rate, deposit_rate, utilisation) = self.get_current_rates();
+        self.liquidate_event(
+            caller,
+            borrow_nonce,
+            debt_covered,
+            seized_amount,
+            principal_written_off,
+            self.blockchain().get_block_epoch(),
+            &borrow_rate,
+            &deposit_rate,
+            &utilisation,
+        );
+    }
+
+    fn emit_claim_liquidation_surplus_event(
+        &self,
+        caller: &ManagedAddress,
+        borrow_nonce: u64,
+        borrow_token_amount: &BigUint,
+        surplus_amount: &BigUint,
+    ) {
+        let (borrow_rate, deposit_rate, utilisation) = self.get_current_rates();
+        self.claim_liquidation_surplus_event(
+            caller,
+            borrow_nonce,
+            borrow_token_amount,
+            surplus_amount,
+            self.blockchain().get_block_epoch(),
+            &borrow_rate,
+            &deposit_rate,
+            &utilisation,
+        );
+    }
+
+    fn emit_start_auction_event(
+        &self,
+        caller: &ManagedAddress,
+        borrow_nonce: u64,
+        start_price: &BigUint,
+        min_price: &BigUint,
+    ) {
+        let (borrow_rate, deposit_rate, utilisation) = self.get_current_rates();
+        self.start_auction_event(
+            caller,
+            borrow_nonce,
+            start_price,
+            min_price,
+            self.blockchain().get_block_epoch(),
+            &borrow_rate,
+            &deposit_rate,
+            &utilisation,
+        );
+    }
+
+    fn emit_bid_event(
+        &self,
+        caller: &ManagedAddress,
+        borrow_nonce: u64,
+        amount_paid: &BigUint,
+        amount_bought: &BigUint,
+        principal_written_off: &BigUint,
+    ) {
+        let (borrow_rate, deposit_rate, utilisation) = self.get_current_rates();
+        self.bid_event(
+            caller,
+            borrow_nonce,
+            amount_paid,
+            amount_bought,
+            principal_written_off,
+            self.blockchain().get_block_epoch(),
+            &borrow_rate,
+            &deposit_rate,
+            &utilisation,
+        );
+    }
+
+    fn emit_withdraw_protocol_revenue_event(
+        &self,
+        caller: &ManagedAddress,
+        treasury_address: &ManagedAddress,
+        amount: &BigUint,
+    ) {
+        let (borrow_rate, deposit_rate, utilisation) = self.get_current_rates();
+        self.withdraw_protocol_revenue_event(
+            caller,
+            treasury_address,
+            amount,
+            self.blockchain().get_block_epoch(),
+            &borrow_rate,
+            &deposit_rate,
+            &utilisation,
+        );
+    }
+
+    fn emit_add_to_reserves_event(
+        &self,
+        amount: &BigUint,
+        protocol_share: &BigUint,
+        reserves_share: &BigUint,
+        bad_debt_repaid: &BigUint,
+        rewards_iou_funded: &BigUint,
+    ) {
+        let (borrow_rate, deposit_rate, utilisation) = self.get_current_rates();
+        self.add_to_reserves_event(
+            amount,
+            protocol_share,
+            reserves_share,
+            bad_debt_repaid,
+            rewards_iou_funded,
+            self.blockchain().get_block_epoch(),
+            &borrow_rate,
+            &deposit_rate,
+            &utilisation,
+        );
+    }
+
+    fn get_current_rates(&self) -> (BigUint, BigUint, BigUint) {
+        (
+            self.get_borrow_rate(),
+            self.get_deposit_rate(),
+            self.get_capital_utilisation(),
+        )
+    }
+
+    #[event("lend")]
+    fn lend_event(
+        &self,
+        #[indexed] caller: &ManagedAddress,
+        #[indexed] lend_nonce: u64,
+        #[indexed] amount: &BigUint,
+        #[indexed] epoch: u64,
+        #[indexed] borrow_rate: &BigUint,
+        #[indexed] deposit_rate: &BigUint,
+        #[indexed] utilisation: &BigUint,
+    );
+
+    #[event("borrow")]
+    fn borrow_event(
+        &self,
+        #[indexed] caller: &ManagedAddress,
+        #[indexed] borrow_nonce: u64,
+        #[indexed] collateral_amount: &BigUint,
+        #[indexed] borrowed_amount: &BigUint,
+        #[indexed] epoch: u64,
+        #[indexed] borrow_rate: &BigUint,
+        #[indexed] deposit_rate: &BigUint,
+        #[indexed] utilisation: &BigUint,
+    );
+
+    #[event("addCollateral")]
+    fn add_collateral_event(
+        &self,
+        #[indexed] caller: &ManagedAddress,
+        #[indexed] old_borrow_nonce: u64,
+        #[indexed] new_borrow_nonce: u64,
+        #[indexed] collateral_amount: &BigUint,
+        #[indexed] epoch: u64,
+    );
+
+    #[event("withdrawCollateral")]
+    fn withdraw_collateral_event(
+        &self,
+        #[indexed] caller: &ManagedAddress,
+        #[indexed] old_borrow_nonce: u64,
+        #[indexed] new_borrow_nonce: u64,
+        #[indexed] collateral_amount: &BigUint,
+        #[indexed] epoch: u64,
+    );
+
+    #[event("mergeBorrowTokens")]
+    fn merge_borrow_tokens_event(
+        &self,
+        #[indexed] caller: &ManagedAddress,
+        #[indexed] new_borrow_nonce: u64,
+        #[indexed] liquid_staking_amount: &BigUint,
+        #[indexed] borrowed_amount: &BigUint,
+        #[indexed] epoch: u64,
+    );
+
+    #[event("splitBorrowTokens")]
+    fn split_borrow_tokens_event(
+        &self,
+        #[indexed] caller: &ManagedAddress,
+        #[indexed] old_borrow_nonce: u64,
+        #[indexed] new_borrow_nonce: u64,
+        #[indexed] liquid_staking_amount: &BigUint,
+        #[indexed] borrowed_amount: &BigUint,
+        #[indexed] epoch: u64,
+    );
+
+    #[event("migrateBorrowPosition")]
+    fn migrate_borrow_position_event(
+        &self,
+        #[indexed] borrow_nonce: u64,
+        #[indexed] borrow_token_amount: &BigUint,
+        #[indexed] borrowed_amount: &BigUint,
+        #[indexed] total_debt: &BigUint,
+        #[indexed] epoch: u64,
+    );
+
+    #[event("borrowMore")]
+    fn borrow_more_event(
+        &self,
+        #[indexed] caller: &ManagedAddress,
+        #[indexed] old_borrow_nonce: u64,
+        #[indexed] new_borrow_nonce: u64,
+        #[indexed] borrowed_amount: &BigUint,
+        #[indexed] epoch: u64,
+        #[indexed] borrow_rate: &BigUint,
+        #[indexed] deposit_rate: &BigUint,
+        #[indexed] utilisation: &BigUint,
+    );
+
+    #[event("repay")]
+    fn repay_event(
+        &self,
+        #[indexed] caller: &ManagedAddress,
+        #[indexed] borrow_nonce: u64,
+        #[indexed] borrow_token_amount: &BigUint,
+        #[indexed] principal_repaid: &BigUint,
+        #[indexed] interest_paid: &BigUint,
+        #[indexed] epoch: u64,
+        #[indexed] borrow_rate: &BigUint,
+        #[indexed] deposit_rate: &BigUint,
+        #[indexed] utilisation: &BigUint,
+    );
+
+    #[event("repayInterest")]
+    fn repay_interest_event(
+        &self,
+        #[indexed] caller: &ManagedAddress,
+        #[indexed] old_borrow_nonce: u64,
+        #[indexed] new_borrow_nonce: u64,
+        #[indexed] interest_paid: &BigUint,
+        #[indexed] epoch: u64,
+        #[indexed] borrow_rate: &BigUint,
+        #[indexed] deposit_rate: &BigUint,
+        #[indexed] utilisation: &BigUint,
+    );
+
+    #[event("repayDebtFor")]
+    fn repay_debt_for_event(
+        &self,
+        #[indexed] caller: &ManagedAddress,
+        #[indexed] borrow_nonce: u64,
+        #[indexed] principal_repaid: &BigUint,
+        #[indexed] interest_paid: &BigUint,
+        #[indexed] epoch: u64,
+        #[indexed] borrow_rate: &BigUint,
+        #[indexed] deposit_rate: &BigUint,
+        #[indexed] utilisation: &BigUint,
+    );
+
+    #[event("withdraw")]
+    fn withdraw_event(
+        &self,
+        #[indexed] caller: &ManagedAddress,
+        #[indexed] lend_nonce: u64,
+        #[indexed] amount: &BigUint,
+        #[indexed] rewards_amount: &BigUint,
+        #[indexed] epoch: u64,
+        #[indexed] borrow_rate: &BigUint,
+        #[indexed] deposit_rate: &BigUint,
+        #[indexed] utilisation: &BigUint,
+    );
+
+    #[event("withdrawQueued")]
+    fn withdraw_queued_event(
+        &self,
+        #[indexed] caller: &ManagedAddress,
+        #[indexed] ticket_id: u64,
+        #[indexed] lend_nonce: u64,
+        #[indexed] amount: &BigUint,
+        #[indexed] epoch: u64,
+    );
+
+    #[event("queuedWithdrawCancelled")]
+    fn queued_withdraw_cancelled_event(
+        &self,
+        #[indexed] caller: &ManagedAddress,
+        #[indexed] ticket_id: u64,
+        #[indexed] amount_left: &BigUint,
+        #[indexed] epoch: u64,
+    );
+
+    #[event("lendForShares")]
+    fn lend_for_shares_event(
+        &self,
+        #[indexed] caller: &ManagedAddress,
+        #[indexed] amount: &BigUint,
+        #[indexed] shares: &BigUint,
+        #[indexed] epoch: u64,
+        #[indexed] borrow_rate: &BigUint,
+        #[indexed] deposit_rate: &BigUint,
+        #[indexed] utilisation: &BigUint,
+    );
+
+    #[event("withdrawShares")]
+    fn withdraw_shares_event(
+        &self,
+        #[indexed] caller: &ManagedAddress,
+        #[indexed] amount: &BigUint,
+        #[indexed] shares: &BigUint,
+        #[indexed] epoch: u64,
+        #[indexed] borrow_rate: &BigUint,
+        #[indexed] deposit_rate: &BigUint,
+        #[indexed] utilisation: &BigUint,
+    );
+
+    #[event("lenderClaimRewards")]
+    fn lender_claim_rewards_event(
+        &self,
+        #[indexed] caller: &ManagedAddress,
+        #[indexed] old_lend_nonce: u64,
+        #[indexed] new_lend_nonce: u64,
+        #[indexed] amount: &BigUint,
+        #[indexed] rewards_amount: &BigUint,
+        #[indexed] epoch: u64,
+        #[indexed] borrow_rate: &BigUint,
+        #[indexed] deposit_rate: &BigUint,
+        #[indexed] utilisation: &BigUint,
+    );
+
+    #[event("rewardsIouIssued")]
+    fn rewards_iou_issued_event(
+        &self,
+        #[indexed] holder: &ManagedAddress,
+        #[indexed] amount: &BigUint,
+        #[indexed] epoch: u64,
+    );
+
+    #[event("claimRewardsIou")]
+    fn claim_rewards_iou_event(
+        &self,
+        #[indexed] caller: &ManagedAddress,
+        #[indexed] amount: &BigUint,
+        #[indexed] epoch: u64,
+    );
+
+    #[event("claimWithdrawQueuePayout")]
+    fn claim_withdraw_queue_payout_event(
+        &self,
+        #[indexed] caller: &ManagedAddress,
+        #[indexed] amount: &BigUint,
+        #[indexed] epoch: u64,
+    );
+
+    #[event("compoundRewards")]
+    fn compound_rewards_event(
+        &self,
+        #[indexed] caller: &ManagedAddress,
+        #[indexed] old_lend_nonce: u64,
+        #[indexed] new_lend_nonce: u64,
+        #[indexed] amount: &BigUint,
+        #[indexed] rewards_amount: &BigUint,
+        #[indexed] epoch: u64,
+        #[indexed] borrow_rate: &BigUint,
+        #[indexed] deposit_rate: &BigUint,
+        #[indexed] utilisation: &BigUint,
+    );
+
+    #[event("mergeLendTokens")]
+    fn merge_lend_tokens_event(
+        &self,
+        #[indexed] caller: &ManagedAddress,
+        #[indexed] new_lend_nonce: u64,
+        #[indexed] amount: &BigUint,
+        #[indexed] rewards_amount: &BigUint,
+        #[indexed] epoch: u64,
+    );
+
+    #[event("autoCompoundRewards")]
+    fn auto_compound_rewards_event(
+        &self,
+        #[indexed] caller: &ManagedAddress,
+        #[indexed] lend_nonce: u64,
+        #[indexed] lend_token_supply: &BigUint,
+        #[indexed] rewards_amount: &BigUint,
+        #[indexed] epoch: u64,
+    );
+
+    #[event("liquidate")]
+    fn liquidate_event(
+        &self,
+        #[indexed] caller: &ManagedAddress,
+        #[indexed] borrow_nonce: u64,
+        #[indexed] debt_covered: &BigUint,
+        #[indexed] seized_amount: &BigUint,
+        #[indexed] principal_written_off: &BigUint,
+        #[indexed] epoch: u64,
+        #[indexed] borrow_rate: &BigUint,
+        #[indexed] deposit_rate: &BigUint,
+        #[indexed] utilisation: &BigUint,
+    );
+
+    #[event("claimLiquidationSurplus")]
+    fn claim_liquidation_surplus_event(
+        &self,
+        #[indexed] caller: &ManagedAddress,
+        #[indexed] borrow_nonce: u64,
+        #[indexed] borrow_token_amount: &BigUint,
+        #[indexed] surplus_amount: &BigUint,
+        #[indexed] epoch: u64,
+        #[indexed] borrow_rate: &BigUint,
+        #[indexed] deposit_rate: &BigUint,
+        #[indexed] utilisation: &BigUint,
+    );
+
+    #[event("startAuction")]
+    fn start_auction_event(
+        &self,
+        #[indexed] caller: &ManagedAddress,
+        #[indexed] borrow_nonce: u64,
+        #[indexed] start_price: &BigUint,
+        #[indexed] min_price: &BigUint,
+        #[indexed] epoch: u64,
+        #[indexed] borrow_rate: &BigUint,
+        #[indexed] deposit_rate: &BigUint,
+        #[indexed] utilisation: &BigUint,
+    );
+
+    #[event("bid")]
+    fn bid_event(
+        &self,
+        #[indexed] caller: &ManagedAddress,
+        #[indexed] borrow_nonce: u64,
+        #[indexed] amount_paid: &BigUint,
+        #[indexed] amount_bought: &BigUint,
+        #[indexed] principal_written_off: &BigUint,
+        #[indexed] epoch: u64,
+        #[indexed] borrow_rate: &BigUint,
+        #[indexed] deposit_rate: &BigUint,
+        #[indexed] utilisation: &BigUint,
+    );
+
+    #[event("auctionCancelled")]
+    fn auction_cancelled_event(
+        &self,
+        #[indexed] caller: &ManagedAddress,
+        #[indexed] borrow_nonce: u64,
+        #[indexed] epoch: u64,
+    );
+
+    #[event("withdrawProtocolRevenue")]
+    fn withdraw_protocol_revenue_event(
+        &self,
+        #[indexed] caller: &ManagedAddress,
+        #[indexed] treasury_address: &ManagedAddress,
+        #[indexed] amount: &BigUint,
+        #[indexed] epoch: u64,
+        #[indexed] borrow_rate: &BigUint,
+        #[indexed] deposit_rate: &BigUint,
+        #[indexed] utilisation: &BigUint,
+    );
+
+    #[event("addToReserves")]
+    fn add_to_reserves_event(
+        &self,
+        #[indexed] amount: &BigUint,
+        #[indexed] protocol_share: &BigUint,
+        #[indexed] reserves_share: &BigUint,
+        #[indexed] bad_debt_repaid: &BigUint,
+        #[indexed] rewards_iou_funded: &BigUint,
+        #[indexed] epoch: u64,
+        #[indexed] borrow_rate: &BigUint,
+        #[indexed] deposit_rate: &BigUint,
+        #[indexed] utilisation: &BigUint,
+    );
+
+    #[event("claimStakingRewards")]
+    fn claim_staking_rewards_event(
+        &self,
+        #[indexed] caller: &ManagedAddress,
+        #[indexed] epoch: u64,
+        #[indexed] first_position_id: u64,
+        #[indexed] last_position_id: u64,
+    );
+
+    #[event("stakingRewardsClaimed")]
+    fn staking_rewards_claimed_event(
+        &self,
+        #[indexed] epoch: u64,
+        #[indexed] last_position_id: u64,
+        #[indexed] reward_amount: &BigUint,
+        #[indexed] completed: bool,
+    );
+
+    #[event("convertStakingTokenToStablecoin")]
+    fn convert_staking_token_event(
+        &self,
+        #[indexed] caller: &ManagedAddress,
+        #[indexed] epoch: u64,
+        #[indexed] staking_token_amount: &BigUint,
+        #[indexed] stablecoin_amount: &BigUint,
+    );
+
+    #[event("missingRewardsChanged")]
+    fn missing_rewards_changed_event(
+        &self,
+        #[indexed] epoch: u64,
+        #[indexed] old_missing_rewards: &BigUint,
+        #[indexed] new_missing_rewards: &BigUint,
+    );
+
+    #[event("penaltyPerLendTokenChanged")]
+    fn penalty_per_lend_token_changed_event(
+        &self,
+        #[indexed] epoch: u64,
+        #[indexed] old_penalty_per_lend_token: &BigUint,
+        #[indexed] new_penalty_per_lend_token: &BigUint,
+    );
+
+    #[event("paramChangeQueued")]
+    fn param_change_queued_event(
+        &self,
+        #[indexed] change_id: u64,
+        #[indexed] unlock_epoch: u64,
+        param_change: &ParamChange<Self::Api>,
+    );
+
+    #[event("paramChangeExecuted")]
+    fn param_change_executed_event(
+        &self,
+        #[indexed] change_id: u64,
+        #[indexed] epoch: u64,
+        param_change: &ParamChange<Self::Api>,
+    );
+
+    #[event("paramChangeCancelled")]
+    fn param_change_cancelled_event(
+        &self,
+        #[indexed] change_id: u64,
+        #[indexed] epoch: u64,
+        param_change: &ParamChange<Self::Api>,
+    );
+}
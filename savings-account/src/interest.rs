@@ -0,0 +1,88 @@
+elrond_wasm::imports!();
+
+#[elrond_wasm::module]
+pub trait InterestModule:
+    crate::math::MathModule + crate::common_storage::CommonStorageModule
+{
+    /// Compounds the borrow index and the total debt up to the current epoch or second.
+    /// Called by every endpoint that changes the lent or borrowed amounts,
+    /// but anyone may call it to keep the rates up to date.
+    #[endpoint(accrueInterest)]
+    fn accrue_interest(&self) -> BigUint {
+        let borrow_index = self.borrow_index().get();
+        let (time_passed, periods_in_year) = self.get_time_passed_since(
+            self.last_borrow_index_update_epoch().get(),
+            self.last_borrow_index_update_timestamp().get(),
+        );
+        if time_passed == 0 {
+            return borrow_index;
+        }
+
+        // both checkpoints are kept, so the time basis can be changed at any point
+        self.last_borrow_index_update_epoch()
+            .set(self.blockchain().get_block_epoch());
+        self.last_borrow_index_update_timestamp()
+            .set(self.blockchain().get_block_timestamp());
+
+        // no interest accrues while nothing is borrowed
+        let borrowed_amount = self.borrowed_amount().get();
+        if borrowed_amount == 0u32 {
+            return borrow_index;
+        }
+
+        let total_debt = self.total_debt().get();
+        let borrow_rate = self.get_borrow_rate();
+        let new_borrow_index =
+            self.compute_borrow_index(&borrow_index, &borrow_rate, time_passed, periods_in_year);
+        let new_total_debt = &total_debt * &new_borrow_index / &borrow_index;
+
+        self.borrow_index().set(&new_borrow_index);
+        self.total_debt().set(&new_total_debt);
+
+        new_borrow_index
+    }
+
+    /// Borrow index as it would be after accruing interest, without saving it
+    fn get_current_borrow_index(&self) -> BigUint {
+        let borrow_index = self.borrow_index().get();
+        let (time_passed, periods_in_year) = self.get_time_passed_since(
+            self.last_borrow_index_update_epoch().get(),
+            self.last_borrow_index_update_timestamp().get(),
+        );
+        if time_passed == 0 {
+            return borrow_index;
+        }
+
+        let borrowed_amount = self.borrowed_amount().get();
+        if borrowed_amount == 0u32 {
+            return borrow_index;
+        }
+
+        let borrow_rate = self.get_borrow_rate();
+        self.compute_borrow_index(&borrow_index, &borrow_rate, time_passed, periods_in_year)
+    }
+
+    /// Total debt as it would be after accruing interest, without saving it
+    fn get_current_total_debt(&self) -> BigUint {
+        let total_debt = self.total_debt().get();
+        let borrow_index = self.borrow_index().get();
+        let current_borrow_index = self.get_current_borrow_index();
+
+        total_debt * current_borrow_index / borrow_index
+    }
+
+    fn increase_total_debt(&self, amount: &BigUint) {
+        self.total_debt().update(|total_debt| *total_debt += amount);
+    }
+
+    fn decrease_total_debt(&self, amount: &BigUint) {
+        self.total_debt().update(|total_debt| {
+            // each position's debt is rounded down, so the sum may be slightly off
+            if *total_debt < *amount {
+                *total_debt = BigUint::zero();
+            } else {
+                *total_debt -= amount;
+            }
+        });
+    }
+}
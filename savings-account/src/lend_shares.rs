@@ -0,0 +1,46 @@
+elrond_wasm::imports!();
+
+use crate::math::DEFAULT_DECIMALS;
+
+/// Storage for lending through the fungible share token.
+/// All the stablecoins lent this way are pooled, and the pool's rewards are added to it,
+/// so the stablecoin value of each share grows over time.
+#[elrond_wasm::module]
+pub trait LendSharesModule {
+    /// Stablecoins per share, with `DEFAULT_DECIMALS` precision
+    fn compute_share_price(&self, shares_lent_amount: &BigUint) -> BigUint {
+        let share_supply = self.share_supply().get();
+        if share_supply == 0u32 {
+            return BigUint::from(DEFAULT_DECIMALS);
+        }
+
+        shares_lent_amount * &BigUint::from(DEFAULT_DECIMALS) / share_supply
+    }
+
+    fn compute_shares_for_amount(&self, shares_lent_amount: &BigUint, amount: &BigUint) -> BigUint {
+        let share_supply = self.share_supply().get();
+        if share_supply == 0u32 {
+            return amount.clone();
+        }
+
+        amount * &share_supply / shares_lent_amount
+    }
+
+    fn compute_amount_for_shares(&self, shares_lent_amount: &BigUint, shares: &BigUint) -> BigUint {
+        let share_supply = self.share_supply().get();
+
+        shares * shares_lent_amount / share_supply
+    }
+
+    #[view(getShareSupply)]
+    #[storage_mapper("shareSupply")]
+    fn share_supply(&self) -> SingleValueMapper<BigUint>;
+
+    /// The pool's stablecoins, including the rewards added so far. Also counted in `lentAmount`.
+    #[view(getSharesLentAmount)]
+    #[storage_mapper("sharesLentAmount")]
+    fn shares_lent_amount(&self) -> SingleValueMapper<BigUint>;
+
+    #[storage_mapper("sharesRewardsIndex")]
+    fn shares_rewards_index(&self) -> SingleValueMapper<BigUint>;
+}
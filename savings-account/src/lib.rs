@@ -2,31 +2,52 @@
 
 elrond_wasm::imports!();
 
+pub mod auction;
 pub mod common_storage;
+pub mod config;
+pub mod events;
+pub mod interest;
+pub mod lend_shares;
+pub mod liquidation;
 pub mod math;
 pub mod model;
 pub mod ongoing_operation;
+pub mod position_management;
 pub mod price_aggregator_proxy;
 pub mod staking_positions_mapper;
 pub mod staking_rewards;
 pub mod tokens;
+pub mod treasury;
+pub mod views;
+pub mod withdraw_queue;
 
-use math::DEFAULT_DECIMALS;
+use liquidation::POSITION_LIQUIDATED_ERR_MSG;
+use math::{BASE_PRECISION, DEFAULT_DECIMALS};
 use model::*;
-use price_aggregator_proxy::*;
+use ongoing_operation::LoopOp;
 
 static REPAY_INVALID_PAYMENTS_ERR_MSG: &[u8] =
-    b"Must send exactly 2 types of tokens: Borrow SFTs and Stablecoins";
+    b"Must send Borrow SFTs, followed by a single Stablecoin payment";
 static NO_REWARDS_ERR_MSG: &[u8] = b"No rewards to claim";
 
 #[elrond_wasm::contract]
 pub trait SavingsAccount:
-    math::MathModule
+    interest::InterestModule
+    + math::MathModule
     + ongoing_operation::OngoingOperationModule
     + price_aggregator_proxy::PriceAggregatorModule
     + staking_rewards::StakingRewardsModule
     + tokens::TokensModule
+    + treasury::TreasuryModule
+    + views::ViewsModule
     + common_storage::CommonStorageModule
+    + config::ConfigModule
+    + events::EventsModule
+    + liquidation::LiquidationModule
+    + position_management::PositionManagementModule
+    + auction::AuctionModule
+    + withdraw_queue::WithdrawQueueModule
+    + lend_shares::LendSharesModule
 {
     #[allow(clippy::too_many_arguments)]
     #[init]
@@ -40,11 +61,15 @@ pub trait SavingsAccount:
         dex_swap_sc_address: ManagedAddress,
         price_aggregator_address: ManagedAddress,
         loan_to_value_percentage: BigUint,
-        lender_rewards_percentage_per_epoch: BigUint,
         base_borrow_rate: BigUint,
         borrow_rate_under_opt_factor: BigUint,
         borrow_rate_over_opt_factor: BigUint,
         optimal_utilisation: BigUint,
+        reserve_factor: BigUint,
+        liquidation_threshold: BigUint,
+        close_factor: BigUint,
+        liquidation_bonus: BigUint,
+        param_change_delay_epochs: u64,
     ) {
         require!(
             stablecoin_token_id.is_valid_esdt_identifier(),
@@ -71,6 +96,18 @@ pub trait SavingsAccount:
                 .is_smart_contract(&price_aggregator_address),
             "Invalid Price Aggregator SC address"
         );
+        require!(param_change_delay_epochs > 0, "Invalid param change delay");
+
+        let liquidation_params = LiquidationParams {
+            liquidation_threshold,
+            close_factor,
+            liquidation_bonus,
+        };
+        self.require_valid_liquidation_params(&liquidation_params);
+        self.require_valid_loan_to_value_percentage(
+            &loan_to_value_percentage,
+            &liquidation_params.liquidation_threshold,
+        );
 
         self.stablecoin_token_id().set(&stablecoin_token_id);
         self.liquid_staking_token_id().set(&liquid_staking_token_id);
@@ -84,29 +121,49 @@ pub trait SavingsAccount:
 
         self.loan_to_value_percentage()
             .set(&loan_to_value_percentage);
-        self.lender_rewards_percentage_per_epoch()
-            .set(&lender_rewards_percentage_per_epoch);
 
         let pool_params = PoolParams {
             base_borrow_rate,
             borrow_rate_under_opt_factor,
             borrow_rate_over_opt_factor,
             optimal_utilisation,
+            reserve_factor,
         };
+        self.require_valid_pool_params(&pool_params);
         self.pool_params().set(&pool_params);
 
+        self.liquidation_params().set(&liquidation_params);
+
+        self.param_change_delay_epochs()
+            .set(param_change_delay_epochs);
+
         let current_epoch = self.blockchain().get_block_epoch();
         self.last_staking_rewards_claim_epoch().set(&current_epoch);
+        self.borrow_index().set(&BigUint::from(BASE_PRECISION));
+        self.last_borrow_index_update_epoch().set(&current_epoch);
+        self.last_borrow_index_update_timestamp()
+            .set(self.blockchain().get_block_timestamp());
 
         // init staking position list
         self.staking_positions().init_mapper();
     }
 
+    /// Lends stablecoins. If one of the lock periods set by the owner is chosen,
+    /// the LEND tokens can't be withdrawn before the lock ends, and earn boosted rewards.
+    /// A lock period of 0 means no lock.
+    /// With `auto_compound` set, the rewards of the LEND tokens are added to their principal
+    /// whenever the tokens are claimed, merged or partially served by the withdraw queue,
+    /// and compounded in place by `processAutoCompound` while they are not locked.
     #[payable("*")]
     #[endpoint]
-    fn lend(&self) -> LendResultType<Self::Api> {
+    fn lend(
+        &self,
+        opt_lock_epochs: OptionalValue<u64>,
+        opt_auto_compound: OptionalValue<bool>,
+    ) -> LendResultType<Self::Api> {
         self.require_no_ongoing_operation();
 
+        self.accrue_interest();
         self.update_global_lender_rewards();
 
         let (payment_amount, payment_token) = self.call_value().payment_token_pair();
@@ -116,9 +173,29 @@ pub trait SavingsAccount:
             "May only lend stablecoins"
         );
 
+        let mut lend_metadata = self.get_current_lend_metadata();
+        lend_metadata.auto_compound = match opt_auto_compound {
+            OptionalValue::Some(auto_compound) => auto_compound,
+            OptionalValue::None => false,
+        };
+        let lock_epochs = match opt_lock_epochs {
+            OptionalValue::Some(lock_epochs) => lock_epochs,
+            OptionalValue::None => 0,
+        };
+        if lock_epochs > 0 {
+            let boost = match self.lock_periods().get(&lock_epochs) {
+                Some(boost) => boost,
+                None => sc_panic!("Invalid lock period"),
+            };
+            lend_metadata.lock_end_epoch = lend_metadata.lend_epoch + lock_epochs;
+            lend_metadata.boost = boost;
+
+            self.increase_lock_boost_amount(&payment_amount, &lend_metadata);
+        }
+
         let caller = self.blockchain().get_caller();
-        let current_epoch = self.blockchain().get_block_epoch();
-        let lend_nonce = self.get_or_create_lend_token_nonce(current_epoch);
+        let lend_nonce = self.get_or_create_lend_nonce_with_rewards_index(&lend_metadata);
+        self.increase_auto_compound_supply(lend_nonce, &lend_metadata, &payment_amount);
         let new_lend_tokens =
             self.lend_token()
                 .nft_add_quantity_and_send(&caller, lend_nonce, payment_amount);
@@ -126,12 +203,14 @@ pub trait SavingsAccount:
         self.lent_amount()
             .update(|lent_amount| *lent_amount += &new_lend_tokens.amount);
 
+        self.emit_lend_event(&caller, lend_nonce, &new_lend_tokens.amount);
+
         new_lend_tokens
     }
 
     #[payable("*")]
     #[endpoint]
-    fn borrow(&self) -> BorrowResultType<Self::Api> {
+    fn borrow(&self, opt_borrow_amount: OptionalValue<BigUint>) -> BorrowResultType<Self::Api> {
         self.require_no_ongoing_operation();
 
         let payment: EsdtTokenPayment<Self::Api> = self.call_value().payment();
@@ -146,11 +225,25 @@ pub trait SavingsAccount:
             self.compute_staking_position_value(&staked_token_value_in_dollars, &payment.amount);
 
         let loan_to_value_percentage = self.loan_to_value_percentage().get();
-        let borrow_value =
+        let max_borrow_value =
             self.compute_borrow_amount(&loan_to_value_percentage, &staking_position_value);
+        require!(max_borrow_value > 0, "Deposit amount too low");
 
-        require!(borrow_value > 0, "Deposit amount too low");
+        let borrow_value = match opt_borrow_amount {
+            OptionalValue::Some(borrow_amount) => {
+                require!(borrow_amount > 0u32, "Invalid borrow amount");
+                require!(
+                    borrow_amount <= max_borrow_value,
+                    "Borrow amount above loan to value limit"
+                );
 
+                borrow_amount
+            }
+            OptionalValue::None => max_borrow_value,
+        };
+
+        let borrow_index = self.accrue_interest();
+        self.update_global_lender_rewards();
         let staking_pos_id = self
             .staking_positions()
             .add_staking_position(payment.token_nonce);
@@ -158,139 +251,152 @@ pub trait SavingsAccount:
             staking_position_id: staking_pos_id,
             borrow_epoch: self.blockchain().get_block_epoch(),
             staked_token_value_in_dollars_at_borrow: staked_token_value_in_dollars,
+            borrowed_amount: borrow_value.clone(),
+            borrow_index,
         };
 
         let caller = self.blockchain().get_caller();
         let borrow_tokens = self.borrow_token().nft_create_and_send(
             &caller,
-            payment.amount,
+            payment.amount.clone(),
             &borrow_token_attributes,
         );
-
-        let lent_amount = self.lent_amount().get();
-        self.borrowed_amount().update(|total_borrowed| {
-            *total_borrowed += &borrow_value;
-            require!(
-                *total_borrowed <= lent_amount,
-                "Not have enough funds to lend"
-            );
+        let mut collateral = ManagedVec::new();
+        collateral.push(PositionCollateral {
+            staking_position_id: staking_pos_id,
+            liquid_staking_amount: payment.amount.clone(),
         });
+        self.borrow_position(borrow_tokens.token_nonce)
+            .set(&BorrowPosition {
+                metadata: borrow_token_attributes,
+                borrow_token_amount: payment.amount.clone(),
+                liquid_staking_amount: payment.amount,
+                collateral,
+                borrowed_amount: borrow_value.clone(),
+            });
+
+        self.increase_total_debt(&borrow_value);
+        self.increase_borrowed_amount(&borrow_value);
+
+        self.emit_borrow_event(
+            &caller,
+            borrow_tokens.token_nonce,
+            &borrow_tokens.amount,
+            &borrow_value,
+        );
 
         let stablecoins_payment = self.send_stablecoins(&caller, borrow_value);
 
         (borrow_tokens, stablecoins_payment).into()
     }
 
+    /// Repays any number of Borrow SFT payments, settled in order, with a single stablecoin payment,
+    /// which must be the last one. Stablecoins left over are sent back.
     #[payable("*")]
     #[endpoint]
     fn repay(&self) -> RepayResultType<Self::Api> {
         self.require_no_ongoing_operation();
 
         let payments = self.call_value().all_esdt_transfers();
-        require!(payments.len() == 2, REPAY_INVALID_PAYMENTS_ERR_MSG);
+        let nr_payments = payments.len();
+        require!(nr_payments >= 2, REPAY_INVALID_PAYMENTS_ERR_MSG);
 
-        let first_payment: EsdtTokenPayment<Self::Api> = payments.get(0);
-        let second_payment: EsdtTokenPayment<Self::Api> = payments.get(1);
-
-        let borrow_token_mapper = self.borrow_token();
+        let stablecoin_payment: EsdtTokenPayment<Self::Api> = payments.get(nr_payments - 1);
         let stablecoin_token_id = self.stablecoin_token_id().get();
-
-        borrow_token_mapper.require_same_token(&first_payment.token_identifier);
         require!(
-            second_payment.token_identifier == stablecoin_token_id,
+            stablecoin_payment.token_identifier == stablecoin_token_id,
             REPAY_INVALID_PAYMENTS_ERR_MSG,
         );
 
-        let borrow_token_amount = &first_payment.amount;
-        let borrow_token_nonce = first_payment.token_nonce;
-        let stablecoin_amount = &second_payment.amount;
-
-        let borrowed_amount = self.borrowed_amount().get();
-        let lent_amount = self.lent_amount().get();
-        let current_utilisation = self.compute_capital_utilisation(&borrowed_amount, &lent_amount);
-
-        let pool_params = self.pool_params().get();
-        let borrow_rate = self.compute_borrow_rate(
-            &pool_params.base_borrow_rate,
-            &pool_params.borrow_rate_under_opt_factor,
-            &pool_params.borrow_rate_over_opt_factor,
-            &pool_params.optimal_utilisation,
-            &current_utilisation,
-        );
-
-        let staked_token_value_in_dollars = self.get_staked_token_value_in_dollars();
-        let staking_position_current_value = self
-            .compute_staking_position_value(&staked_token_value_in_dollars, borrow_token_amount);
+        let caller = self.blockchain().get_caller();
+        let current_borrow_index = self.accrue_interest();
+        self.update_global_lender_rewards();
+        let mut total_stablecoins_needed = BigUint::zero();
+        let mut liquid_staking_payments = ManagedVec::new();
+        for i in 0..nr_payments - 1 {
+            let borrow_payment: EsdtTokenPayment<Self::Api> = payments.get(i);
+            let (position_liquid_staking_payments, stablecoins_needed) =
+                self.repay_borrow_tokens(&caller, &borrow_payment, &current_borrow_index);
+
+            total_stablecoins_needed += stablecoins_needed;
+            for liquid_staking_payment in position_liquid_staking_payments.iter() {
+                liquid_staking_payments.push(liquid_staking_payment);
+            }
+        }
 
-        let borrow_metadata: BorrowMetadata<Self::Api> =
-            borrow_token_mapper.get_token_attributes(borrow_token_nonce);
-        let debt = self.compute_debt(
-            &staking_position_current_value,
-            borrow_metadata.borrow_epoch,
-            &borrow_rate,
-        );
-        let total_stablecoins_needed = &staking_position_current_value + &debt;
+        // nothing is repaid if the stablecoins don't cover all the positions
         require!(
-            stablecoin_amount >= &total_stablecoins_needed,
+            stablecoin_payment.amount >= total_stablecoins_needed,
             "Not enough stablecoins paid to cover the debt"
         );
 
-        // even if the value of the staked token changed between borrow and repay time,
-        // we still need to map the repaid value to the initial value at borrow time,
-        // this is done to keep the borrowed_amount valid
-        let borrow_amount_repaid = self.compute_staking_position_value(
-            &borrow_metadata.staked_token_value_in_dollars_at_borrow,
-            borrow_token_amount,
-        );
-        self.borrowed_amount()
-            .update(|borrowed_amount| *borrowed_amount -= &borrow_amount_repaid);
-
-        // the "debt" and any additional value paid is added to the reserves
-        if total_stablecoins_needed > borrow_amount_repaid {
-            let extra_reserves = &total_stablecoins_needed - &borrow_amount_repaid;
-            self.stablecoin_reserves()
-                .update(|stablecoin_reserves| *stablecoin_reserves += extra_reserves);
-        }
-
-        borrow_token_mapper.nft_burn(borrow_token_nonce, borrow_token_amount);
-
-        let caller = self.blockchain().get_caller();
-        let extra_stablecoins_paid = stablecoin_amount - &total_stablecoins_needed;
+        let extra_stablecoins_paid = stablecoin_payment.amount - &total_stablecoins_needed;
         let extra_stablecoins_payment = if extra_stablecoins_paid > 0u32 {
             self.send_stablecoins(&caller, extra_stablecoins_paid)
         } else {
             EsdtTokenPayment::new(stablecoin_token_id, 0, BigUint::zero())
         };
 
-        let mut staking_positions_mapper = self.staking_positions();
-        let liquid_staking_token_id = self.liquid_staking_token_id().get();
-        let liquid_staking_nonce = staking_positions_mapper
-            .get_staking_position(borrow_metadata.staking_position_id)
-            .liquid_staking_nonce;
+        (liquid_staking_payments, extra_stablecoins_payment).into()
+    }
+
+    /// Burns the Borrow SFTs, releases the collateral backing them
+    /// and returns the amount of stablecoins needed to cover their debt
+    fn repay_borrow_tokens(
+        &self,
+        caller: &ManagedAddress,
+        payment: &EsdtTokenPayment<Self::Api>,
+        current_borrow_index: &BigUint,
+    ) -> (ManagedVec<EsdtTokenPayment<Self::Api>>, BigUint) {
+        let borrow_token_mapper = self.borrow_token();
+        borrow_token_mapper.require_same_token(&payment.token_identifier);
+
+        let borrow_token_amount = &payment.amount;
+        let borrow_token_nonce = payment.token_nonce;
+
+        self.require_not_auctioned(borrow_token_nonce);
+
+        let mut borrow_position = self.get_borrow_position(borrow_token_nonce);
+        require!(
+            borrow_position.borrowed_amount > 0u32,
+            POSITION_LIQUIDATED_ERR_MSG
+        );
+
+        let (liquid_staking_amount, borrow_amount_repaid, debt) =
+            self.get_repay_amounts(&borrow_position, borrow_token_amount, current_borrow_index);
+        let stablecoins_needed = &borrow_amount_repaid + &debt;
 
-        let liquid_staking_tokens_for_nonce = self
-            .blockchain()
-            .get_sc_balance(&liquid_staking_token_id, liquid_staking_nonce);
+        self.borrowed_amount()
+            .update(|borrowed_amount| *borrowed_amount -= &borrow_amount_repaid);
+        self.decrease_total_debt(&stablecoins_needed);
 
-        // no tokens left after transfer, so we clear the entry
-        if &liquid_staking_tokens_for_nonce == borrow_token_amount {
-            staking_positions_mapper.remove_staking_position(borrow_metadata.staking_position_id);
+        // the "debt" is split between the reserves and the protocol revenue
+        self.add_to_reserves(debt.clone());
+
+        borrow_position.borrowed_amount -= &borrow_amount_repaid;
+        borrow_position.borrow_token_amount -= borrow_token_amount;
+        let released_collateral =
+            self.take_position_collateral(&mut borrow_position, &liquid_staking_amount);
+        if borrow_position.borrow_token_amount == 0u32 {
+            self.borrow_position(borrow_token_nonce).clear();
+        } else {
+            self.borrow_position(borrow_token_nonce)
+                .set(&borrow_position);
         }
 
-        self.send().direct(
-            &caller,
-            &liquid_staking_token_id,
-            liquid_staking_nonce,
+        borrow_token_mapper.nft_burn(borrow_token_nonce, borrow_token_amount);
+
+        self.emit_repay_event(
+            caller,
+            borrow_token_nonce,
             borrow_token_amount,
-            &[],
+            &borrow_amount_repaid,
+            &debt,
         );
 
-        let liquid_staking_payment = EsdtTokenPayment::new(
-            liquid_staking_token_id,
-            liquid_staking_nonce,
-            borrow_token_amount.clone(),
-        );
-        (liquid_staking_payment, extra_stablecoins_payment).into()
+        let liquid_staking_payments = self.send_position_collateral(caller, &released_collateral);
+
+        (liquid_staking_payments, stablecoins_needed)
     }
 
     #[payable("*")]
@@ -301,36 +407,498 @@ pub trait SavingsAccount:
     ) -> WithdrawResultType<Self::Api> {
         self.require_no_ongoing_operation();
 
+        self.accrue_interest();
         self.update_global_lender_rewards();
 
         let payment: EsdtTokenPayment<Self::Api> = self.call_value().payment();
         let lend_token_mapper = self.lend_token();
         lend_token_mapper.require_same_token(&payment.token_identifier);
 
-        let lend_metadata: LendMetadata =
-            lend_token_mapper.get_token_attributes(payment.token_nonce);
-
+        // liquidity is reserved for the queued requests first
         let lent_amount = self.lent_amount().get();
-        let borrowed_amount = self.borrowed_amount().get();
-        let leftover_lend_amount = lent_amount - borrowed_amount;
+        let unavailable_amount = self.borrowed_amount().get()
+            + self.bad_debt().get()
+            + self.queued_withdraw_amount().get();
         require!(
-            payment.amount <= leftover_lend_amount,
+            &payment.amount + &unavailable_amount <= lent_amount,
             "Cannot withdraw, not enough funds"
         );
 
-        lend_token_mapper.nft_burn(payment.token_nonce, &payment.amount);
+        let caller = self.blockchain().get_caller();
+        let total_withdraw_amount = self.withdraw_lend_tokens(
+            &caller,
+            payment.token_nonce,
+            &payment.amount,
+            opt_reject_if_penalty,
+        );
+
+        self.send_stablecoins(&caller, total_withdraw_amount)
+    }
+
+    /// Locks LEND tokens in the contract, to be withdrawn once there's enough liquidity.
+    /// Requests are served in order through `processWithdrawQueue`,
+    /// and keep earning rewards until then. Returns the ticket ID of the request.
+    #[payable("*")]
+    #[endpoint(queueWithdraw)]
+    fn queue_withdraw(&self) -> u64 {
+        self.require_no_ongoing_operation();
+
+        let payment: EsdtTokenPayment<Self::Api> = self.call_value().payment();
+        self.lend_token()
+            .require_same_token(&payment.token_identifier);
+        let _ = self.get_unlocked_lend_metadata(payment.token_nonce);
+
+        let ticket_id = self.last_withdraw_ticket_id().update(|id| {
+            *id += 1;
+            *id
+        });
+        let caller = self.blockchain().get_caller();
+        let current_epoch = self.blockchain().get_block_epoch();
+        self.withdraw_queued_event(
+            &caller,
+            ticket_id,
+            payment.token_nonce,
+            &payment.amount,
+            current_epoch,
+        );
+
+        self.queued_withdraw_amount()
+            .update(|queued_amount| *queued_amount += &payment.amount);
+        self.withdraw_request(ticket_id).set(&WithdrawRequest {
+            lender: caller,
+            lend_nonce: payment.token_nonce,
+            lend_amount: payment.amount,
+            queue_epoch: current_epoch,
+        });
+        self.withdraw_queue().push_back(ticket_id);
+
+        ticket_id
+    }
+
+    /// Gives back the LEND tokens of a request that was not fully served yet
+    #[endpoint(cancelQueuedWithdraw)]
+    fn cancel_queued_withdraw(&self, ticket_id: u64) -> EsdtTokenPayment<Self::Api> {
+        self.require_no_ongoing_operation();
+
+        let request = self.get_withdraw_request(ticket_id);
+        let caller = self.blockchain().get_caller();
+        require!(
+            request.lender == caller,
+            "Only the lender may cancel the request"
+        );
+
+        self.withdraw_request(ticket_id).clear();
+        self.queued_withdraw_amount()
+            .update(|queued_amount| *queued_amount -= &request.lend_amount);
+
+        self.queued_withdraw_cancelled_event(
+            &caller,
+            ticket_id,
+            &request.lend_amount,
+            self.blockchain().get_block_epoch(),
+        );
+
+        let lend_token_id = self.lend_token().get_token_id();
+        self.send().direct(
+            &caller,
+            &lend_token_id,
+            request.lend_nonce,
+            &request.lend_amount,
+            &[],
+        );
+
+        EsdtTokenPayment::new(lend_token_id, request.lend_nonce, request.lend_amount)
+    }
+
+    /// Serves the queued withdraw requests in order, while there's liquidity and gas left.
+    /// If the liquidity only covers part of a request, that part is paid out
+    /// and the rest stays at the front of the queue. Anyone may call this.
+    /// The stablecoins are not sent here, so a lender that can't receive them
+    /// doesn't hold up the queue. They are claimed through `claimWithdrawQueuePayout`.
+    #[endpoint(processWithdrawQueue)]
+    fn process_withdraw_queue(&self) -> OperationCompletionStatus {
+        self.require_no_ongoing_operation();
+
+        self.accrue_interest();
+        self.update_global_lender_rewards();
+
+        let mut queue_mapper = self.withdraw_queue();
+        self.run_while_it_has_gas(
+            || {
+                let ticket_id = match queue_mapper.front() {
+                    Some(ticket_id) => ticket_id,
+                    None => return LoopOp::Break,
+                };
+
+                // cancelled request
+                let request_mapper = self.withdraw_request(ticket_id);
+                if request_mapper.is_empty() {
+                    let _ = queue_mapper.pop_front();
+                    return LoopOp::Continue;
+                }
+
+                let available_liquidity =
+                    self.lent_amount().get() - self.borrowed_amount().get() - self.bad_debt().get();
+                if available_liquidity == 0u32 {
+                    return LoopOp::Break;
+                }
+
+                let mut request = request_mapper.get();
+                let withdraw_amount = if request.lend_amount > available_liquidity {
+                    available_liquidity
+                } else {
+                    request.lend_amount.clone()
+                };
+                request.lend_amount -= &withdraw_amount;
+                self.queued_withdraw_amount()
+                    .update(|queued_amount| *queued_amount -= &withdraw_amount);
+
+                let total_withdraw_amount = self.withdraw_lend_tokens(
+                    &request.lender,
+                    request.lend_nonce,
+                    &withdraw_amount,
+                    OptionalValue::None,
+                );
+                self.withdraw_queue_payout(&request.lender)
+                    .update(|payout| *payout += total_withdraw_amount);
+
+                if request.lend_amount > 0u32 {
+                    self.compound_queued_lend_tokens(&mut request);
+                    request_mapper.set(&request);
+                    return LoopOp::Break;
+                }
+
+                request_mapper.clear();
+                let _ = queue_mapper.pop_front();
+
+                LoopOp::Continue
+            },
+            None,
+        )
+    }
+
+    /// Sends the caller the stablecoins of their withdraw requests served so far
+    #[endpoint(claimWithdrawQueuePayout)]
+    fn claim_withdraw_queue_payout(&self) -> EsdtTokenPayment<Self::Api> {
+        self.require_no_ongoing_operation();
+
+        let caller = self.blockchain().get_caller();
+        let payout_mapper = self.withdraw_queue_payout(&caller);
+        let amount = payout_mapper.get();
+        require!(amount > 0u32, "Nothing to claim");
+
+        payout_mapper.clear();
+
+        self.claim_withdraw_queue_payout_event(
+            &caller,
+            &amount,
+            self.blockchain().get_block_epoch(),
+        );
+
+        self.send_stablecoins(&caller, amount)
+    }
+
+    /// Compounds the rewards of the auto-compounding LEND tokens still waiting in the queue,
+    /// so they keep earning on their rewards until they are served
+    fn compound_queued_lend_tokens(&self, request: &mut WithdrawRequest<Self::Api>) {
+        let lend_metadata: LendMetadata =
+            self.lend_token().get_token_attributes(request.lend_nonce);
+        if !lend_metadata.auto_compound {
+            return;
+        }
+
+        let rewards_amount = self.get_lender_claimable_rewards(
+            request.lend_nonce,
+            &lend_metadata,
+            &request.lend_amount,
+        );
+        if rewards_amount == 0u32 {
+            return;
+        }
+
+        let (lend_nonce, rewards_amount) = self.compound_lend_tokens(
+            &request.lender,
+            request.lend_nonce,
+            &lend_metadata,
+            &request.lend_amount,
+            OptionalValue::None,
+        );
+        self.queued_withdraw_amount()
+            .update(|queued_amount| *queued_amount += &rewards_amount);
+
+        request.lend_nonce = lend_nonce;
+        request.lend_amount += rewards_amount;
+    }
+
+    /// Compounds the pending rewards of the unlocked auto-compounding LEND tokens in place,
+    /// so passive lenders earn on their rewards without claiming them.
+    /// The rewards stay owed to the holders of the tokens, and are paid out along with the
+    /// rest of their rewards. Nonces are served in turn, while there's gas left,
+    /// and not while the rewards have a penalty. Anyone may call this.
+    #[endpoint(processAutoCompound)]
+    fn process_auto_compound(&self) -> OperationCompletionStatus {
+        self.require_no_ongoing_operation();
+
+        self.accrue_interest();
+        self.update_global_lender_rewards();
+        require!(
+            self.penalty_per_lend_token().get() == 0u32,
+            "Rewards have penalty"
+        );
+
+        let caller = self.blockchain().get_caller();
+        let current_rewards_index = self.lender_rewards_index().get();
+        let mut nonces_mapper = self.auto_compound_lend_nonces();
+        self.run_while_it_has_gas(
+            || {
+                let lend_nonce = match nonces_mapper.front() {
+                    Some(lend_nonce) => lend_nonce,
+                    None => return LoopOp::Break,
+                };
+
+                // all the tokens of the nonce were burned
+                let supply = self.auto_compound_lend_token_supply(lend_nonce).get();
+                if supply == 0u32 {
+                    let _ = nonces_mapper.pop_front();
+                    return LoopOp::Continue;
+                }
+
+                // every nonce left was compounded already
+                if self.lend_token_rewards_index(lend_nonce).get() == current_rewards_index {
+                    return LoopOp::Break;
+                }
+
+                self.compound_lend_nonce(&caller, lend_nonce, &supply, &current_rewards_index);
+                let _ = nonces_mapper.pop_front();
+                nonces_mapper.push_back(lend_nonce);
+
+                LoopOp::Continue
+            },
+            None,
+        )
+    }
+
+    fn compound_lend_nonce(
+        &self,
+        caller: &ManagedAddress,
+        lend_nonce: u64,
+        supply: &BigUint,
+        current_rewards_index: &BigUint,
+    ) {
+        let lend_metadata: LendMetadata = self.lend_token().get_token_attributes(lend_nonce);
+        let compounded_rewards_mapper = self.lend_token_compounded_rewards(lend_nonce);
+        let old_compounded_rewards = compounded_rewards_mapper.get();
+        let new_compounded_rewards = self.get_lender_claimable_rewards(
+            lend_nonce,
+            &lend_metadata,
+            &BigUint::from(DEFAULT_DECIMALS),
+        );
+        compounded_rewards_mapper.set(&new_compounded_rewards);
+        self.lend_token_rewards_index(lend_nonce)
+            .set(current_rewards_index);
+
+        let rewards_amount =
+            supply * &(new_compounded_rewards - old_compounded_rewards) / DEFAULT_DECIMALS;
+        self.auto_compounded_rewards_amount()
+            .update(|amount| *amount += &rewards_amount);
+
+        self.auto_compound_rewards_event(
+            caller,
+            lend_nonce,
+            supply,
+            &rewards_amount,
+            self.blockchain().get_block_epoch(),
+        );
+    }
+
+    /// Burns the LEND tokens and returns the amount owed to the lender,
+    /// i.e. the lent amount plus the rewards left after the penalty
+    fn withdraw_lend_tokens(
+        &self,
+        lender: &ManagedAddress,
+        lend_token_nonce: u64,
+        lend_token_amount: &BigUint,
+        opt_reject_if_penalty: OptionalValue<bool>,
+    ) -> BigUint {
+        let lend_metadata = self.get_unlocked_lend_metadata(lend_token_nonce);
+        self.lend_token()
+            .nft_burn(lend_token_nonce, lend_token_amount);
+        self.decrease_auto_compound_supply(lend_token_nonce, &lend_metadata, lend_token_amount);
 
         self.lent_amount()
-            .update(|amount| *amount -= &payment.amount);
+            .update(|amount| *amount -= lend_token_amount);
+        self.decrease_lock_boost_amount(lend_token_amount, &lend_metadata);
 
         let rewards_amount = self.try_claim_with_penalty(
-            lend_metadata.lend_epoch,
-            &payment.amount,
+            lender,
+            lend_token_nonce,
+            &lend_metadata,
+            lend_token_amount,
             opt_reject_if_penalty,
         );
-        let total_withdraw_amount = payment.amount + rewards_amount;
+        self.emit_withdraw_event(lender, lend_token_nonce, lend_token_amount, &rewards_amount);
+
+        lend_token_amount + &rewards_amount
+    }
+
+    /// Lends stablecoins in exchange for fungible share tokens.
+    /// Shares are minted at the current share price, which grows as the pool earns rewards.
+    #[payable("*")]
+    #[endpoint(lendForShares)]
+    fn lend_for_shares(&self) -> EsdtTokenPayment<Self::Api> {
+        self.require_no_ongoing_operation();
+        require!(!self.share_token().is_empty(), "Share token not issued");
+
+        self.accrue_interest();
+        self.update_global_lender_rewards();
+
+        let (payment_amount, payment_token) = self.call_value().payment_token_pair();
+        let stablecoin_token_id = self.stablecoin_token_id().get();
+        require!(
+            payment_token == stablecoin_token_id,
+            "May only lend stablecoins"
+        );
+
+        let shares_lent_amount = self.add_shares_rewards();
+        let shares = self.compute_shares_for_amount(&shares_lent_amount, &payment_amount);
+        require!(shares > 0u32, "Lend amount too low");
+
+        self.shares_lent_amount()
+            .set(&(shares_lent_amount + &payment_amount));
+        self.share_supply()
+            .update(|share_supply| *share_supply += &shares);
+        self.lent_amount()
+            .update(|lent_amount| *lent_amount += &payment_amount);
+
         let caller = self.blockchain().get_caller();
-        self.send_stablecoins(&caller, total_withdraw_amount)
+        self.emit_lend_for_shares_event(&caller, &payment_amount, &shares);
+
+        self.share_token().mint_and_send(&caller, shares)
+    }
+
+    /// Burns share tokens and sends their stablecoin value at the current share price
+    #[payable("*")]
+    #[endpoint(withdrawShares)]
+    fn withdraw_shares(&self) -> WithdrawResultType<Self::Api> {
+        self.require_no_ongoing_operation();
+
+        self.accrue_interest();
+        self.update_global_lender_rewards();
+
+        let payment: EsdtTokenPayment<Self::Api> = self.call_value().payment();
+        let share_token_mapper = self.share_token();
+        share_token_mapper.require_same_token(&payment.token_identifier);
+
+        let shares_lent_amount = self.add_shares_rewards();
+        let withdraw_amount = self.compute_amount_for_shares(&shares_lent_amount, &payment.amount);
+
+        // liquidity is reserved for the queued requests first
+        let lent_amount = self.lent_amount().get();
+        let unavailable_amount = self.borrowed_amount().get()
+            + self.bad_debt().get()
+            + self.queued_withdraw_amount().get();
+        require!(
+            &withdraw_amount + &unavailable_amount <= lent_amount,
+            "Cannot withdraw, not enough funds"
+        );
+
+        share_token_mapper.burn(&payment.amount);
+        self.shares_lent_amount()
+            .set(&(shares_lent_amount - &withdraw_amount));
+        self.share_supply()
+            .update(|share_supply| *share_supply -= &payment.amount);
+        self.lent_amount()
+            .update(|lent_amount| *lent_amount -= &withdraw_amount);
+
+        let caller = self.blockchain().get_caller();
+        self.emit_withdraw_shares_event(&caller, &withdraw_amount, &payment.amount);
+
+        self.send_stablecoins(&caller, withdraw_amount)
+    }
+
+    /// Stablecoins per share token, with 18 decimals.
+    /// The penalty is taken at its last computed value, as the view doesn't save anything.
+    #[view(getSharePrice)]
+    fn get_share_price(&self) -> BigUint {
+        let shares_lent_amount = self.shares_lent_amount().get();
+        let rewards_index_at_update = self.shares_rewards_index().get();
+        let current_rewards_index = self.get_current_lender_rewards_index();
+        if rewards_index_at_update == current_rewards_index {
+            return self.compute_share_price(&shares_lent_amount);
+        }
+
+        let rewards_amount = self.compute_reward_amount(
+            &shares_lent_amount,
+            &rewards_index_at_update,
+            &current_rewards_index,
+        );
+        let penalty_amount = self.get_penalty_amount(&shares_lent_amount);
+        let rewards_amount = if rewards_amount > penalty_amount {
+            rewards_amount - penalty_amount
+        } else {
+            BigUint::zero()
+        };
+
+        let sc_address = self.blockchain().get_sc_address();
+        let iou = self.rewards_iou(&sc_address).get();
+        let iou_funds = self.rewards_iou_funds().get();
+        let redeemable_iou = if iou < iou_funds { iou } else { iou_funds };
+
+        self.compute_share_price(&(shares_lent_amount + rewards_amount + redeemable_iou))
+    }
+
+    /// Adds the rewards earned by the share pool since the last update to the pool,
+    /// so they are lent further, and returns the pool's new amount
+    fn add_shares_rewards(&self) -> BigUint {
+        let mut shares_lent_amount = self.shares_lent_amount().get();
+        let rewards_index_at_update = self.shares_rewards_index().get();
+        let current_rewards_index = self.lender_rewards_index().get();
+        // nothing was earned since the last update, so there is no penalty to take either
+        if rewards_index_at_update == current_rewards_index {
+            return shares_lent_amount;
+        }
+
+        self.shares_rewards_index().set(&current_rewards_index);
+
+        let rewards_amount = self.compute_reward_amount(
+            &shares_lent_amount,
+            &rewards_index_at_update,
+            &current_rewards_index,
+        );
+        let sc_address = self.blockchain().get_sc_address();
+        let mut rewards_amount = self.apply_rewards_penalty(
+            &sc_address,
+            rewards_amount,
+            &shares_lent_amount,
+            OptionalValue::None,
+        );
+        // the rewards the pool lost to the penalty go back into it, once the reserves recover
+        rewards_amount += self.redeem_rewards_iou(&sc_address);
+        if rewards_amount > 0u32 {
+            self.lent_amount()
+                .update(|lent_amount| *lent_amount += &rewards_amount);
+            shares_lent_amount += rewards_amount;
+            self.shares_lent_amount().set(&shares_lent_amount);
+        }
+
+        shares_lent_amount
+    }
+
+    /// Lender rewards index as it would be after accruing interest and updating the rewards,
+    /// without saving it
+    fn get_current_lender_rewards_index(&self) -> BigUint {
+        let rewards_index = self.lender_rewards_index().get();
+        let (time_passed, periods_in_year) = self.get_time_passed_since(
+            self.last_rewards_update_epoch().get(),
+            self.last_rewards_update_timestamp().get(),
+        );
+        if time_passed == 0 {
+            return rewards_index;
+        }
+
+        let deposit_rate = self.get_deposit_rate_for_debt(&self.get_current_total_debt());
+        rewards_index
+            + self.compute_rewards_index_increase(&deposit_rate, time_passed, periods_in_year)
     }
 
     #[payable("*")]
@@ -341,23 +909,36 @@ pub trait SavingsAccount:
     ) -> ClaimRewardsResultType<Self::Api> {
         self.require_no_ongoing_operation();
 
+        self.accrue_interest();
         self.update_global_lender_rewards();
 
         let payment: EsdtTokenPayment<Self::Api> = self.call_value().payment();
+        let caller = self.blockchain().get_caller();
         let lend_token_mapper = self.lend_token();
         lend_token_mapper.require_same_token(&payment.token_identifier);
 
         let lend_metadata: LendMetadata =
             lend_token_mapper.get_token_attributes(payment.token_nonce);
-        let current_epoch = self.blockchain().get_block_epoch();
-        require!(lend_metadata.lend_epoch < current_epoch, NO_REWARDS_ERR_MSG);
+        if lend_metadata.auto_compound {
+            let new_lend_tokens = self.compound_lend_rewards(
+                &caller,
+                &payment,
+                &lend_metadata,
+                opt_reject_if_penalty,
+            );
+            let no_stablecoins =
+                EsdtTokenPayment::new(self.stablecoin_token_id().get(), 0, BigUint::zero());
+
+            return (new_lend_tokens, no_stablecoins).into();
+        }
+
+        let new_lend_metadata = self.get_lend_metadata_for_claim(&lend_metadata, &payment.amount);
 
         // burn old sfts
         lend_token_mapper.nft_burn(payment.token_nonce, &payment.amount);
 
         // create and send new sfts, with updated metadata
-        let caller = self.blockchain().get_caller();
-        let lend_nonce = self.get_or_create_lend_token_nonce(current_epoch);
+        let lend_nonce = self.get_or_create_lend_nonce_with_rewards_index(&new_lend_metadata);
         let new_lend_tokens = lend_token_mapper.nft_add_quantity_and_send(
             &caller,
             lend_nonce,
@@ -365,24 +946,396 @@ pub trait SavingsAccount:
         );
 
         let rewards_amount = self.try_claim_with_penalty(
-            lend_metadata.lend_epoch,
+            &caller,
+            payment.token_nonce,
+            &lend_metadata,
             &payment.amount,
             opt_reject_if_penalty,
         );
         require!(rewards_amount > 0, NO_REWARDS_ERR_MSG);
 
+        self.emit_lender_claim_rewards_event(
+            &caller,
+            payment.token_nonce,
+            lend_nonce,
+            &payment.amount,
+            &rewards_amount,
+        );
+
         let stablecoins_payment = self.send_stablecoins(&caller, rewards_amount);
         (new_lend_tokens, stablecoins_payment).into()
     }
 
+    /// Adds the pending rewards of the LEND tokens to their principal, instead of paying them out.
+    /// The tokens are reissued at the current epoch, keeping their lock, if any.
+    #[payable("*")]
+    #[endpoint(compoundRewards)]
+    fn compound_rewards(
+        &self,
+        opt_reject_if_penalty: OptionalValue<bool>,
+    ) -> EsdtTokenPayment<Self::Api> {
+        self.require_no_ongoing_operation();
+
+        self.accrue_interest();
+        self.update_global_lender_rewards();
+
+        let payment: EsdtTokenPayment<Self::Api> = self.call_value().payment();
+        let caller = self.blockchain().get_caller();
+        let lend_token_mapper = self.lend_token();
+        lend_token_mapper.require_same_token(&payment.token_identifier);
+
+        let lend_metadata: LendMetadata =
+            lend_token_mapper.get_token_attributes(payment.token_nonce);
+
+        self.compound_lend_rewards(&caller, &payment, &lend_metadata, opt_reject_if_penalty)
+    }
+
+    /// Merges several LEND nonces into a single one, created at the current epoch.
+    /// The pending rewards of all the merged tokens are paid out,
+    /// or added to the merged tokens if they are auto-compounding.
+    /// Locked tokens can only be merged with tokens that have the same lock,
+    /// and auto-compounding tokens only with other auto-compounding tokens.
+    #[payable("*")]
+    #[endpoint(mergeLendTokens)]
+    fn merge_lend_tokens(
+        &self,
+        opt_reject_if_penalty: OptionalValue<bool>,
+    ) -> MergeLendTokensResultType<Self::Api> {
+        self.require_no_ongoing_operation();
+
+        self.accrue_interest();
+        self.update_global_lender_rewards();
+
+        let payments = self.call_value().all_esdt_transfers();
+        let nr_payments = payments.len();
+        require!(nr_payments >= 2, "Must send at least 2 LEND payments");
+
+        let reject_if_penalty = match opt_reject_if_penalty {
+            OptionalValue::Some(reject) => reject,
+            OptionalValue::None => false,
+        };
+        let caller = self.blockchain().get_caller();
+        let lend_token_mapper = self.lend_token();
+        let mut new_lend_metadata = self.get_current_lend_metadata();
+        let mut total_amount = BigUint::zero();
+        let mut rewards_amount = BigUint::zero();
+        for i in 0..nr_payments {
+            let payment: EsdtTokenPayment<Self::Api> = payments.get(i);
+            lend_token_mapper.require_same_token(&payment.token_identifier);
+
+            let lend_metadata: LendMetadata =
+                lend_token_mapper.get_token_attributes(payment.token_nonce);
+            let (lock_end_epoch, boost) = self.release_ended_lock(&lend_metadata, &payment.amount);
+            if i == 0 {
+                new_lend_metadata.lock_end_epoch = lock_end_epoch;
+                new_lend_metadata.boost = boost;
+                new_lend_metadata.auto_compound = lend_metadata.auto_compound;
+            } else {
+                require!(
+                    lock_end_epoch == new_lend_metadata.lock_end_epoch
+                        && boost == new_lend_metadata.boost,
+                    "Can only merge LEND tokens with the same lock"
+                );
+                require!(
+                    lend_metadata.auto_compound == new_lend_metadata.auto_compound,
+                    "Can only merge LEND tokens with the same auto-compound setting"
+                );
+            }
+
+            rewards_amount += self.try_claim_with_penalty(
+                &caller,
+                payment.token_nonce,
+                &lend_metadata,
+                &payment.amount,
+                OptionalValue::Some(reject_if_penalty),
+            );
+            total_amount += &payment.amount;
+
+            lend_token_mapper.nft_burn(payment.token_nonce, &payment.amount);
+            self.decrease_auto_compound_supply(
+                payment.token_nonce,
+                &lend_metadata,
+                &payment.amount,
+            );
+        }
+
+        if new_lend_metadata.auto_compound && rewards_amount > 0u32 {
+            // the rewards stay in the SC, as lent stablecoins
+            self.add_compounded_rewards(&new_lend_metadata, &rewards_amount);
+            total_amount += &rewards_amount;
+        }
+
+        let lend_nonce = self.get_or_create_lend_nonce_with_rewards_index(&new_lend_metadata);
+        self.increase_auto_compound_supply(lend_nonce, &new_lend_metadata, &total_amount);
+        let new_lend_tokens =
+            lend_token_mapper.nft_add_quantity_and_send(&caller, lend_nonce, total_amount);
+
+        self.merge_lend_tokens_event(
+            &caller,
+            lend_nonce,
+            &new_lend_tokens.amount,
+            &rewards_amount,
+            self.blockchain().get_block_epoch(),
+        );
+
+        let stablecoins_payment = if rewards_amount > 0u32 && !new_lend_metadata.auto_compound {
+            self.send_stablecoins(&caller, rewards_amount)
+        } else {
+            EsdtTokenPayment::new(self.stablecoin_token_id().get(), 0, BigUint::zero())
+        };
+
+        (new_lend_tokens, stablecoins_payment).into()
+    }
+
+    fn compound_lend_rewards(
+        &self,
+        caller: &ManagedAddress,
+        payment: &EsdtTokenPayment<Self::Api>,
+        lend_metadata: &LendMetadata,
+        opt_reject_if_penalty: OptionalValue<bool>,
+    ) -> EsdtTokenPayment<Self::Api> {
+        let (lend_nonce, rewards_amount) = self.compound_lend_tokens(
+            caller,
+            payment.token_nonce,
+            lend_metadata,
+            &payment.amount,
+            opt_reject_if_penalty,
+        );
+        require!(rewards_amount > 0, NO_REWARDS_ERR_MSG);
+
+        let new_lend_amount = &payment.amount + &rewards_amount;
+
+        self.send().direct(
+            caller,
+            &payment.token_identifier,
+            lend_nonce,
+            &new_lend_amount,
+            &[],
+        );
+
+        EsdtTokenPayment::new(
+            payment.token_identifier.clone(),
+            lend_nonce,
+            new_lend_amount,
+        )
+    }
+
+    /// Burns the LEND tokens held by the SC and creates new ones at the current epoch,
+    /// with their pending rewards added to the principal.
+    /// Returns the nonce of the new tokens, which are kept by the SC, and the compounded rewards.
+    fn compound_lend_tokens(
+        &self,
+        lender: &ManagedAddress,
+        lend_token_nonce: u64,
+        lend_metadata: &LendMetadata,
+        lend_token_amount: &BigUint,
+        opt_reject_if_penalty: OptionalValue<bool>,
+    ) -> (u64, BigUint) {
+        let new_lend_metadata = self.get_lend_metadata_for_claim(lend_metadata, lend_token_amount);
+
+        let rewards_amount = self.try_claim_with_penalty(
+            lender,
+            lend_token_nonce,
+            lend_metadata,
+            lend_token_amount,
+            opt_reject_if_penalty,
+        );
+
+        let lend_token_mapper = self.lend_token();
+        lend_token_mapper.nft_burn(lend_token_nonce, lend_token_amount);
+        self.decrease_auto_compound_supply(lend_token_nonce, lend_metadata, lend_token_amount);
+
+        // the rewards stay in the SC, as lent stablecoins
+        self.add_compounded_rewards(&new_lend_metadata, &rewards_amount);
+
+        let lend_nonce = self.get_or_create_lend_nonce_with_rewards_index(&new_lend_metadata);
+        let new_lend_amount = lend_token_amount + &rewards_amount;
+        self.increase_auto_compound_supply(lend_nonce, &new_lend_metadata, &new_lend_amount);
+        let _ = lend_token_mapper.nft_add_quantity(lend_nonce, new_lend_amount);
+
+        self.emit_compound_rewards_event(
+            lender,
+            lend_token_nonce,
+            lend_nonce,
+            lend_token_amount,
+            &rewards_amount,
+        );
+
+        (lend_nonce, rewards_amount)
+    }
+
+    fn add_compounded_rewards(&self, new_lend_metadata: &LendMetadata, rewards_amount: &BigUint) {
+        self.increase_lock_boost_amount(rewards_amount, new_lend_metadata);
+        self.lent_amount()
+            .update(|lent_amount| *lent_amount += rewards_amount);
+    }
+
+    /// Tracks the supply of the unlocked auto-compounding LEND nonces,
+    /// which `processAutoCompound` compounds in place
+    fn increase_auto_compound_supply(
+        &self,
+        lend_nonce: u64,
+        lend_metadata: &LendMetadata,
+        amount: &BigUint,
+    ) {
+        if !lend_metadata.auto_compound || lend_metadata.is_locked() {
+            return;
+        }
+
+        let supply_mapper = self.auto_compound_lend_token_supply(lend_nonce);
+        if supply_mapper.is_empty() {
+            self.auto_compound_lend_nonces().push_back(lend_nonce);
+        }
+        supply_mapper.update(|supply| *supply += amount);
+    }
+
+    /// The rewards compounded for the burned tokens are paid out with their other rewards,
+    /// so they no longer earn rewards
+    fn decrease_auto_compound_supply(
+        &self,
+        lend_nonce: u64,
+        lend_metadata: &LendMetadata,
+        amount: &BigUint,
+    ) {
+        if !lend_metadata.auto_compound || lend_metadata.is_locked() {
+            return;
+        }
+
+        self.auto_compound_lend_token_supply(lend_nonce)
+            .update(|supply| *supply -= amount);
+
+        let compounded_rewards_amount =
+            amount * &self.lend_token_compounded_rewards(lend_nonce).get() / DEFAULT_DECIMALS;
+        self.auto_compounded_rewards_amount().update(|total| {
+            // may be off by one, because of rounding
+            if *total > compounded_rewards_amount {
+                *total -= compounded_rewards_amount;
+            } else {
+                *total = BigUint::zero();
+            }
+        });
+    }
+
+    fn get_lend_metadata_for_claim(
+        &self,
+        lend_metadata: &LendMetadata,
+        lend_amount: &BigUint,
+    ) -> LendMetadata {
+        let mut new_lend_metadata = self.get_current_lend_metadata();
+        let (lock_end_epoch, boost) = self.release_ended_lock(lend_metadata, lend_amount);
+        new_lend_metadata.lock_end_epoch = lock_end_epoch;
+        new_lend_metadata.boost = boost;
+        new_lend_metadata.auto_compound = lend_metadata.auto_compound;
+        require!(lend_metadata != &new_lend_metadata, NO_REWARDS_ERR_MSG);
+
+        new_lend_metadata
+    }
+
+    /// Returns the lock kept by reissued LEND tokens.
+    /// A lock that has ended is dropped, so the new tokens no longer count for the lock boost.
+    fn release_ended_lock(
+        &self,
+        lend_metadata: &LendMetadata,
+        lend_amount: &BigUint,
+    ) -> (u64, u64) {
+        if !lend_metadata.is_locked()
+            || self.blockchain().get_block_epoch() < lend_metadata.lock_end_epoch
+        {
+            return (lend_metadata.lock_end_epoch, lend_metadata.boost);
+        }
+
+        self.decrease_lock_boost_amount(lend_amount, lend_metadata);
+
+        (0, 0)
+    }
+
+    fn increase_lock_boost_amount(&self, lend_amount: &BigUint, lend_metadata: &LendMetadata) {
+        if !lend_metadata.is_locked() {
+            return;
+        }
+
+        let lock_boost_amount = self.compute_lock_boost_amount(lend_amount, lend_metadata.boost);
+        let mut lock_boost_by_end_epoch_mapper = self.lock_boost_amount_by_end_epoch();
+        let amount_for_end_epoch = lock_boost_by_end_epoch_mapper
+            .get(&lend_metadata.lock_end_epoch)
+            .unwrap_or_else(BigUint::zero);
+        let _ = lock_boost_by_end_epoch_mapper.insert(
+            lend_metadata.lock_end_epoch,
+            amount_for_end_epoch + &lock_boost_amount,
+        );
+        self.lock_boost_amount()
+            .update(|amount| *amount += lock_boost_amount);
+    }
+
+    /// Locks whose end epoch has passed were already dropped from the lock boost
+    /// when the rewards were updated, so there is nothing left to decrease for them
+    fn decrease_lock_boost_amount(&self, lend_amount: &BigUint, lend_metadata: &LendMetadata) {
+        let mut lock_boost_by_end_epoch_mapper = self.lock_boost_amount_by_end_epoch();
+        let amount_for_end_epoch =
+            match lock_boost_by_end_epoch_mapper.get(&lend_metadata.lock_end_epoch) {
+                Some(amount) if lend_metadata.is_locked() => amount,
+                _ => return,
+            };
+
+        // may be off by one, because of rounding
+        let lock_boost_amount = self.compute_lock_boost_amount(lend_amount, lend_metadata.boost);
+        let decrease_amount = if amount_for_end_epoch > lock_boost_amount {
+            let _ = lock_boost_by_end_epoch_mapper.insert(
+                lend_metadata.lock_end_epoch,
+                &amount_for_end_epoch - &lock_boost_amount,
+            );
+            lock_boost_amount
+        } else {
+            let _ = lock_boost_by_end_epoch_mapper.remove(&lend_metadata.lock_end_epoch);
+            amount_for_end_epoch
+        };
+        self.lock_boost_amount().update(|amount| {
+            if *amount > decrease_amount {
+                *amount -= decrease_amount;
+            } else {
+                *amount = BigUint::zero();
+            }
+        });
+    }
+
+    fn get_unlocked_lend_metadata(&self, lend_token_nonce: u64) -> LendMetadata {
+        let lend_metadata: LendMetadata = self.lend_token().get_token_attributes(lend_token_nonce);
+        require!(
+            self.blockchain().get_block_epoch() >= lend_metadata.lock_end_epoch,
+            "Lend tokens are still locked"
+        );
+
+        lend_metadata
+    }
+
     fn try_claim_with_penalty(
         &self,
-        lend_epoch: u64,
+        lender: &ManagedAddress,
+        lend_token_nonce: u64,
+        lend_metadata: &LendMetadata,
         lend_token_amount: &BigUint,
         opt_reject_if_penalty: OptionalValue<bool>,
     ) -> BigUint {
-        let mut rewards_amount = self.get_lender_claimable_rewards(lend_epoch, lend_token_amount);
-        let penalty_amount = self.get_penalty_amount(lend_token_amount);
+        let rewards_amount =
+            self.get_lender_claimable_rewards(lend_token_nonce, lend_metadata, lend_token_amount);
+        self.apply_rewards_penalty(
+            lender,
+            rewards_amount,
+            lend_token_amount,
+            opt_reject_if_penalty,
+        )
+    }
+
+    /// Takes the penalty for `lend_amount` out of the rewards, if the reserves can't cover them.
+    /// The rewards lost this way are owed to `iou_holder`, and paid back once the reserves recover.
+    fn apply_rewards_penalty(
+        &self,
+        iou_holder: &ManagedAddress,
+        mut rewards_amount: BigUint,
+        lend_amount: &BigUint,
+        opt_reject_if_penalty: OptionalValue<bool>,
+    ) -> BigUint {
+        let penalty_amount = self.get_penalty_amount(lend_amount);
         if penalty_amount > 0u32 {
             let reject = match opt_reject_if_penalty {
                 OptionalValue::Some(r) => r,
@@ -390,11 +1343,13 @@ pub trait SavingsAccount:
             };
             require!(!reject, "Rewards have penalty");
 
-            if rewards_amount > penalty_amount {
-                rewards_amount -= &penalty_amount;
+            let lost_rewards_amount = if rewards_amount > penalty_amount {
+                penalty_amount.clone()
             } else {
-                rewards_amount = BigUint::zero();
-            }
+                rewards_amount.clone()
+            };
+            rewards_amount -= &lost_rewards_amount;
+            self.add_rewards_iou(iou_holder, lost_rewards_amount);
 
             self.missing_rewards().update(|missing_rewards| {
                 // since we round up for penalty, this is possible if everyone claims
@@ -411,8 +1366,55 @@ pub trait SavingsAccount:
         rewards_amount
     }
 
+    /// Pays the caller the rewards they lost to the penalty, as far as the reserves recovered so far allow
+    #[endpoint(claimRewardsIou)]
+    fn claim_rewards_iou(&self) -> EsdtTokenPayment<Self::Api> {
+        self.require_no_ongoing_operation();
+
+        let caller = self.blockchain().get_caller();
+        let amount = self.redeem_rewards_iou(&caller);
+        require!(amount > 0u32, "No rewards IOU to claim");
+
+        self.claim_rewards_iou_event(&caller, &amount, self.blockchain().get_block_epoch());
+
+        self.send_stablecoins(&caller, amount)
+    }
+
+    fn add_rewards_iou(&self, holder: &ManagedAddress, amount: BigUint) {
+        if amount == 0u32 {
+            return;
+        }
+
+        self.rewards_iou(holder).update(|iou| *iou += &amount);
+        self.total_rewards_iou().update(|total| *total += &amount);
+
+        self.rewards_iou_issued_event(holder, &amount, self.blockchain().get_block_epoch());
+    }
+
+    /// Takes as much of the holder's IOU as the funds set aside allow, and returns the amount
+    fn redeem_rewards_iou(&self, holder: &ManagedAddress) -> BigUint {
+        let iou_mapper = self.rewards_iou(holder);
+        let iou = iou_mapper.get();
+        let funds = self.rewards_iou_funds().get();
+        let amount = if iou < funds {
+            iou.clone()
+        } else {
+            funds.clone()
+        };
+        if amount == 0u32 {
+            return amount;
+        }
+
+        iou_mapper.set(&(iou - &amount));
+        self.rewards_iou_funds().set(&(funds - &amount));
+        self.total_rewards_iou().update(|total| *total -= &amount);
+
+        amount
+    }
+
     #[view(getPenaltyAmount)]
     fn get_penalty_amount_view(&self, lend_amount: BigUint) -> BigUint {
+        self.accrue_interest();
         self.update_global_lender_rewards();
         self.get_penalty_amount(&lend_amount)
     }
@@ -431,12 +1433,15 @@ pub trait SavingsAccount:
     #[view(getLenderClaimableRewards)]
     fn get_lender_claimable_rewards_view(
         &self,
-        lend_epoch: u64,
+        lend_token_nonce: u64,
         lend_token_amount: BigUint,
     ) -> BigUint {
+        self.accrue_interest();
         self.update_global_lender_rewards();
 
-        let rewards = self.get_lender_claimable_rewards(lend_epoch, &lend_token_amount);
+        let lend_metadata: LendMetadata = self.lend_token().get_token_attributes(lend_token_nonce);
+        let rewards =
+            self.get_lender_claimable_rewards(lend_token_nonce, &lend_metadata, &lend_token_amount);
         let penalty = self.get_penalty_amount(&lend_token_amount);
 
         if rewards > penalty {
@@ -448,25 +1453,77 @@ pub trait SavingsAccount:
 
     fn get_lender_claimable_rewards(
         &self,
-        lend_epoch: u64,
+        lend_token_nonce: u64,
+        lend_metadata: &LendMetadata,
         lend_token_amount: &BigUint,
     ) -> BigUint {
-        let last_valid_claim_epoch = self.last_rewards_update_epoch().get();
-        let lender_rewards_percentage_per_epoch = self.lender_rewards_percentage_per_epoch().get();
+        let rewards_index_at_lend = self.lend_token_rewards_index(lend_token_nonce).get();
+        let current_rewards_index = self.lender_rewards_index().get();
+
+        if !lend_metadata.is_locked() {
+            // the rewards compounded by `processAutoCompound` earn rewards as well
+            let compounded_rewards_amount = lend_token_amount
+                * &self.lend_token_compounded_rewards(lend_token_nonce).get()
+                / DEFAULT_DECIMALS;
+            let rewards_amount = self.compute_reward_amount(
+                &(lend_token_amount + &compounded_rewards_amount),
+                &rewards_index_at_lend,
+                &current_rewards_index,
+            );
+
+            return compounded_rewards_amount + rewards_amount;
+        }
 
-        self.compute_reward_amount(
+        let rewards_amount = self.compute_reward_amount(
             lend_token_amount,
-            lend_epoch,
-            last_valid_claim_epoch,
-            &lender_rewards_percentage_per_epoch,
-        )
+            &rewards_index_at_lend,
+            &current_rewards_index,
+        );
+
+        let current_epoch = self.blockchain().get_block_epoch();
+        let boosted_rewards_amount =
+            self.apply_lock_boost(rewards_amount.clone(), lend_metadata.boost);
+        if current_epoch <= lend_metadata.lock_end_epoch {
+            return boosted_rewards_amount;
+        }
+
+        // only the rewards earned until the lock ended are boosted,
+        // assuming they were earned evenly since the tokens were created
+        let locked_epochs = lend_metadata.lock_end_epoch - lend_metadata.lend_epoch;
+        let epochs_since_lend = current_epoch - lend_metadata.lend_epoch;
+        let extra_rewards_amount = boosted_rewards_amount - &rewards_amount;
+
+        rewards_amount
+            + extra_rewards_amount * BigUint::from(locked_epochs) / BigUint::from(epochs_since_lend)
     }
 
-    fn get_staked_token_value_in_dollars(&self) -> BigUint {
-        let staked_token_ticker = self.staked_token_ticker().get();
-        let opt_price = self.get_price_for_pair(staked_token_ticker, DOLLAR_TICKER.into());
+    /// All tokens of a nonce are created in the same epoch (or second),
+    /// so they all start from the rewards index saved when the nonce was created.
+    /// The index is never moved for an existing nonce, as its tokens would lose their rewards.
+    fn get_or_create_lend_nonce_with_rewards_index(&self, lend_metadata: &LendMetadata) -> u64 {
+        let (lend_nonce, is_new_nonce) = self.get_or_create_lend_token_nonce(lend_metadata);
+        if is_new_nonce {
+            let current_rewards_index = self.lender_rewards_index().get();
+            self.lend_token_rewards_index(lend_nonce)
+                .set(&current_rewards_index);
+        }
 
-        opt_price.unwrap_or_else(|| sc_panic!("Failed to get staked token price"))
+        lend_nonce
+    }
+
+    fn get_current_lend_metadata(&self) -> LendMetadata {
+        let lend_timestamp = match self.time_basis().get() {
+            TimeBasis::Epochs => 0,
+            TimeBasis::Seconds => self.blockchain().get_block_timestamp(),
+        };
+
+        LendMetadata {
+            lend_epoch: self.blockchain().get_block_epoch(),
+            lend_timestamp,
+            lock_end_epoch: 0,
+            boost: 0,
+            auto_compound: false,
+        }
     }
 
     fn get_staking_amount_for_position(&self, liquid_staking_token_nonce: u64) -> BigUint {
@@ -475,11 +1532,4 @@ pub trait SavingsAccount:
         self.blockchain()
             .get_sc_balance(&liquid_staking_token_id, liquid_staking_token_nonce)
     }
-
-    #[storage_mapper("poolParams")]
-    fn pool_params(&self) -> SingleValueMapper<PoolParams<Self::Api>>;
-
-    #[view(getLoadToValuePercentage)]
-    #[storage_mapper("loadToValuePercentage")]
-    fn loan_to_value_percentage(&self) -> SingleValueMapper<BigUint>;
 }
@@ -0,0 +1,347 @@
+elrond_wasm::imports!();
+
+use crate::{
+    math::BASE_PRECISION,
+    model::{BorrowPosition, LiquidateResultType, PositionCollateral},
+};
+
+static INVALID_BORROW_NONCE_ERR_MSG: &[u8] = b"Invalid borrow token nonce";
+pub static POSITION_LIQUIDATED_ERR_MSG: &[u8] = b"Position was liquidated";
+pub static POSITION_HEALTHY_ERR_MSG: &[u8] = b"Position is healthy";
+static POSITION_AUCTIONED_ERR_MSG: &[u8] = b"Position is being auctioned";
+
+#[elrond_wasm::module]
+pub trait LiquidationModule:
+    crate::interest::InterestModule
+    + crate::math::MathModule
+    + crate::ongoing_operation::OngoingOperationModule
+    + crate::price_aggregator_proxy::PriceAggregatorModule
+    + crate::staking_rewards::StakingRewardsModule
+    + crate::tokens::TokensModule
+    + crate::treasury::TreasuryModule
+    + crate::common_storage::CommonStorageModule
+{
+    /// Pays part of the debt of an unhealthy position, up to `close_factor` of the total debt.
+    /// The liquidator receives liquid staking tokens worth the paid amount
+    /// plus the liquidation bonus, capped at the position's collateral.
+    ///
+    /// Only the liquidated fraction of the collateral is released. The Borrow SFTs stay
+    /// with their holders, backed by the remaining collateral and debt.
+    /// If the whole debt is paid, any collateral left can be claimed by the
+    /// Borrow SFT holders through `claimLiquidationSurplus`.
+    #[payable("*")]
+    #[endpoint]
+    fn liquidate(&self, borrow_token_nonce: u64) -> LiquidateResultType<Self::Api> {
+        self.require_no_ongoing_operation();
+
+        let (payment_amount, payment_token) = self.call_value().payment_token_pair();
+        let stablecoin_token_id = self.stablecoin_token_id().get();
+        require!(
+            payment_token == stablecoin_token_id,
+            "May only pay with stablecoins"
+        );
+
+        self.require_not_auctioned(borrow_token_nonce);
+
+        let mut borrow_position = self.get_borrow_position(borrow_token_nonce);
+        require!(
+            borrow_position.borrowed_amount > 0u32,
+            POSITION_LIQUIDATED_ERR_MSG
+        );
+
+        self.accrue_interest();
+        self.update_global_lender_rewards();
+        let staked_token_value_in_dollars = self.get_staked_token_value_in_dollars();
+        let total_debt = self.get_borrow_position_total_debt(&borrow_position);
+        let health_factor = self.get_borrow_position_health_factor(
+            &borrow_position,
+            &staked_token_value_in_dollars,
+            &total_debt,
+        );
+        require!(health_factor < BASE_PRECISION, POSITION_HEALTHY_ERR_MSG);
+
+        let liquidation_params = self.liquidation_params().get();
+        let max_debt_to_cover = &total_debt * &liquidation_params.close_factor / BASE_PRECISION;
+        let debt_covered = if payment_amount > max_debt_to_cover {
+            max_debt_to_cover
+        } else {
+            payment_amount.clone()
+        };
+        require!(debt_covered > 0u32, "Liquidation amount too low");
+
+        let bonus_multiplier =
+            BigUint::from(BASE_PRECISION) + &liquidation_params.liquidation_bonus;
+        let seized_value = &debt_covered * &bonus_multiplier / BASE_PRECISION;
+        let mut seized_amount =
+            self.compute_staking_token_amount(&staked_token_value_in_dollars, &seized_value);
+        if seized_amount > borrow_position.liquid_staking_amount {
+            seized_amount = borrow_position.liquid_staking_amount.clone();
+        }
+
+        // the principal goes back to the pool, the accrued interest is added to the reserves
+        let principal_repaid = &borrow_position.borrowed_amount * &debt_covered / &total_debt;
+        let extra_reserves = &debt_covered - &principal_repaid;
+        borrow_position.borrowed_amount -= &principal_repaid;
+        let seized_collateral = self.take_position_collateral(&mut borrow_position, &seized_amount);
+
+        // no collateral left to back the remaining debt, so it's written off,
+        // and the principal that is lost is recorded as bad debt
+        let mut debt_removed = debt_covered.clone();
+        let mut principal_written_off = BigUint::zero();
+        if borrow_position.liquid_staking_amount == 0u32 {
+            debt_removed = total_debt;
+            principal_written_off = borrow_position.borrowed_amount.clone();
+            borrow_position.borrowed_amount = BigUint::zero();
+        }
+
+        self.borrowed_amount().update(|borrowed_amount| {
+            *borrowed_amount -= &principal_repaid;
+            *borrowed_amount -= &principal_written_off;
+        });
+        self.decrease_total_debt(&debt_removed);
+        self.add_to_reserves(extra_reserves);
+        self.add_bad_debt(principal_written_off.clone());
+
+        if borrow_position.liquid_staking_amount == 0u32 {
+            self.borrow_position(borrow_token_nonce).clear();
+        } else {
+            self.borrow_position(borrow_token_nonce)
+                .set(&borrow_position);
+        }
+
+        let caller = self.blockchain().get_caller();
+        self.emit_liquidate_event(
+            &caller,
+            borrow_token_nonce,
+            &debt_covered,
+            &seized_amount,
+            &principal_written_off,
+        );
+
+        let liquid_staking_payments = self.send_position_collateral(&caller, &seized_collateral);
+
+        let extra_stablecoins_paid = payment_amount - debt_covered;
+        let extra_stablecoins_payment = if extra_stablecoins_paid > 0u32 {
+            self.send_stablecoins(&caller, extra_stablecoins_paid)
+        } else {
+            EsdtTokenPayment::new(stablecoin_token_id, 0, BigUint::zero())
+        };
+
+        (liquid_staking_payments, extra_stablecoins_payment).into()
+    }
+
+    /// Returns the holder's share of the collateral left over after liquidation.
+    #[payable("*")]
+    #[endpoint(claimLiquidationSurplus)]
+    fn claim_liquidation_surplus(&self) -> ManagedVec<EsdtTokenPayment<Self::Api>> {
+        self.require_no_ongoing_operation();
+
+        let payment: EsdtTokenPayment<Self::Api> = self.call_value().payment();
+        let borrow_token_mapper = self.borrow_token();
+        borrow_token_mapper.require_same_token(&payment.token_identifier);
+        self.require_not_auctioned(payment.token_nonce);
+
+        let mut borrow_position = self.get_borrow_position(payment.token_nonce);
+        require!(
+            borrow_position.borrowed_amount == 0u32,
+            "Position was not liquidated"
+        );
+
+        let surplus_amount = &borrow_position.liquid_staking_amount * &payment.amount
+            / &borrow_position.borrow_token_amount;
+        borrow_position.borrow_token_amount -= &payment.amount;
+        let surplus_collateral =
+            self.take_position_collateral(&mut borrow_position, &surplus_amount);
+        if borrow_position.borrow_token_amount == 0u32 {
+            self.borrow_position(payment.token_nonce).clear();
+        } else {
+            self.borrow_position(payment.token_nonce)
+                .set(&borrow_position);
+        }
+
+        borrow_token_mapper.nft_burn(payment.token_nonce, &payment.amount);
+
+        let caller = self.blockchain().get_caller();
+        self.emit_claim_liquidation_surplus_event(
+            &caller,
+            payment.token_nonce,
+            &payment.amount,
+            &surplus_amount,
+        );
+
+        self.send_position_collateral(&caller, &surplus_collateral)
+    }
+
+    #[view(getHealthFactor)]
+    fn get_health_factor(&self, borrow_token_nonce: u64) -> BigUint {
+        let borrow_position = self.get_borrow_position(borrow_token_nonce);
+        require!(
+            borrow_position.borrowed_amount > 0u32,
+            POSITION_LIQUIDATED_ERR_MSG
+        );
+
+        let staked_token_value_in_dollars = self.get_staked_token_value_in_dollars();
+        let total_debt = self.get_borrow_position_total_debt(&borrow_position);
+
+        self.get_borrow_position_health_factor(
+            &borrow_position,
+            &staked_token_value_in_dollars,
+            &total_debt,
+        )
+    }
+
+    fn require_not_auctioned(&self, borrow_token_nonce: u64) {
+        require!(
+            self.auction(borrow_token_nonce).is_empty(),
+            POSITION_AUCTIONED_ERR_MSG
+        );
+    }
+
+    fn get_borrow_position(&self, borrow_token_nonce: u64) -> BorrowPosition<Self::Api> {
+        let mapper = self.borrow_position(borrow_token_nonce);
+        require!(!mapper.is_empty(), INVALID_BORROW_NONCE_ERR_MSG);
+
+        mapper.get()
+    }
+
+    fn get_borrow_position_total_debt(
+        &self,
+        borrow_position: &BorrowPosition<Self::Api>,
+    ) -> BigUint {
+        let current_borrow_index = self.get_current_borrow_index();
+        let debt = self.compute_debt(
+            &borrow_position.borrowed_amount,
+            &borrow_position.metadata.borrow_index,
+            &current_borrow_index,
+        );
+
+        &borrow_position.borrowed_amount + &debt
+    }
+
+    /// Returns the liquid staking amount released, the borrowed amount repaid
+    /// and the interest owed for the given amount of Borrow SFTs.
+    /// After a partial liquidation, each Borrow SFT is backed by less collateral.
+    fn get_repay_amounts(
+        &self,
+        borrow_position: &BorrowPosition<Self::Api>,
+        borrow_token_amount: &BigUint,
+        current_borrow_index: &BigUint,
+    ) -> (BigUint, BigUint, BigUint) {
+        let liquid_staking_amount = &borrow_position.liquid_staking_amount * borrow_token_amount
+            / &borrow_position.borrow_token_amount;
+        let borrow_amount_repaid = &borrow_position.borrowed_amount * borrow_token_amount
+            / &borrow_position.borrow_token_amount;
+        let debt = self.compute_debt(
+            &borrow_amount_repaid,
+            &borrow_position.metadata.borrow_index,
+            current_borrow_index,
+        );
+
+        (liquid_staking_amount, borrow_amount_repaid, debt)
+    }
+
+    /// Takes `amount` out of the position's collateral, from its staking positions in order
+    fn take_position_collateral(
+        &self,
+        borrow_position: &mut BorrowPosition<Self::Api>,
+        amount: &BigUint,
+    ) -> ManagedVec<PositionCollateral<Self::Api>> {
+        let mut amount_left = amount.clone();
+        let mut collateral_taken = ManagedVec::new();
+        let mut collateral_left = ManagedVec::new();
+        for collateral in borrow_position.collateral.iter() {
+            if amount_left == 0u32 {
+                collateral_left.push(collateral);
+                continue;
+            }
+
+            let amount_taken = if collateral.liquid_staking_amount > amount_left {
+                amount_left.clone()
+            } else {
+                collateral.liquid_staking_amount.clone()
+            };
+            amount_left -= &amount_taken;
+
+            let amount_kept = &collateral.liquid_staking_amount - &amount_taken;
+            if amount_kept > 0u32 {
+                collateral_left.push(PositionCollateral {
+                    staking_position_id: collateral.staking_position_id,
+                    liquid_staking_amount: amount_kept,
+                });
+            }
+            collateral_taken.push(PositionCollateral {
+                staking_position_id: collateral.staking_position_id,
+                liquid_staking_amount: amount_taken,
+            });
+        }
+
+        borrow_position.collateral = collateral_left;
+        borrow_position.liquid_staking_amount -= amount;
+
+        collateral_taken
+    }
+
+    fn add_to_collateral(
+        &self,
+        collateral: &mut ManagedVec<PositionCollateral<Self::Api>>,
+        staking_position_id: u64,
+        amount: &BigUint,
+    ) {
+        let mut added = false;
+        let mut new_collateral = ManagedVec::new();
+        for mut collateral_part in collateral.iter() {
+            if collateral_part.staking_position_id == staking_position_id {
+                collateral_part.liquid_staking_amount += amount;
+                added = true;
+            }
+
+            new_collateral.push(collateral_part);
+        }
+        if !added {
+            new_collateral.push(PositionCollateral {
+                staking_position_id,
+                liquid_staking_amount: amount.clone(),
+            });
+        }
+
+        *collateral = new_collateral;
+    }
+
+    /// Sends the collateral, with one payment for each staking position
+    fn send_position_collateral(
+        &self,
+        to: &ManagedAddress,
+        collateral: &ManagedVec<PositionCollateral<Self::Api>>,
+    ) -> ManagedVec<EsdtTokenPayment<Self::Api>> {
+        let mut payments = ManagedVec::new();
+        for collateral_part in collateral.iter() {
+            let payment = self.send_liquid_staking_tokens(
+                to,
+                collateral_part.staking_position_id,
+                &collateral_part.liquid_staking_amount,
+            );
+            payments.push(payment);
+        }
+
+        payments
+    }
+
+    fn get_borrow_position_health_factor(
+        &self,
+        borrow_position: &BorrowPosition<Self::Api>,
+        staked_token_value_in_dollars: &BigUint,
+        total_debt: &BigUint,
+    ) -> BigUint {
+        let collateral_value = self.compute_staking_position_value(
+            staked_token_value_in_dollars,
+            &borrow_position.liquid_staking_amount,
+        );
+        let liquidation_params = self.liquidation_params().get();
+
+        self.compute_health_factor(
+            &collateral_value,
+            &liquidation_params.liquidation_threshold,
+            total_debt,
+        )
+    }
+}
@@ -1,11 +1,52 @@
 elrond_wasm::imports!();
 
+use crate::model::TimeBasis;
+
 pub const BASE_PRECISION: u32 = 1_000_000_000; // Could be reduced maybe? Since we're working with epochs instead of seconds
 pub const DEFAULT_DECIMALS: u64 = 1_000_000_000_000_000_000; // most tokens have 10^18 decimals. TODO: Add as configurable value
-const EPOCHS_IN_YEAR: u32 = 365;
+pub const EPOCHS_IN_YEAR: u64 = 365;
+const SECONDS_IN_YEAR: u64 = EPOCHS_IN_YEAR * 24 * 60 * 60;
 
 #[elrond_wasm::module]
-pub trait MathModule {
+pub trait MathModule: crate::common_storage::CommonStorageModule {
+    /// Returns the time passed since the given checkpoint and the number of periods in a year,
+    /// counted in epochs or seconds, depending on the configured time basis
+    fn get_time_passed_since(&self, last_epoch: u64, last_timestamp: u64) -> (u64, u64) {
+        match self.time_basis().get() {
+            TimeBasis::Epochs => {
+                let current_epoch = self.blockchain().get_block_epoch();
+                (current_epoch - last_epoch, EPOCHS_IN_YEAR)
+            }
+            TimeBasis::Seconds => {
+                let current_timestamp = self.blockchain().get_block_timestamp();
+                (current_timestamp - last_timestamp, SECONDS_IN_YEAR)
+            }
+        }
+    }
+
+    fn get_capital_utilisation(&self) -> BigUint {
+        let lent_amount = self.lent_amount().get();
+        if lent_amount == 0u32 {
+            return BigUint::zero();
+        }
+
+        let total_debt = self.total_debt().get();
+        self.compute_capital_utilisation(&total_debt, &lent_amount)
+    }
+
+    fn get_borrow_rate(&self) -> BigUint {
+        let current_utilisation = self.get_capital_utilisation();
+
+        let pool_params = self.pool_params().get();
+        self.compute_borrow_rate(
+            &pool_params.base_borrow_rate,
+            &pool_params.borrow_rate_under_opt_factor,
+            &pool_params.borrow_rate_over_opt_factor,
+            &pool_params.optimal_utilisation,
+            &current_utilisation,
+        )
+    }
+
     fn compute_borrow_rate(
         &self,
         r_base: &BigUint,
@@ -43,44 +84,153 @@ pub trait MathModule {
         (staked_token_value_in_dollars * staked_amount) / DEFAULT_DECIMALS
     }
 
+    fn compute_staking_token_amount(
+        &self,
+        staked_token_value_in_dollars: &BigUint,
+        value_in_dollars: &BigUint,
+    ) -> BigUint {
+        (value_in_dollars * DEFAULT_DECIMALS) / staked_token_value_in_dollars
+    }
+
     fn compute_borrow_amount(&self, borrow_rate: &BigUint, deposit_value: &BigUint) -> BigUint {
         borrow_rate * deposit_value / BASE_PRECISION
     }
 
-    fn compute_debt(&self, amount: &BigUint, borrow_epoch: u64, borrow_rate: &BigUint) -> BigUint {
-        let current_epoch = self.blockchain().get_block_epoch();
-        let epoch_diff = current_epoch - borrow_epoch;
+    fn compute_borrow_index(
+        &self,
+        borrow_index: &BigUint,
+        borrow_rate: &BigUint,
+        nr_periods: u64,
+        periods_in_year: u64,
+    ) -> BigUint {
+        let rate_per_period = borrow_rate * BASE_PRECISION / periods_in_year;
+        let compound_factor = self.compute_compound_factor(&rate_per_period, nr_periods);
+
+        (borrow_index * &compound_factor) / DEFAULT_DECIMALS
+    }
 
-        let bp = BigUint::from(BASE_PRECISION);
-        let time_unit_percentage = (&bp * epoch_diff) / EPOCHS_IN_YEAR;
-        let debt_percetange = (&time_unit_percentage * borrow_rate) / &bp;
+    /// (1 + rate)^periods, with both the rate and the result in DEFAULT_DECIMALS precision
+    fn compute_compound_factor(&self, rate_per_period: &BigUint, nr_periods: u64) -> BigUint {
+        let mut result = BigUint::from(DEFAULT_DECIMALS);
+        let mut base = BigUint::from(DEFAULT_DECIMALS) + rate_per_period;
+        let mut exponent = nr_periods;
+        while exponent > 0 {
+            if exponent % 2 == 1 {
+                result = &result * &base / DEFAULT_DECIMALS;
+            }
 
-        (&debt_percetange * amount) / bp
+            base = &base * &base / DEFAULT_DECIMALS;
+            exponent /= 2;
+        }
+
+        result
     }
 
-    fn compute_reward_amount_per_epoch(
+    fn compute_debt(
         &self,
         amount: &BigUint,
-        reward_percentage_per_epoch: &BigUint,
+        borrow_index: &BigUint,
+        current_borrow_index: &BigUint,
+    ) -> BigUint {
+        let amount_with_interest = (amount * current_borrow_index) / borrow_index;
+
+        amount_with_interest - amount
+    }
+
+    fn compute_health_factor(
+        &self,
+        collateral_value: &BigUint,
+        liquidation_threshold: &BigUint,
+        total_debt: &BigUint,
+    ) -> BigUint {
+        let max_debt = self.compute_borrow_amount(liquidation_threshold, collateral_value);
+
+        (max_debt * BASE_PRECISION) / total_debt
+    }
+
+    fn get_deposit_rate(&self) -> BigUint {
+        self.get_deposit_rate_for_debt(&self.total_debt().get())
+    }
+
+    /// Deposit rate for the given total debt, instead of the saved one
+    fn get_deposit_rate_for_debt(&self, total_debt: &BigUint) -> BigUint {
+        let lent_amount = self.lent_amount().get();
+        if lent_amount == 0u32 {
+            return BigUint::zero();
+        }
+
+        let current_utilisation = self.compute_capital_utilisation(total_debt, &lent_amount);
+        if current_utilisation == 0u32 {
+            return BigUint::zero();
+        }
+
+        let pool_params = self.pool_params().get();
+        let borrow_rate = self.compute_borrow_rate(
+            &pool_params.base_borrow_rate,
+            &pool_params.borrow_rate_under_opt_factor,
+            &pool_params.borrow_rate_over_opt_factor,
+            &pool_params.optimal_utilisation,
+            &current_utilisation,
+        );
+
+        self.compute_deposit_rate(
+            &current_utilisation,
+            &borrow_rate,
+            &pool_params.reserve_factor,
+        )
+    }
+
+    /// depositRate = utilisation^2 * borrowRate * (1 - reserveFactor)
+    fn compute_deposit_rate(
+        &self,
+        u_current: &BigUint,
+        borrow_rate: &BigUint,
+        reserve_factor: &BigUint,
+    ) -> BigUint {
+        let bp = BigUint::from(BASE_PRECISION);
+        let utilisation_squared = u_current * u_current / &bp;
+        let lenders_share = &bp - reserve_factor;
+
+        utilisation_squared * borrow_rate / &bp * lenders_share / bp
+    }
+
+    /// Rewards per lent token for the given yearly rate, in DEFAULT_DECIMALS precision
+    fn compute_rewards_index_increase(
+        &self,
+        deposit_rate: &BigUint,
+        nr_periods: u64,
+        periods_in_year: u64,
     ) -> BigUint {
-        (reward_percentage_per_epoch * amount) / BASE_PRECISION
+        deposit_rate * BASE_PRECISION * nr_periods / periods_in_year
     }
 
     fn compute_reward_amount(
         &self,
         amount: &BigUint,
-        lend_epoch: u64,
-        last_valid_claim_epoch: u64,
-        reward_percentage_per_epoch: &BigUint,
+        rewards_index_at_lend: &BigUint,
+        current_rewards_index: &BigUint,
     ) -> BigUint {
-        if lend_epoch >= last_valid_claim_epoch {
+        if rewards_index_at_lend >= current_rewards_index {
             return BigUint::zero();
         }
 
-        let epoch_diff = last_valid_claim_epoch - lend_epoch;
-        let reward_amt_per_epoch =
-            self.compute_reward_amount_per_epoch(amount, reward_percentage_per_epoch);
+        amount * &(current_rewards_index - rewards_index_at_lend) / DEFAULT_DECIMALS
+    }
+
+    /// The extra amount a locked lend counts for in the lender rewards
+    fn compute_lock_boost_amount(&self, amount: &BigUint, boost: u64) -> BigUint {
+        if boost == 0 {
+            return BigUint::zero();
+        }
+
+        amount * &BigUint::from(boost - u64::from(BASE_PRECISION)) / BASE_PRECISION
+    }
+
+    fn apply_lock_boost(&self, rewards_amount: BigUint, boost: u64) -> BigUint {
+        if boost == 0 {
+            return rewards_amount;
+        }
 
-        reward_amt_per_epoch * epoch_diff
+        rewards_amount * BigUint::from(boost) / BASE_PRECISION
     }
 }
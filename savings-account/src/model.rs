@@ -1,28 +1,286 @@
 elrond_wasm::imports!();
 elrond_wasm::derive_imports!();
 
+use elrond_wasm::elrond_codec::{
+    self as codec, NestedDecode as _, NestedDecodeInput as _, NestedEncode as _,
+};
+
 pub type LendResultType<M> = EsdtTokenPayment<M>;
 pub type BorrowResultType<M> = MultiValue2<EsdtTokenPayment<M>, EsdtTokenPayment<M>>;
-pub type RepayResultType<M> = MultiValue2<EsdtTokenPayment<M>, EsdtTokenPayment<M>>;
+pub type RepayResultType<M> = MultiValue2<ManagedVec<M, EsdtTokenPayment<M>>, EsdtTokenPayment<M>>;
+pub type RepayInterestResultType<M> = MultiValue2<EsdtTokenPayment<M>, EsdtTokenPayment<M>>;
 pub type WithdrawResultType<M> = EsdtTokenPayment<M>;
+pub type WithdrawCollateralResultType<M> =
+    MultiValue2<EsdtTokenPayment<M>, ManagedVec<M, EsdtTokenPayment<M>>>;
 pub type ClaimRewardsResultType<M> = MultiValue2<EsdtTokenPayment<M>, EsdtTokenPayment<M>>;
+pub type MergeLendTokensResultType<M> = MultiValue2<EsdtTokenPayment<M>, EsdtTokenPayment<M>>;
+pub type LiquidateResultType<M> =
+    MultiValue2<ManagedVec<M, EsdtTokenPayment<M>>, EsdtTokenPayment<M>>;
+pub type BidResultType<M> = MultiValue2<ManagedVec<M, EsdtTokenPayment<M>>, EsdtTokenPayment<M>>;
 
-#[derive(TypeAbi, TopEncode, TopDecode)]
+#[derive(TypeAbi, TopEncode, TopDecode, NestedEncode, NestedDecode)]
 pub struct PoolParams<M: ManagedTypeApi> {
     pub base_borrow_rate: BigUint<M>,
     pub borrow_rate_under_opt_factor: BigUint<M>,
     pub borrow_rate_over_opt_factor: BigUint<M>,
     pub optimal_utilisation: BigUint<M>,
+    pub reserve_factor: BigUint<M>,
 }
 
-#[derive(TypeAbi, TopEncode, TopDecode, PartialEq, Debug)]
+#[derive(TypeAbi, TopEncode, TopDecode, NestedEncode, NestedDecode)]
+pub struct LiquidationParams<M: ManagedTypeApi> {
+    pub liquidation_threshold: BigUint<M>,
+    pub close_factor: BigUint<M>,
+    pub liquidation_bonus: BigUint<M>,
+}
+
+#[derive(TypeAbi, TopEncode, TopDecode, NestedEncode, NestedDecode)]
+pub struct AuctionParams<M: ManagedTypeApi> {
+    pub start_price_premium: BigUint<M>,
+    pub price_decrease_per_round: BigUint<M>,
+    pub min_price_percentage: BigUint<M>,
+    pub max_rounds: u64,
+}
+
+#[derive(TypeAbi, TopEncode, TopDecode)]
+pub struct MarketState<M: ManagedTypeApi> {
+    pub lent_amount: BigUint<M>,
+    pub borrowed_amount: BigUint<M>,
+    pub total_debt: BigUint<M>,
+    pub available_liquidity: BigUint<M>,
+    pub capital_utilisation: BigUint<M>,
+    pub borrow_rate: BigUint<M>,
+    pub deposit_rate: BigUint<M>,
+}
+
+#[derive(TypeAbi, TopEncode, TopDecode, NestedEncode, NestedDecode)]
+pub enum ParamChange<M: ManagedTypeApi> {
+    PoolParams(PoolParams<M>),
+    LoanToValuePercentage(BigUint<M>),
+    PriceAggregatorAddress(ManagedAddress<M>),
+    DelegationScAddress(ManagedAddress<M>),
+    DexSwapScAddress(ManagedAddress<M>),
+    AuctionParams(AuctionParams<M>),
+    LiquidationParams(LiquidationParams<M>),
+    ParamChangeDelay(u64),
+    TimeBasis(TimeBasis),
+}
+
+#[derive(TypeAbi, TopEncode, TopDecode, NestedEncode, NestedDecode)]
+pub struct PendingParamChange<M: ManagedTypeApi> {
+    pub param_change: ParamChange<M>,
+    pub queue_epoch: u64,
+    pub unlock_epoch: u64,
+}
+
+#[derive(TypeAbi, TopEncode, TopDecode)]
+pub struct WithdrawRequest<M: ManagedTypeApi> {
+    pub lender: ManagedAddress<M>,
+    pub lend_nonce: u64,
+    pub lend_amount: BigUint<M>,
+    pub queue_epoch: u64,
+}
+
+#[derive(TypeAbi, TopEncode, TopDecode, NestedEncode, NestedDecode, PartialEq, Clone, Copy)]
+pub enum TimeBasis {
+    Epochs,
+    Seconds,
+}
+
+/// `lend_timestamp` is only set for tokens created while interest is counted in seconds,
+/// `lock_end_epoch` and `boost` are only set for locked tokens,
+/// and `auto_compound` marks tokens whose rewards are added to the principal instead of being paid out.
+/// Fields that are not set are left out of the encoding, so tokens created before they existed stay readable.
+#[derive(TypeAbi, PartialEq, Debug)]
 pub struct LendMetadata {
     pub lend_epoch: u64,
+    pub lend_timestamp: u64,
+    pub lock_end_epoch: u64,
+    pub boost: u64,
+    pub auto_compound: bool,
 }
 
-#[derive(TypeAbi, TopEncode, TopDecode, PartialEq, Debug)]
+impl LendMetadata {
+    pub fn is_locked(&self) -> bool {
+        self.boost != 0
+    }
+}
+
+impl codec::TopEncode for LendMetadata {
+    fn top_encode_or_handle_err<O, H>(&self, output: O, h: H) -> Result<(), H::HandledErr>
+    where
+        O: codec::TopEncodeOutput,
+        H: codec::EncodeErrorHandler,
+    {
+        let mut buffer = output.start_nested_encode();
+        self.lend_epoch.dep_encode_or_handle_err(&mut buffer, h)?;
+        if self.lend_timestamp != 0 || self.is_locked() || self.auto_compound {
+            self.lend_timestamp
+                .dep_encode_or_handle_err(&mut buffer, h)?;
+        }
+        if self.is_locked() || self.auto_compound {
+            self.lock_end_epoch
+                .dep_encode_or_handle_err(&mut buffer, h)?;
+            self.boost.dep_encode_or_handle_err(&mut buffer, h)?;
+        }
+        if self.auto_compound {
+            self.auto_compound
+                .dep_encode_or_handle_err(&mut buffer, h)?;
+        }
+        output.finalize_nested_encode(buffer);
+
+        Ok(())
+    }
+}
+
+impl codec::TopDecode for LendMetadata {
+    fn top_decode_or_handle_err<I, H>(input: I, h: H) -> Result<Self, H::HandledErr>
+    where
+        I: codec::TopDecodeInput,
+        H: codec::DecodeErrorHandler,
+    {
+        let mut buffer = input.into_nested_buffer();
+        let lend_epoch = u64::dep_decode_or_handle_err(&mut buffer, h)?;
+        let lend_timestamp = if buffer.is_depleted() {
+            0
+        } else {
+            u64::dep_decode_or_handle_err(&mut buffer, h)?
+        };
+        let (lock_end_epoch, boost) = if buffer.is_depleted() {
+            (0, 0)
+        } else {
+            (
+                u64::dep_decode_or_handle_err(&mut buffer, h)?,
+                u64::dep_decode_or_handle_err(&mut buffer, h)?,
+            )
+        };
+        let auto_compound = if buffer.is_depleted() {
+            false
+        } else {
+            bool::dep_decode_or_handle_err(&mut buffer, h)?
+        };
+        if !buffer.is_depleted() {
+            return Err(h.handle_error(codec::DecodeError::INPUT_TOO_LONG));
+        }
+
+        Ok(LendMetadata {
+            lend_epoch,
+            lend_timestamp,
+            lock_end_epoch,
+            boost,
+            auto_compound,
+        })
+    }
+}
+
+/// `borrowed_amount` and `borrow_index` are not set for tokens created before they existed,
+/// which are left out of the encoding, as for `LendMetadata`.
+/// Such tokens must be migrated through `migrateBorrowPosition` before they can be used.
+#[derive(TypeAbi, NestedEncode, NestedDecode, PartialEq, Debug)]
 pub struct BorrowMetadata<M: ManagedTypeApi> {
     pub staking_position_id: u64,
     pub borrow_epoch: u64,
     pub staked_token_value_in_dollars_at_borrow: BigUint<M>,
+    pub borrowed_amount: BigUint<M>,
+    pub borrow_index: BigUint<M>,
+}
+
+impl<M: ManagedTypeApi> BorrowMetadata<M> {
+    pub fn is_legacy(&self) -> bool {
+        self.borrow_index == 0u32
+    }
+}
+
+impl<M: ManagedTypeApi> codec::TopEncode for BorrowMetadata<M> {
+    fn top_encode_or_handle_err<O, H>(&self, output: O, h: H) -> Result<(), H::HandledErr>
+    where
+        O: codec::TopEncodeOutput,
+        H: codec::EncodeErrorHandler,
+    {
+        let mut buffer = output.start_nested_encode();
+        self.staking_position_id
+            .dep_encode_or_handle_err(&mut buffer, h)?;
+        self.borrow_epoch.dep_encode_or_handle_err(&mut buffer, h)?;
+        self.staked_token_value_in_dollars_at_borrow
+            .dep_encode_or_handle_err(&mut buffer, h)?;
+        if !self.is_legacy() {
+            self.borrowed_amount
+                .dep_encode_or_handle_err(&mut buffer, h)?;
+            self.borrow_index.dep_encode_or_handle_err(&mut buffer, h)?;
+        }
+        output.finalize_nested_encode(buffer);
+
+        Ok(())
+    }
+}
+
+impl<M: ManagedTypeApi> codec::TopDecode for BorrowMetadata<M> {
+    fn top_decode_or_handle_err<I, H>(input: I, h: H) -> Result<Self, H::HandledErr>
+    where
+        I: codec::TopDecodeInput,
+        H: codec::DecodeErrorHandler,
+    {
+        let mut buffer = input.into_nested_buffer();
+        let staking_position_id = u64::dep_decode_or_handle_err(&mut buffer, h)?;
+        let borrow_epoch = u64::dep_decode_or_handle_err(&mut buffer, h)?;
+        let staked_token_value_in_dollars_at_borrow =
+            BigUint::dep_decode_or_handle_err(&mut buffer, h)?;
+        let (borrowed_amount, borrow_index) = if buffer.is_depleted() {
+            (BigUint::zero(), BigUint::zero())
+        } else {
+            (
+                BigUint::dep_decode_or_handle_err(&mut buffer, h)?,
+                BigUint::dep_decode_or_handle_err(&mut buffer, h)?,
+            )
+        };
+        if !buffer.is_depleted() {
+            return Err(h.handle_error(codec::DecodeError::INPUT_TOO_LONG));
+        }
+
+        Ok(BorrowMetadata {
+            staking_position_id,
+            borrow_epoch,
+            staked_token_value_in_dollars_at_borrow,
+            borrowed_amount,
+            borrow_index,
+        })
+    }
+}
+
+/// Liquid staking tokens backing a Borrow position, held under one of the staking positions
+#[derive(
+    TypeAbi, TopEncode, TopDecode, NestedEncode, NestedDecode, ManagedVecItem, PartialEq, Debug,
+)]
+pub struct PositionCollateral<M: ManagedTypeApi> {
+    pub staking_position_id: u64,
+    pub liquid_staking_amount: BigUint<M>,
+}
+
+/// `collateral` adds up to `liquid_staking_amount`. It may span several staking positions,
+/// as collateral added after a staking rewards claim has a different nonce.
+#[derive(TypeAbi, TopEncode, TopDecode, PartialEq, Debug)]
+pub struct BorrowPosition<M: ManagedTypeApi> {
+    pub metadata: BorrowMetadata<M>,
+    pub borrow_token_amount: BigUint<M>,
+    pub liquid_staking_amount: BigUint<M>,
+    pub collateral: ManagedVec<M, PositionCollateral<M>>,
+    pub borrowed_amount: BigUint<M>,
+}
+
+#[derive(TypeAbi, TopEncode, TopDecode)]
+pub struct RepayQuote<M: ManagedTypeApi> {
+    pub stablecoins_needed: BigUint<M>,
+    pub borrowed_amount: BigUint<M>,
+    pub debt: BigUint<M>,
+    pub liquid_staking_amount: BigUint<M>,
+    pub liquid_staking_tokens: ManagedVec<M, EsdtTokenPayment<M>>,
+    pub collateral_value: BigUint<M>,
+    pub health_factor: BigUint<M>,
+}
+
+#[derive(TypeAbi, TopEncode, TopDecode, PartialEq, Debug)]
+pub struct Auction<M: ManagedTypeApi> {
+    pub start_round: u64,
+    pub start_price: BigUint<M>,
+    pub min_price: BigUint<M>,
 }
@@ -0,0 +1,643 @@
+elrond_wasm::imports!();
+
+use crate::{
+    liquidation::POSITION_LIQUIDATED_ERR_MSG,
+    math::{DEFAULT_DECIMALS, EPOCHS_IN_YEAR},
+    model::{
+        BorrowMetadata, BorrowPosition, BorrowResultType, PositionCollateral,
+        RepayInterestResultType, WithdrawCollateralResultType,
+    },
+};
+
+static ADD_COLLATERAL_INVALID_PAYMENTS_ERR_MSG: &[u8] =
+    b"Must send exactly 2 types of tokens: Borrow SFTs and liquid staking tokens";
+static REPAY_INTEREST_INVALID_PAYMENTS_ERR_MSG: &[u8] =
+    b"Must send exactly 2 types of tokens: Borrow SFTs and stablecoins";
+
+#[elrond_wasm::module]
+pub trait PositionManagementModule:
+    crate::events::EventsModule
+    + crate::interest::InterestModule
+    + crate::liquidation::LiquidationModule
+    + crate::math::MathModule
+    + crate::ongoing_operation::OngoingOperationModule
+    + crate::price_aggregator_proxy::PriceAggregatorModule
+    + crate::staking_rewards::StakingRewardsModule
+    + crate::tokens::TokensModule
+    + crate::treasury::TreasuryModule
+    + crate::withdraw_queue::WithdrawQueueModule
+    + crate::common_storage::CommonStorageModule
+{
+    /// Adds liquid staking tokens to the collateral of an existing position.
+    /// All the position's Borrow SFTs must be sent, and they are reissued under a new nonce.
+    /// The added tokens may have any nonce, e.g. after the position's tokens were moved
+    /// to a new nonce by `claimStakingRewards`. They are kept under their own staking position.
+    #[payable("*")]
+    #[endpoint(addCollateral)]
+    fn add_collateral(&self) -> EsdtTokenPayment<Self::Api> {
+        self.require_no_ongoing_operation();
+
+        let payments = self.call_value().all_esdt_transfers();
+        require!(payments.len() == 2, ADD_COLLATERAL_INVALID_PAYMENTS_ERR_MSG);
+
+        let borrow_payment: EsdtTokenPayment<Self::Api> = payments.get(0);
+        let collateral_payment: EsdtTokenPayment<Self::Api> = payments.get(1);
+
+        let liquid_staking_token_id = self.liquid_staking_token_id().get();
+        require!(
+            collateral_payment.token_identifier == liquid_staking_token_id,
+            ADD_COLLATERAL_INVALID_PAYMENTS_ERR_MSG
+        );
+
+        self.accrue_interest();
+        self.update_global_lender_rewards();
+        let mut borrow_position = self.take_whole_borrow_position(&borrow_payment);
+
+        let staking_position_id = self
+            .staking_positions()
+            .add_staking_position(collateral_payment.token_nonce);
+        self.add_to_collateral(
+            &mut borrow_position.collateral,
+            staking_position_id,
+            &collateral_payment.amount,
+        );
+        borrow_position.liquid_staking_amount += &collateral_payment.amount;
+
+        let caller = self.blockchain().get_caller();
+        let new_borrow_tokens = self.reissue_borrow_position(
+            &caller,
+            borrow_position.metadata,
+            borrow_position.liquid_staking_amount,
+            borrow_position.collateral,
+            borrow_position.borrowed_amount,
+        );
+
+        self.add_collateral_event(
+            &caller,
+            borrow_payment.token_nonce,
+            new_borrow_tokens.token_nonce,
+            &collateral_payment.amount,
+            self.blockchain().get_block_epoch(),
+        );
+
+        new_borrow_tokens
+    }
+
+    /// Borrows more stablecoins against an existing position, up to the loan-to-value limit.
+    /// If no amount is given, the maximum is borrowed.
+    /// All the position's Borrow SFTs must be sent, and they are reissued under a new nonce.
+    #[payable("*")]
+    #[endpoint(borrowMore)]
+    fn borrow_more(
+        &self,
+        opt_borrow_amount: OptionalValue<BigUint>,
+    ) -> BorrowResultType<Self::Api> {
+        self.require_no_ongoing_operation();
+
+        let payment: EsdtTokenPayment<Self::Api> = self.call_value().payment();
+        let current_borrow_index = self.accrue_interest();
+        self.update_global_lender_rewards();
+        let borrow_position = self.take_whole_borrow_position(&payment);
+
+        let position_total_debt = self.get_borrow_position_total_debt(&borrow_position);
+        let staked_token_value_in_dollars = self.get_staked_token_value_in_dollars();
+        let collateral_value = self.compute_staking_position_value(
+            &staked_token_value_in_dollars,
+            &borrow_position.liquid_staking_amount,
+        );
+        let loan_to_value_percentage = self.loan_to_value_percentage().get();
+        let max_total_debt =
+            self.compute_borrow_amount(&loan_to_value_percentage, &collateral_value);
+        require!(
+            max_total_debt > position_total_debt,
+            "Position is at the loan to value limit"
+        );
+
+        let max_borrow_value = &max_total_debt - &position_total_debt;
+        let borrow_value = match opt_borrow_amount {
+            OptionalValue::Some(borrow_amount) => {
+                require!(borrow_amount > 0u32, "Invalid borrow amount");
+                require!(
+                    borrow_amount <= max_borrow_value,
+                    "Borrow amount above loan to value limit"
+                );
+
+                borrow_amount
+            }
+            OptionalValue::None => max_borrow_value,
+        };
+
+        // the index is moved back, so that the interest accrued so far is kept:
+        // newBorrowedAmount * currentIndex / newIndex = totalDebt + borrowValue
+        let new_borrowed_amount = &borrow_position.borrowed_amount + &borrow_value;
+        let new_borrow_index =
+            &new_borrowed_amount * &current_borrow_index / &(position_total_debt + &borrow_value);
+        let metadata = BorrowMetadata {
+            staking_position_id: borrow_position.metadata.staking_position_id,
+            borrow_epoch: self.blockchain().get_block_epoch(),
+            staked_token_value_in_dollars_at_borrow: staked_token_value_in_dollars,
+            borrowed_amount: new_borrowed_amount.clone(),
+            borrow_index: new_borrow_index,
+        };
+
+        let caller = self.blockchain().get_caller();
+        let new_borrow_tokens = self.reissue_borrow_position(
+            &caller,
+            metadata,
+            borrow_position.liquid_staking_amount,
+            borrow_position.collateral,
+            new_borrowed_amount,
+        );
+
+        self.increase_total_debt(&borrow_value);
+        self.increase_borrowed_amount(&borrow_value);
+
+        self.emit_borrow_more_event(
+            &caller,
+            payment.token_nonce,
+            new_borrow_tokens.token_nonce,
+            &borrow_value,
+        );
+
+        let stablecoins_payment = self.send_stablecoins(&caller, borrow_value);
+
+        (new_borrow_tokens, stablecoins_payment).into()
+    }
+
+    /// Releases part of the liquid staking tokens backing a position, as long as
+    /// its total debt stays within the loan-to-value limit of the remaining collateral.
+    /// All the position's Borrow SFTs must be sent, and they are reissued under a new nonce.
+    #[payable("*")]
+    #[endpoint(withdrawCollateral)]
+    fn withdraw_collateral(
+        &self,
+        liquid_staking_amount: BigUint,
+    ) -> WithdrawCollateralResultType<Self::Api> {
+        self.require_no_ongoing_operation();
+
+        let payment: EsdtTokenPayment<Self::Api> = self.call_value().payment();
+        self.accrue_interest();
+        self.update_global_lender_rewards();
+        let mut borrow_position = self.take_whole_borrow_position(&payment);
+        require!(
+            liquid_staking_amount > 0u32
+                && liquid_staking_amount < borrow_position.liquid_staking_amount,
+            "Invalid collateral amount"
+        );
+
+        let position_total_debt = self.get_borrow_position_total_debt(&borrow_position);
+        let withdrawn_collateral =
+            self.take_position_collateral(&mut borrow_position, &liquid_staking_amount);
+        let staked_token_value_in_dollars = self.get_staked_token_value_in_dollars();
+        let collateral_value = self.compute_staking_position_value(
+            &staked_token_value_in_dollars,
+            &borrow_position.liquid_staking_amount,
+        );
+        let loan_to_value_percentage = self.loan_to_value_percentage().get();
+        let max_total_debt =
+            self.compute_borrow_amount(&loan_to_value_percentage, &collateral_value);
+        require!(
+            position_total_debt <= max_total_debt,
+            "Withdrawal would exceed the loan to value limit"
+        );
+
+        let caller = self.blockchain().get_caller();
+        let new_borrow_tokens = self.reissue_borrow_position(
+            &caller,
+            borrow_position.metadata,
+            borrow_position.liquid_staking_amount,
+            borrow_position.collateral,
+            borrow_position.borrowed_amount,
+        );
+
+        self.withdraw_collateral_event(
+            &caller,
+            payment.token_nonce,
+            new_borrow_tokens.token_nonce,
+            &liquid_staking_amount,
+            self.blockchain().get_block_epoch(),
+        );
+
+        let liquid_staking_payments = self.send_position_collateral(&caller, &withdrawn_collateral);
+
+        (new_borrow_tokens, liquid_staking_payments).into()
+    }
+
+    /// Merges the positions of several Borrow SFT nonces backed by the same staking position.
+    /// All the Borrow SFTs of each position must be sent, and they are reissued under a single new nonce.
+    /// The debt accrued by each position is kept.
+    #[payable("*")]
+    #[endpoint(mergeBorrowTokens)]
+    fn merge_borrow_tokens(&self) -> EsdtTokenPayment<Self::Api> {
+        self.require_no_ongoing_operation();
+
+        let payments = self.call_value().all_esdt_transfers();
+        let nr_payments = payments.len();
+        require!(nr_payments >= 2, "Must send at least 2 Borrow SFT payments");
+
+        let current_borrow_index = self.accrue_interest();
+        self.update_global_lender_rewards();
+        let mut staking_position_id = 0;
+        let mut liquid_staking_amount = BigUint::zero();
+        let mut collateral = ManagedVec::new();
+        let mut borrowed_amount = BigUint::zero();
+        let mut total_debt = BigUint::zero();
+        let mut collateral_value_at_borrow = BigUint::zero();
+        for i in 0..nr_payments {
+            let payment: EsdtTokenPayment<Self::Api> = payments.get(i);
+            let borrow_position = self.take_whole_borrow_position(&payment);
+            if i == 0 {
+                staking_position_id = borrow_position.metadata.staking_position_id;
+            } else {
+                require!(
+                    borrow_position.metadata.staking_position_id == staking_position_id,
+                    "Borrow SFTs must share the same staking position"
+                );
+            }
+
+            total_debt += self.get_borrow_position_total_debt(&borrow_position);
+            collateral_value_at_borrow += &borrow_position
+                .metadata
+                .staked_token_value_in_dollars_at_borrow
+                * &borrow_position.liquid_staking_amount;
+            for collateral_part in borrow_position.collateral.iter() {
+                self.add_to_collateral(
+                    &mut collateral,
+                    collateral_part.staking_position_id,
+                    &collateral_part.liquid_staking_amount,
+                );
+            }
+            liquid_staking_amount += borrow_position.liquid_staking_amount;
+            borrowed_amount += borrow_position.borrowed_amount;
+        }
+
+        // the index is moved back, so that the interest accrued so far is kept:
+        // borrowedAmount * currentIndex / newIndex = totalDebt
+        let new_borrow_index = &borrowed_amount * &current_borrow_index / &total_debt;
+        let metadata = BorrowMetadata {
+            staking_position_id,
+            borrow_epoch: self.blockchain().get_block_epoch(),
+            staked_token_value_in_dollars_at_borrow: collateral_value_at_borrow
+                / &liquid_staking_amount,
+            borrowed_amount: borrowed_amount.clone(),
+            borrow_index: new_borrow_index,
+        };
+
+        let caller = self.blockchain().get_caller();
+        let new_borrow_tokens = self.reissue_borrow_position(
+            &caller,
+            metadata,
+            liquid_staking_amount,
+            collateral,
+            borrowed_amount.clone(),
+        );
+
+        self.merge_borrow_tokens_event(
+            &caller,
+            new_borrow_tokens.token_nonce,
+            &new_borrow_tokens.amount,
+            &borrowed_amount,
+            self.blockchain().get_block_epoch(),
+        );
+
+        new_borrow_tokens
+    }
+
+    /// Splits part of a position into a new Borrow SFT nonce, e.g. before selling or transferring it.
+    /// The sent Borrow SFTs take their share of the position's collateral and debt with them,
+    /// and the rest of the position stays with the Borrow SFTs that were not sent.
+    #[payable("*")]
+    #[endpoint(splitBorrowTokens)]
+    fn split_borrow_tokens(&self) -> EsdtTokenPayment<Self::Api> {
+        self.require_no_ongoing_operation();
+
+        let payment: EsdtTokenPayment<Self::Api> = self.call_value().payment();
+        let borrow_token_mapper = self.borrow_token();
+        borrow_token_mapper.require_same_token(&payment.token_identifier);
+
+        let borrow_token_nonce = payment.token_nonce;
+        self.require_not_auctioned(borrow_token_nonce);
+
+        let mut borrow_position = self.get_borrow_position(borrow_token_nonce);
+        require!(
+            borrow_position.borrowed_amount > 0u32,
+            POSITION_LIQUIDATED_ERR_MSG
+        );
+        require!(
+            payment.amount < borrow_position.borrow_token_amount,
+            "Must keep part of the Borrow SFTs of the position"
+        );
+
+        // same split as for repays, so both positions keep the same borrow index
+        let liquid_staking_amount = &borrow_position.liquid_staking_amount * &payment.amount
+            / &borrow_position.borrow_token_amount;
+        let borrowed_amount = &borrow_position.borrowed_amount * &payment.amount
+            / &borrow_position.borrow_token_amount;
+        require!(
+            liquid_staking_amount > 0u32 && borrowed_amount > 0u32,
+            "Split amount too low"
+        );
+
+        let collateral =
+            self.take_position_collateral(&mut borrow_position, &liquid_staking_amount);
+        borrow_position.borrowed_amount -= &borrowed_amount;
+        borrow_position.borrow_token_amount -= &payment.amount;
+        self.borrow_position(borrow_token_nonce)
+            .set(&borrow_position);
+
+        borrow_token_mapper.nft_burn(borrow_token_nonce, &payment.amount);
+
+        let mut metadata = borrow_position.metadata;
+        metadata.borrowed_amount = borrowed_amount.clone();
+
+        let caller = self.blockchain().get_caller();
+        let new_borrow_tokens = self.reissue_borrow_position(
+            &caller,
+            metadata,
+            liquid_staking_amount.clone(),
+            collateral,
+            borrowed_amount.clone(),
+        );
+
+        self.split_borrow_tokens_event(
+            &caller,
+            borrow_token_nonce,
+            new_borrow_tokens.token_nonce,
+            &liquid_staking_amount,
+            &borrowed_amount,
+            self.blockchain().get_block_epoch(),
+        );
+
+        new_borrow_tokens
+    }
+
+    /// Pays the interest accrued by a position, leaving its principal and collateral unchanged.
+    /// All the position's Borrow SFTs must be sent, followed by the stablecoins.
+    /// They are reissued under a new nonce, with interest accruing from the current borrow index.
+    /// Anything above the accrued interest is sent back.
+    #[payable("*")]
+    #[endpoint(repayInterest)]
+    fn repay_interest(&self) -> RepayInterestResultType<Self::Api> {
+        self.require_no_ongoing_operation();
+
+        let payments = self.call_value().all_esdt_transfers();
+        require!(payments.len() == 2, REPAY_INTEREST_INVALID_PAYMENTS_ERR_MSG);
+
+        let borrow_payment: EsdtTokenPayment<Self::Api> = payments.get(0);
+        let stablecoin_payment: EsdtTokenPayment<Self::Api> = payments.get(1);
+
+        let stablecoin_token_id = self.stablecoin_token_id().get();
+        require!(
+            stablecoin_payment.token_identifier == stablecoin_token_id,
+            REPAY_INTEREST_INVALID_PAYMENTS_ERR_MSG
+        );
+
+        let current_borrow_index = self.accrue_interest();
+        self.update_global_lender_rewards();
+        let borrow_position = self.take_whole_borrow_position(&borrow_payment);
+
+        let interest = self.compute_debt(
+            &borrow_position.borrowed_amount,
+            &borrow_position.metadata.borrow_index,
+            &current_borrow_index,
+        );
+        require!(interest > 0u32, "No interest to pay");
+        require!(
+            stablecoin_payment.amount >= interest,
+            "Not enough stablecoins paid to cover the interest"
+        );
+
+        self.decrease_total_debt(&interest);
+        self.add_to_reserves(interest.clone());
+
+        let metadata = BorrowMetadata {
+            staking_position_id: borrow_position.metadata.staking_position_id,
+            borrow_epoch: self.blockchain().get_block_epoch(),
+            staked_token_value_in_dollars_at_borrow: borrow_position
+                .metadata
+                .staked_token_value_in_dollars_at_borrow,
+            borrowed_amount: borrow_position.borrowed_amount.clone(),
+            borrow_index: current_borrow_index,
+        };
+
+        let caller = self.blockchain().get_caller();
+        let new_borrow_tokens = self.reissue_borrow_position(
+            &caller,
+            metadata,
+            borrow_position.liquid_staking_amount,
+            borrow_position.collateral,
+            borrow_position.borrowed_amount,
+        );
+
+        self.emit_repay_interest_event(
+            &caller,
+            borrow_payment.token_nonce,
+            new_borrow_tokens.token_nonce,
+            &interest,
+        );
+
+        let extra_stablecoins_paid = stablecoin_payment.amount - interest;
+        let extra_stablecoins_payment = if extra_stablecoins_paid > 0u32 {
+            self.send_stablecoins(&caller, extra_stablecoins_paid)
+        } else {
+            EsdtTokenPayment::new(stablecoin_token_id, 0, BigUint::zero())
+        };
+
+        (new_borrow_tokens, extra_stablecoins_payment).into()
+    }
+
+    /// Pays stablecoins towards the debt of any position, without receiving its collateral.
+    /// Anything above the position's total debt is sent back.
+    /// Once the whole debt is paid, the Borrow SFT holders can take the collateral back
+    /// through `claimLiquidationSurplus`.
+    #[payable("*")]
+    #[endpoint(repayDebtFor)]
+    fn repay_debt_for(&self, borrow_token_nonce: u64) -> EsdtTokenPayment<Self::Api> {
+        self.require_no_ongoing_operation();
+
+        let (payment_amount, payment_token) = self.call_value().payment_token_pair();
+        let stablecoin_token_id = self.stablecoin_token_id().get();
+        require!(
+            payment_token == stablecoin_token_id,
+            "May only repay with stablecoins"
+        );
+
+        self.require_not_auctioned(borrow_token_nonce);
+        let mut borrow_position = self.get_borrow_position(borrow_token_nonce);
+        require!(
+            borrow_position.borrowed_amount > 0u32,
+            POSITION_LIQUIDATED_ERR_MSG
+        );
+
+        self.accrue_interest();
+        self.update_global_lender_rewards();
+        let total_debt = self.get_borrow_position_total_debt(&borrow_position);
+        let debt_repaid = if payment_amount > total_debt {
+            total_debt.clone()
+        } else {
+            payment_amount.clone()
+        };
+
+        // same split as for liquidations, so the position's borrow index stays valid
+        let principal_repaid = &borrow_position.borrowed_amount * &debt_repaid / &total_debt;
+        let interest_paid = &debt_repaid - &principal_repaid;
+        borrow_position.borrowed_amount -= &principal_repaid;
+        self.borrow_position(borrow_token_nonce)
+            .set(&borrow_position);
+
+        self.borrowed_amount()
+            .update(|borrowed_amount| *borrowed_amount -= &principal_repaid);
+        self.decrease_total_debt(&debt_repaid);
+        self.add_to_reserves(interest_paid.clone());
+
+        let caller = self.blockchain().get_caller();
+        self.emit_repay_debt_for_event(
+            &caller,
+            borrow_token_nonce,
+            &principal_repaid,
+            &interest_paid,
+        );
+
+        let extra_stablecoins_paid = payment_amount - debt_repaid;
+        if extra_stablecoins_paid > 0u32 {
+            self.send_stablecoins(&caller, extra_stablecoins_paid)
+        } else {
+            EsdtTokenPayment::new(stablecoin_token_id, 0, BigUint::zero())
+        }
+    }
+
+    /// Creates the position of Borrow SFTs minted before positions were tracked,
+    /// so they can be repaid and liquidated. `borrow_token_amount` is the amount of
+    /// the nonce still in circulation. These SFTs were minted 1:1 with the liquid staking
+    /// tokens, at the maximum loan to value percentage, which is assumed unchanged since.
+    /// Their interest is counted from the borrow epoch, at the current borrow rate.
+    #[only_owner]
+    #[endpoint(migrateBorrowPosition)]
+    fn migrate_borrow_position(&self, borrow_token_nonce: u64, borrow_token_amount: BigUint) {
+        self.require_no_ongoing_operation();
+        require!(
+            self.borrow_position(borrow_token_nonce).is_empty(),
+            "Position already migrated"
+        );
+        require!(borrow_token_amount > 0u32, "Invalid borrow token amount");
+
+        let mut metadata: BorrowMetadata<Self::Api> =
+            self.borrow_token().get_token_attributes(borrow_token_nonce);
+        require!(metadata.is_legacy(), "Not a legacy Borrow token");
+
+        let current_borrow_index = self.accrue_interest();
+        self.update_global_lender_rewards();
+
+        let staking_position_value = self.compute_staking_position_value(
+            &metadata.staked_token_value_in_dollars_at_borrow,
+            &borrow_token_amount,
+        );
+        let borrowed_amount = self.compute_borrow_amount(
+            &self.loan_to_value_percentage().get(),
+            &staking_position_value,
+        );
+        require!(borrowed_amount > 0u32, "Invalid borrow token amount");
+
+        // the index the position would have started from, had the current rate applied since
+        let epochs_since_borrow = self.blockchain().get_block_epoch() - metadata.borrow_epoch;
+        let interest_factor = self.compute_borrow_index(
+            &BigUint::from(DEFAULT_DECIMALS),
+            &self.get_borrow_rate(),
+            epochs_since_borrow,
+            EPOCHS_IN_YEAR,
+        );
+        let borrow_index = &current_borrow_index * DEFAULT_DECIMALS / interest_factor;
+
+        // the borrowed amount was already counted at borrow time, but not the debt
+        let total_debt = &borrowed_amount * &current_borrow_index / &borrow_index;
+        self.increase_total_debt(&total_debt);
+
+        metadata.borrowed_amount = borrowed_amount.clone();
+        metadata.borrow_index = borrow_index;
+        let mut collateral = ManagedVec::new();
+        collateral.push(PositionCollateral {
+            staking_position_id: metadata.staking_position_id,
+            liquid_staking_amount: borrow_token_amount.clone(),
+        });
+        self.borrow_position(borrow_token_nonce)
+            .set(&BorrowPosition {
+                metadata,
+                borrow_token_amount: borrow_token_amount.clone(),
+                liquid_staking_amount: borrow_token_amount.clone(),
+                collateral,
+                borrowed_amount: borrowed_amount.clone(),
+            });
+
+        self.migrate_borrow_position_event(
+            borrow_token_nonce,
+            &borrow_token_amount,
+            &borrowed_amount,
+            &total_debt,
+            self.blockchain().get_block_epoch(),
+        );
+    }
+
+    /// Burns all the Borrow SFTs of a position and removes it, so it can be reissued
+    fn take_whole_borrow_position(
+        &self,
+        payment: &EsdtTokenPayment<Self::Api>,
+    ) -> BorrowPosition<Self::Api> {
+        let borrow_token_mapper = self.borrow_token();
+        borrow_token_mapper.require_same_token(&payment.token_identifier);
+
+        let borrow_token_nonce = payment.token_nonce;
+        self.require_not_auctioned(borrow_token_nonce);
+
+        let borrow_position = self.get_borrow_position(borrow_token_nonce);
+        require!(
+            borrow_position.borrowed_amount > 0u32,
+            POSITION_LIQUIDATED_ERR_MSG
+        );
+        require!(
+            payment.amount == borrow_position.borrow_token_amount,
+            "Must send all the Borrow SFTs of the position"
+        );
+
+        borrow_token_mapper.nft_burn(borrow_token_nonce, &payment.amount);
+        self.borrow_position(borrow_token_nonce).clear();
+
+        borrow_position
+    }
+
+    /// Creates a new Borrow SFT nonce, with one token per liquid staking token backing it
+    fn reissue_borrow_position(
+        &self,
+        to: &ManagedAddress,
+        metadata: BorrowMetadata<Self::Api>,
+        liquid_staking_amount: BigUint,
+        collateral: ManagedVec<PositionCollateral<Self::Api>>,
+        borrowed_amount: BigUint,
+    ) -> EsdtTokenPayment<Self::Api> {
+        let new_borrow_tokens =
+            self.borrow_token()
+                .nft_create_and_send(to, liquid_staking_amount.clone(), &metadata);
+        self.borrow_position(new_borrow_tokens.token_nonce)
+            .set(&BorrowPosition {
+                metadata,
+                borrow_token_amount: liquid_staking_amount.clone(),
+                liquid_staking_amount,
+                collateral,
+                borrowed_amount,
+            });
+
+        new_borrow_tokens
+    }
+
+    /// The liquidity reserved for queued withdrawals can't be borrowed
+    fn increase_borrowed_amount(&self, amount: &BigUint) {
+        let lent_amount = self.lent_amount().get();
+        let unavailable_amount = self.bad_debt().get() + self.queued_withdraw_amount().get();
+        self.borrowed_amount().update(|total_borrowed| {
+            *total_borrowed += amount;
+            require!(
+                &*total_borrowed + &unavailable_amount <= lent_amount,
+                "Not have enough funds to lend"
+            );
+        });
+    }
+}
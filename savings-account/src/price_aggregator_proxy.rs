@@ -42,16 +42,12 @@ impl<M: ManagedTypeApi> From<AggregatorResultAsMultiResult<M>> for AggregatorRes
 }
 
 #[elrond_wasm::module]
-pub trait PriceAggregatorModule {
-    #[only_owner]
-    #[endpoint(setPriceAggregatorAddress)]
-    fn set_price_aggregator_address(&self, address: ManagedAddress) {
-        require!(
-            self.blockchain().is_smart_contract(&address),
-            "Invalid price aggregator address"
-        );
+pub trait PriceAggregatorModule: crate::tokens::TokensModule {
+    fn get_staked_token_value_in_dollars(&self) -> BigUint {
+        let staked_token_ticker = self.staked_token_ticker().get();
+        let opt_price = self.get_price_for_pair(staked_token_ticker, DOLLAR_TICKER.into());
 
-        self.price_aggregator_address().set(&address);
+        opt_price.unwrap_or_else(|| sc_panic!("Failed to get staked token price"))
     }
 
     fn get_price_for_pair(
@@ -90,6 +90,27 @@ where
         result
     }
 
+    /// Moves the staking position to a new liquid staking nonce,
+    /// e.g. after claiming its rewards
+    pub fn update_liquid_staking_nonce(
+        &mut self,
+        pos_id: StakingPositionId,
+        new_liquid_staking_nonce: LiquidStakingTokenNonce,
+    ) {
+        let old_liquid_staking_nonce = self.update_staking_position(pos_id, |pos| {
+            let old_nonce = pos.liquid_staking_nonce;
+            pos.liquid_staking_nonce = new_liquid_staking_nonce;
+
+            old_nonce
+        });
+
+        let old_nonce_to_id_key = self.build_staking_nonce_to_pos_id_key(old_liquid_staking_nonce);
+        storage_clear(old_nonce_to_id_key.as_ref());
+
+        let new_nonce_to_id_key = self.build_staking_nonce_to_pos_id_key(new_liquid_staking_nonce);
+        storage_set(new_nonce_to_id_key.as_ref(), &pos_id);
+    }
+
     pub fn add_staking_position(
         &mut self,
         liquid_staking_nonce: LiquidStakingTokenNonce,
@@ -136,6 +157,8 @@ where
         }
 
         let pos: StakingPosition = storage_get(current_pos_key.as_ref());
+        let nonce_to_id_key = self.build_staking_nonce_to_pos_id_key(pos.liquid_staking_nonce);
+        storage_clear(nonce_to_id_key.as_ref());
 
         // re-connect nodes
         let prev_pos_key = self.build_staking_pos_key(pos.prev_pos_id);
@@ -48,9 +48,11 @@ pub struct StakingPosition {
 
 #[elrond_wasm::module]
 pub trait StakingRewardsModule:
-    crate::math::MathModule
+    crate::events::EventsModule
+    + crate::math::MathModule
     + crate::ongoing_operation::OngoingOperationModule
     + crate::tokens::TokensModule
+    + crate::treasury::TreasuryModule
     + crate::common_storage::CommonStorageModule
 {
     #[endpoint(claimStakingRewards)]
@@ -128,6 +130,13 @@ pub trait StakingRewardsModule:
         }
 
         if !transfers.is_empty() {
+            self.claim_staking_rewards_event(
+                &self.blockchain().get_caller(),
+                current_epoch,
+                callback_pos_ids.get(0),
+                callback_pos_ids.get(cb_ids_len - 1),
+            );
+
             self.delegation_proxy(self.delegation_sc_address().get())
                 .claim_rewards(transfers)
                 .async_call()
@@ -167,7 +176,7 @@ pub trait StakingRewardsModule:
                     _ => sc_panic!("Invalid operation in callback"),
                 };
 
-                let (_reward_amount, new_liquid_staking_tokens) = payments.into_tuple();
+                let (reward_amount, new_liquid_staking_tokens) = payments.into_tuple();
                 require!(
                     new_liquid_staking_tokens.len() == pos_ids.len(),
                     "Invalid old and new liquid staking position lengths"
@@ -178,14 +187,21 @@ pub trait StakingRewardsModule:
                 // update liquid staking token nonces
                 // needed to know which liquid staking SFT to return on repay
                 for (pos_id, new_token) in pos_ids.iter().zip(new_liquid_staking_tokens.iter()) {
-                    staking_positions_mapper.update_staking_position(pos_id, |pos| {
-                        pos.liquid_staking_nonce = new_token.token_nonce
-                    });
+                    staking_positions_mapper
+                        .update_liquid_staking_nonce(pos_id, new_token.token_nonce);
                 }
 
+                let current_epoch = self.blockchain().get_block_epoch();
                 let last_valid_id = staking_positions_mapper.get_last_valid_staking_pos_id();
-                if last_pos_id == last_valid_id {
-                    let current_epoch = self.blockchain().get_block_epoch();
+                let completed = last_pos_id == last_valid_id;
+                self.staking_rewards_claimed_event(
+                    current_epoch,
+                    last_pos_id,
+                    &reward_amount,
+                    completed,
+                );
+
+                if completed {
                     self.last_staking_rewards_claim_epoch().set(&current_epoch);
                     self.clear_operation();
 
@@ -226,7 +242,7 @@ pub trait StakingRewardsModule:
             .dex_proxy(dex_sc_address)
             .swap_tokens_fixed_input(
                 staking_token_id,
-                staking_token_balance,
+                staking_token_balance.clone(),
                 stablecoin_token_id.clone(),
                 1u32,
             )
@@ -237,8 +253,14 @@ pub trait StakingRewardsModule:
             "Invalid token received from PAIR swap"
         );
 
-        self.stablecoin_reserves()
-            .update(|stablecoin_reserves| *stablecoin_reserves += received_payment.amount);
+        self.convert_staking_token_event(
+            &self.blockchain().get_caller(),
+            current_epoch,
+            &staking_token_balance,
+            &received_payment.amount,
+        );
+
+        self.add_to_reserves(received_payment.amount);
         self.last_staking_token_convert_epoch().set(current_epoch);
 
         self.update_global_lender_rewards();
@@ -246,25 +268,37 @@ pub trait StakingRewardsModule:
 
     fn update_global_lender_rewards(&self) {
         let current_epoch = self.blockchain().get_block_epoch();
-        let last_rewards_update_epoch = self.last_rewards_update_epoch().get();
+        let current_timestamp = self.blockchain().get_block_timestamp();
         let total_lent_amount = self.lent_amount().get();
-        let extra_rewards_needed = if last_rewards_update_epoch < current_epoch {
+        let last_update_epoch = self.last_rewards_update_epoch().get();
+        let (time_passed, periods_in_year) = self.get_time_passed_since(
+            last_update_epoch,
+            self.last_rewards_update_timestamp().get(),
+        );
+        let extra_rewards_needed = if time_passed > 0 {
             self.total_missed_rewards_by_claim_since_last_calculation()
                 .clear();
 
-            let reward_percentage_per_epoch = self.lender_rewards_percentage_per_epoch().get();
-            self.compute_reward_amount(
-                &total_lent_amount,
-                last_rewards_update_epoch,
-                current_epoch,
-                &reward_percentage_per_epoch,
-            )
+            let deposit_rate = self.get_deposit_rate();
+            let rewards_index_increase =
+                self.compute_rewards_index_increase(&deposit_rate, time_passed, periods_in_year);
+            self.lender_rewards_index()
+                .update(|rewards_index| *rewards_index += &rewards_index_increase);
+
+            // locked lends earn boosted rewards, and auto-compounded rewards earn rewards as well
+            let lock_boost_amount =
+                self.release_ended_lock_boosts(last_update_epoch, current_epoch);
+            let rewards_base_amount = &total_lent_amount
+                + &lock_boost_amount
+                + &self.auto_compounded_rewards_amount().get();
+            rewards_base_amount * &rewards_index_increase / DEFAULT_DECIMALS
         } else {
             BigUint::zero()
         };
 
         let mut stablecoin_reserves = self.stablecoin_reserves().get();
-        let mut missing_rewards = self.missing_rewards().get();
+        let old_missing_rewards = self.missing_rewards().get();
+        let mut missing_rewards = old_missing_rewards.clone();
         if extra_rewards_needed > stablecoin_reserves {
             let extra_missing_rewards = &extra_rewards_needed - &stablecoin_reserves;
             missing_rewards += extra_missing_rewards;
@@ -294,10 +328,102 @@ pub trait StakingRewardsModule:
                 / total_lent_amount
         };
 
+        let old_penalty_per_lend_token = self.penalty_per_lend_token().get();
+        if penalty_per_lend_token != old_penalty_per_lend_token {
+            self.penalty_per_lend_token_changed_event(
+                current_epoch,
+                &old_penalty_per_lend_token,
+                &penalty_per_lend_token,
+            );
+        }
+        if missing_rewards != old_missing_rewards {
+            self.missing_rewards_changed_event(
+                current_epoch,
+                &old_missing_rewards,
+                &missing_rewards,
+            );
+        }
+
         self.penalty_per_lend_token().set(&penalty_per_lend_token);
         self.missing_rewards().set(&missing_rewards);
         self.stablecoin_reserves().set(&stablecoin_reserves);
         self.last_rewards_update_epoch().set(current_epoch);
+        self.last_rewards_update_timestamp().set(current_timestamp);
+    }
+
+    /// Returns the lock boost amount that earned rewards since the last update.
+    /// Locks that ended in the meantime only count until their end epoch,
+    /// and are then dropped, even if their holders never touch the LEND tokens again.
+    fn release_ended_lock_boosts(&self, last_update_epoch: u64, current_epoch: u64) -> BigUint {
+        let mut lock_boost_by_end_epoch_mapper = self.lock_boost_amount_by_end_epoch();
+        let mut ended_lock_end_epochs = ManagedVec::<Self::Api, u64>::new();
+        let mut ended_lock_boost_amount = BigUint::zero();
+        let mut boosted_epochs_amount = BigUint::zero();
+        for (lock_end_epoch, amount) in lock_boost_by_end_epoch_mapper.iter() {
+            if lock_end_epoch >= current_epoch {
+                continue;
+            }
+
+            let boosted_epochs = lock_end_epoch.saturating_sub(last_update_epoch);
+            boosted_epochs_amount += &amount * &BigUint::from(boosted_epochs);
+            ended_lock_boost_amount += amount;
+            ended_lock_end_epochs.push(lock_end_epoch);
+        }
+        if ended_lock_end_epochs.is_empty() {
+            return self.lock_boost_amount().get();
+        }
+
+        for lock_end_epoch in ended_lock_end_epochs.iter() {
+            let _ = lock_boost_by_end_epoch_mapper.remove(&lock_end_epoch);
+        }
+
+        let mut lock_boost_amount = self.lock_boost_amount().get();
+        if lock_boost_amount > ended_lock_boost_amount {
+            lock_boost_amount -= ended_lock_boost_amount;
+        } else {
+            lock_boost_amount = BigUint::zero();
+        }
+        self.lock_boost_amount().set(&lock_boost_amount);
+
+        // the ended locks are boosted for the epochs until their end, out of the whole period
+        let epochs_since_last_update = current_epoch - last_update_epoch;
+        lock_boost_amount + boosted_epochs_amount / BigUint::from(epochs_since_last_update)
+    }
+
+    fn send_liquid_staking_tokens(
+        &self,
+        to: &ManagedAddress,
+        staking_position_id: u64,
+        amount: &BigUint,
+    ) -> EsdtTokenPayment<Self::Api> {
+        let mut staking_positions_mapper = self.staking_positions();
+        let liquid_staking_token_id = self.liquid_staking_token_id().get();
+        let liquid_staking_nonce = staking_positions_mapper
+            .get_staking_position(staking_position_id)
+            .liquid_staking_nonce;
+
+        let liquid_staking_tokens_for_nonce = self
+            .blockchain()
+            .get_sc_balance(&liquid_staking_token_id, liquid_staking_nonce);
+
+        // no tokens left after transfer, so we clear the entry
+        if &liquid_staking_tokens_for_nonce == amount {
+            staking_positions_mapper.remove_staking_position(staking_position_id);
+        }
+
+        self.send().direct(
+            to,
+            &liquid_staking_token_id,
+            liquid_staking_nonce,
+            amount,
+            &[],
+        );
+
+        EsdtTokenPayment::new(
+            liquid_staking_token_id,
+            liquid_staking_nonce,
+            amount.clone(),
+        )
     }
 
     #[proxy]
@@ -328,9 +454,29 @@ pub trait StakingRewardsModule:
     #[storage_mapper("lastRewardsUpdateEpoch")]
     fn last_rewards_update_epoch(&self) -> SingleValueMapper<u64>;
 
-    #[view(getLenderRewardsPercentagePerEpoch)]
-    #[storage_mapper("lenderRewardsPercentagePerEpoch")]
-    fn lender_rewards_percentage_per_epoch(&self) -> SingleValueMapper<BigUint>;
+    #[storage_mapper("lastRewardsUpdateTimestamp")]
+    fn last_rewards_update_timestamp(&self) -> SingleValueMapper<u64>;
+
+    #[view(getLenderRewardsIndex)]
+    #[storage_mapper("lenderRewardsIndex")]
+    fn lender_rewards_index(&self) -> SingleValueMapper<BigUint>;
+
+    #[storage_mapper("lendTokenRewardsIndex")]
+    fn lend_token_rewards_index(&self, lend_token_nonce: u64) -> SingleValueMapper<BigUint>;
+
+    /// Rewards per LEND token already compounded by `processAutoCompound`, with 18 decimals
+    #[storage_mapper("lendTokenCompoundedRewards")]
+    fn lend_token_compounded_rewards(&self, lend_token_nonce: u64) -> SingleValueMapper<BigUint>;
+
+    #[storage_mapper("autoCompoundLendTokenSupply")]
+    fn auto_compound_lend_token_supply(&self, lend_token_nonce: u64) -> SingleValueMapper<BigUint>;
+
+    #[storage_mapper("autoCompoundLendNonces")]
+    fn auto_compound_lend_nonces(&self) -> QueueMapper<u64>;
+
+    #[view(getAutoCompoundedRewardsAmount)]
+    #[storage_mapper("autoCompoundedRewardsAmount")]
+    fn auto_compounded_rewards_amount(&self) -> SingleValueMapper<BigUint>;
 
     #[storage_mapper("missingRewards")]
     fn missing_rewards(&self) -> SingleValueMapper<BigUint>;
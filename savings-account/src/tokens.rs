@@ -5,6 +5,7 @@ elrond_wasm::derive_imports!();
 
 static LEND_TOKEN_TICKER: &[u8] = b"LEND";
 static BORROW_TOKEN_TICKER: &[u8] = b"BORROW";
+static SHARE_TOKEN_TICKER: &[u8] = b"LENDSHARE";
 static TOKEN_ALREADY_ISSUED_ERR_MSG: &[u8] = b"Token already issued";
 const INITIAL_SFT_AMOUNT: u32 = 1;
 
@@ -21,6 +22,7 @@ pub trait TokensModule {
             payment_amount,
             token_name,
             LEND_TOKEN_TICKER.into(),
+            EsdtTokenType::Meta,
             num_decimals,
         )
         .call_and_exit();
@@ -37,6 +39,25 @@ pub trait TokensModule {
             payment_amount,
             token_name,
             BORROW_TOKEN_TICKER.into(),
+            EsdtTokenType::Meta,
+            num_decimals,
+        )
+        .call_and_exit();
+    }
+
+    /// Issues the fungible token received when lending through `lendForShares`
+    #[only_owner]
+    #[payable("EGLD")]
+    #[endpoint(issueShareToken)]
+    fn issue_share_token(&self, token_name: ManagedBuffer, num_decimals: usize) {
+        require!(self.share_token().is_empty(), TOKEN_ALREADY_ISSUED_ERR_MSG);
+
+        let payment_amount = self.call_value().egld_value();
+        self.issue_token(
+            payment_amount,
+            token_name,
+            SHARE_TOKEN_TICKER.into(),
+            EsdtTokenType::Fungible,
             num_decimals,
         )
         .call_and_exit();
@@ -47,6 +68,7 @@ pub trait TokensModule {
         issue_cost: BigUint,
         token_name: ManagedBuffer,
         token_ticker: ManagedBuffer,
+        token_type: EsdtTokenType,
         num_decimals: usize,
     ) -> AsyncCall {
         ESDTSystemSmartContractProxy::new_proxy_obj()
@@ -54,7 +76,7 @@ pub trait TokensModule {
                 issue_cost,
                 token_name,
                 token_ticker.clone(),
-                EsdtTokenType::Meta,
+                token_type,
                 num_decimals,
             )
             .async_call()
@@ -73,19 +95,38 @@ pub trait TokensModule {
         EsdtTokenPayment::new(stablecoin_token_id, 0, amount)
     }
 
-    fn get_or_create_lend_token_nonce(&self, lend_epoch: u64) -> u64 {
-        let mapper = self.lend_epoch_to_token_nonce(lend_epoch);
+    /// Returns the nonce for the given attributes, and whether it was just created
+    fn get_or_create_lend_token_nonce(&self, lend_metadata: &LendMetadata) -> (u64, bool) {
+        let mapper = if lend_metadata.auto_compound {
+            self.auto_compound_lend_token_nonce(
+                lend_metadata.lend_epoch,
+                lend_metadata.lend_timestamp,
+                lend_metadata.lock_end_epoch,
+                lend_metadata.boost,
+            )
+        } else if lend_metadata.is_locked() {
+            self.locked_lend_token_nonce(
+                lend_metadata.lend_epoch,
+                lend_metadata.lend_timestamp,
+                lend_metadata.lock_end_epoch,
+                lend_metadata.boost,
+            )
+        } else if lend_metadata.lend_timestamp == 0 {
+            self.lend_epoch_to_token_nonce(lend_metadata.lend_epoch)
+        } else {
+            self.lend_timestamp_to_token_nonce(lend_metadata.lend_timestamp)
+        };
         let existing_nonce = mapper.get();
         if existing_nonce != 0 {
-            return existing_nonce;
+            return (existing_nonce, false);
         }
 
         let new_lend_token = self
             .lend_token()
-            .nft_create(INITIAL_SFT_AMOUNT.into(), &LendMetadata { lend_epoch });
+            .nft_create(INITIAL_SFT_AMOUNT.into(), lend_metadata);
         mapper.set(new_lend_token.token_nonce);
 
-        new_lend_token.token_nonce
+        (new_lend_token.token_nonce, true)
     }
 
     // callbacks
@@ -102,6 +143,8 @@ pub trait TokensModule {
                     self.lend_token().set_token_id(&token_id);
                 } else if token_ticker == ManagedBuffer::new_from_bytes(BORROW_TOKEN_TICKER) {
                     self.borrow_token().set_token_id(&token_id);
+                } else if token_ticker == ManagedBuffer::new_from_bytes(SHARE_TOKEN_TICKER) {
+                    self.share_token().set_token_id(&token_id);
                 } else {
                     self.issue_callback_refund();
                 }
@@ -146,6 +189,31 @@ pub trait TokensModule {
     #[storage_mapper("lendEpochToTokenNonce")]
     fn lend_epoch_to_token_nonce(&self, lend_epoch: u64) -> SingleValueMapper<u64>;
 
+    #[storage_mapper("lendTimestampToTokenNonce")]
+    fn lend_timestamp_to_token_nonce(&self, lend_timestamp: u64) -> SingleValueMapper<u64>;
+
+    #[view(getShareTokenId)]
+    #[storage_mapper("shareTokenId")]
+    fn share_token(&self) -> FungibleTokenMapper<Self::Api>;
+
+    #[storage_mapper("lockedLendTokenNonce")]
+    fn locked_lend_token_nonce(
+        &self,
+        lend_epoch: u64,
+        lend_timestamp: u64,
+        lock_end_epoch: u64,
+        boost: u64,
+    ) -> SingleValueMapper<u64>;
+
+    #[storage_mapper("autoCompoundLendTokenNonce")]
+    fn auto_compound_lend_token_nonce(
+        &self,
+        lend_epoch: u64,
+        lend_timestamp: u64,
+        lock_end_epoch: u64,
+        boost: u64,
+    ) -> SingleValueMapper<u64>;
+
     #[view(getBorrowTokenId)]
     #[storage_mapper("borrowTokenId")]
     fn borrow_token(&self) -> NonFungibleTokenMapper<Self::Api>;
@@ -0,0 +1,118 @@
+elrond_wasm::imports!();
+
+use crate::math::BASE_PRECISION;
+
+#[elrond_wasm::module]
+pub trait TreasuryModule:
+    crate::events::EventsModule
+    + crate::math::MathModule
+    + crate::tokens::TokensModule
+    + crate::common_storage::CommonStorageModule
+{
+    /// Sends the protocol revenue to the treasury address. If no amount is given, everything is sent.
+    /// Stablecoin reserves, which are used to pay lenders, can not be withdrawn.
+    #[only_owner]
+    #[endpoint(withdrawProtocolRevenue)]
+    fn withdraw_protocol_revenue(
+        &self,
+        treasury_address: ManagedAddress,
+        opt_amount: OptionalValue<BigUint>,
+    ) -> EsdtTokenPayment<Self::Api> {
+        let protocol_revenue = self.protocol_revenue().get();
+        let amount = match opt_amount {
+            OptionalValue::Some(amount) => amount,
+            OptionalValue::None => protocol_revenue.clone(),
+        };
+        require!(amount > 0u32, "No protocol revenue to withdraw");
+        require!(amount <= protocol_revenue, "Not enough protocol revenue");
+
+        self.protocol_revenue().set(&(protocol_revenue - &amount));
+
+        self.emit_withdraw_protocol_revenue_event(
+            &self.blockchain().get_caller(),
+            &treasury_address,
+            &amount,
+        );
+
+        self.send_stablecoins(&treasury_address, amount)
+    }
+
+    /// Splits interest and staking yield between the protocol revenue and the stablecoin reserves,
+    /// according to the reserve factor. Bad debt and unfunded rewards IOUs are paid from the
+    /// reserves share first.
+    fn add_to_reserves(&self, amount: BigUint) {
+        if amount == 0u32 {
+            return;
+        }
+
+        let pool_params = self.pool_params().get();
+        let protocol_share = &amount * &pool_params.reserve_factor / BASE_PRECISION;
+        let mut reserves_share = &amount - &protocol_share;
+
+        // lent stablecoins lost to bad debt are paid back first
+        let bad_debt = self.bad_debt().get();
+        let mut bad_debt_share = BigUint::zero();
+        if bad_debt > 0u32 {
+            bad_debt_share = if bad_debt < reserves_share {
+                bad_debt.clone()
+            } else {
+                reserves_share.clone()
+            };
+            reserves_share -= &bad_debt_share;
+            self.bad_debt().set(&(bad_debt - &bad_debt_share));
+        }
+
+        // rewards lost by lenders to the penalty are paid back first
+        let total_rewards_iou = self.total_rewards_iou().get();
+        let rewards_iou_funds = self.rewards_iou_funds().get();
+        let mut iou_share = BigUint::zero();
+        if total_rewards_iou > rewards_iou_funds {
+            let unfunded_iou = total_rewards_iou - &rewards_iou_funds;
+            iou_share = if unfunded_iou < reserves_share {
+                unfunded_iou
+            } else {
+                reserves_share.clone()
+            };
+            reserves_share -= &iou_share;
+            self.rewards_iou_funds()
+                .set(&(rewards_iou_funds + &iou_share));
+        }
+
+        self.protocol_revenue()
+            .update(|protocol_revenue| *protocol_revenue += &protocol_share);
+        self.stablecoin_reserves()
+            .update(|stablecoin_reserves| *stablecoin_reserves += &reserves_share);
+
+        self.emit_add_to_reserves_event(
+            &amount,
+            &protocol_share,
+            &reserves_share,
+            &bad_debt_share,
+            &iou_share,
+        );
+    }
+
+    /// Records borrowed stablecoins that will never be repaid. They are covered from the
+    /// stablecoin reserves as far as these allow, and the rest is paid back from the next
+    /// stablecoins added to the reserves. Until then, they can't be withdrawn or borrowed.
+    fn add_bad_debt(&self, amount: BigUint) {
+        if amount == 0u32 {
+            return;
+        }
+
+        let stablecoin_reserves = self.stablecoin_reserves().get();
+        let covered_amount = if amount < stablecoin_reserves {
+            amount.clone()
+        } else {
+            stablecoin_reserves.clone()
+        };
+        self.stablecoin_reserves()
+            .set(&(stablecoin_reserves - &covered_amount));
+        self.bad_debt()
+            .update(|bad_debt| *bad_debt += amount - covered_amount);
+    }
+
+    #[view(getProtocolRevenue)]
+    #[storage_mapper("protocolRevenue")]
+    fn protocol_revenue(&self) -> SingleValueMapper<BigUint>;
+}
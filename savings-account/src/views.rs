@@ -0,0 +1,186 @@
+elrond_wasm::imports!();
+
+use crate::{
+    liquidation::POSITION_LIQUIDATED_ERR_MSG,
+    model::{BorrowPosition, MarketState, RepayQuote},
+};
+
+/// Read-only market figures, projected to the current epoch or second,
+/// so front-ends don't have to reimplement the interest math off-chain.
+#[elrond_wasm::module]
+pub trait ViewsModule:
+    crate::interest::InterestModule
+    + crate::liquidation::LiquidationModule
+    + crate::math::MathModule
+    + crate::withdraw_queue::WithdrawQueueModule
+    + crate::common_storage::CommonStorageModule
+{
+    #[view(getCapitalUtilisation)]
+    fn get_capital_utilisation_view(&self) -> BigUint {
+        self.get_market_state().capital_utilisation
+    }
+
+    #[view(getBorrowRate)]
+    fn get_borrow_rate_view(&self) -> BigUint {
+        self.get_market_state().borrow_rate
+    }
+
+    #[view(getDepositRate)]
+    fn get_deposit_rate_view(&self) -> BigUint {
+        self.get_market_state().deposit_rate
+    }
+
+    #[view(getCurrentTotalDebt)]
+    fn get_current_total_debt_view(&self) -> BigUint {
+        self.get_current_total_debt()
+    }
+
+    /// Stablecoins that can be borrowed or withdrawn right away.
+    /// The liquidity reserved for queued withdrawals is left out.
+    #[view(getAvailableLiquidity)]
+    fn get_available_liquidity(&self) -> BigUint {
+        let lent_amount = self.lent_amount().get();
+        let borrowed_amount = self.borrowed_amount().get();
+
+        self.compute_available_liquidity(&lent_amount, &borrowed_amount)
+    }
+
+    #[view(getMarketState)]
+    fn get_market_state(&self) -> MarketState<Self::Api> {
+        let lent_amount = self.lent_amount().get();
+        let borrowed_amount = self.borrowed_amount().get();
+        let total_debt = self.get_current_total_debt();
+        let available_liquidity = self.compute_available_liquidity(&lent_amount, &borrowed_amount);
+
+        let capital_utilisation = if lent_amount > 0u32 {
+            self.compute_capital_utilisation(&total_debt, &lent_amount)
+        } else {
+            BigUint::zero()
+        };
+        let pool_params = self.pool_params().get();
+        let borrow_rate = self.compute_borrow_rate(
+            &pool_params.base_borrow_rate,
+            &pool_params.borrow_rate_under_opt_factor,
+            &pool_params.borrow_rate_over_opt_factor,
+            &pool_params.optimal_utilisation,
+            &capital_utilisation,
+        );
+        let deposit_rate = self.compute_deposit_rate(
+            &capital_utilisation,
+            &borrow_rate,
+            &pool_params.reserve_factor,
+        );
+
+        MarketState {
+            lent_amount,
+            borrowed_amount,
+            total_debt,
+            available_liquidity,
+            capital_utilisation,
+            borrow_rate,
+            deposit_rate,
+        }
+    }
+
+    /// Stablecoins that must be sent along with the given Borrow SFTs to `repay`
+    #[view(getRepayAmount)]
+    fn get_repay_amount(&self, borrow_token_nonce: u64, borrow_token_amount: BigUint) -> BigUint {
+        let borrow_position =
+            self.get_repayable_borrow_position(borrow_token_nonce, &borrow_token_amount);
+        let current_borrow_index = self.get_current_borrow_index();
+        let (_, borrowed_amount, debt) = self.get_repay_amounts(
+            &borrow_position,
+            &borrow_token_amount,
+            &current_borrow_index,
+        );
+
+        borrowed_amount + debt
+    }
+
+    /// The debt and collateral backing the given Borrow SFTs.
+    /// The health factor is the one of the whole position.
+    #[view(getRepayQuote)]
+    fn get_repay_quote(
+        &self,
+        borrow_token_nonce: u64,
+        borrow_token_amount: BigUint,
+    ) -> RepayQuote<Self::Api> {
+        let mut borrow_position =
+            self.get_repayable_borrow_position(borrow_token_nonce, &borrow_token_amount);
+        let current_borrow_index = self.get_current_borrow_index();
+        let (liquid_staking_amount, borrowed_amount, debt) = self.get_repay_amounts(
+            &borrow_position,
+            &borrow_token_amount,
+            &current_borrow_index,
+        );
+
+        let staked_token_value_in_dollars = self.get_staked_token_value_in_dollars();
+        let collateral_value = self
+            .compute_staking_position_value(&staked_token_value_in_dollars, &liquid_staking_amount);
+        let position_total_debt = self.get_borrow_position_total_debt(&borrow_position);
+        let health_factor = self.get_borrow_position_health_factor(
+            &borrow_position,
+            &staked_token_value_in_dollars,
+            &position_total_debt,
+        );
+
+        let liquid_staking_token_id = self.liquid_staking_token_id().get();
+        let staking_positions_mapper = self.staking_positions();
+        let released_collateral =
+            self.take_position_collateral(&mut borrow_position, &liquid_staking_amount);
+        let mut liquid_staking_tokens = ManagedVec::new();
+        for collateral_part in released_collateral.iter() {
+            let liquid_staking_nonce = staking_positions_mapper
+                .get_staking_position(collateral_part.staking_position_id)
+                .liquid_staking_nonce;
+            liquid_staking_tokens.push(EsdtTokenPayment::new(
+                liquid_staking_token_id.clone(),
+                liquid_staking_nonce,
+                collateral_part.liquid_staking_amount,
+            ));
+        }
+
+        RepayQuote {
+            stablecoins_needed: &borrowed_amount + &debt,
+            borrowed_amount,
+            debt,
+            liquid_staking_amount,
+            liquid_staking_tokens,
+            collateral_value,
+            health_factor,
+        }
+    }
+
+    fn compute_available_liquidity(
+        &self,
+        lent_amount: &BigUint,
+        borrowed_amount: &BigUint,
+    ) -> BigUint {
+        let unavailable_amount =
+            borrowed_amount + &self.bad_debt().get() + self.queued_withdraw_amount().get();
+        if *lent_amount > unavailable_amount {
+            lent_amount - &unavailable_amount
+        } else {
+            BigUint::zero()
+        }
+    }
+
+    fn get_repayable_borrow_position(
+        &self,
+        borrow_token_nonce: u64,
+        borrow_token_amount: &BigUint,
+    ) -> BorrowPosition<Self::Api> {
+        let borrow_position = self.get_borrow_position(borrow_token_nonce);
+        require!(
+            borrow_position.borrowed_amount > 0u32,
+            POSITION_LIQUIDATED_ERR_MSG
+        );
+        require!(
+            *borrow_token_amount > 0u32
+                && *borrow_token_amount <= borrow_position.borrow_token_amount,
+            "Invalid Borrow SFT amount"
+        );
+
+        borrow_position
+    }
+}
@@ -0,0 +1,46 @@
+elrond_wasm::imports!();
+
+use crate::model::WithdrawRequest;
+
+#[elrond_wasm::module]
+pub trait WithdrawQueueModule {
+    #[view(getWithdrawRequest)]
+    fn get_withdraw_request(&self, ticket_id: u64) -> WithdrawRequest<Self::Api> {
+        let mapper = self.withdraw_request(ticket_id);
+        require!(!mapper.is_empty(), "Unknown withdraw request");
+
+        mapper.get()
+    }
+
+    /// Ticket IDs of the requests waiting to be served, in order.
+    /// Cancelled requests are only removed once they reach the front of the queue.
+    #[view(getWithdrawQueue)]
+    fn get_withdraw_queue(&self) -> MultiValueEncoded<u64> {
+        let mut result = MultiValueEncoded::new();
+        for ticket_id in self.withdraw_queue().iter() {
+            if !self.withdraw_request(ticket_id).is_empty() {
+                result.push(ticket_id);
+            }
+        }
+
+        result
+    }
+
+    #[view(getQueuedWithdrawAmount)]
+    #[storage_mapper("queuedWithdrawAmount")]
+    fn queued_withdraw_amount(&self) -> SingleValueMapper<BigUint>;
+
+    #[storage_mapper("lastWithdrawTicketId")]
+    fn last_withdraw_ticket_id(&self) -> SingleValueMapper<u64>;
+
+    #[storage_mapper("withdrawQueue")]
+    fn withdraw_queue(&self) -> QueueMapper<u64>;
+
+    #[storage_mapper("withdrawRequest")]
+    fn withdraw_request(&self, ticket_id: u64) -> SingleValueMapper<WithdrawRequest<Self::Api>>;
+
+    /// Stablecoins of the served requests, waiting to be claimed by the lender
+    #[view(getWithdrawQueuePayout)]
+    #[storage_mapper("withdrawQueuePayout")]
+    fn withdraw_queue_payout(&self, lender: &ManagedAddress) -> SingleValueMapper<BigUint>;
+}
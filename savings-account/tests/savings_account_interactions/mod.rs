@@ -1,194 +1,1095 @@
 use crate::savings_account_setup::{
     SavingsAccountSetup, BORROW_TOKEN_ID, DECIMALS, LEND_TOKEN_ID, LIQUID_STAKING_TOKEN_ID,
-    NR_STAKING_POSITIONS, STABLECOIN_TOKEN_ID,
+    NR_STAKING_POSITIONS, SHARE_TOKEN_ID, STABLECOIN_TOKEN_ID,
+};
+use elrond_wasm::{
+    elrond_codec::{multi_types::OptionalValue, top_encode_to_vec_u8_or_panic},
+    types::Address,
 };
-use elrond_wasm::{elrond_codec::multi_types::OptionalValue, types::Address};
 use elrond_wasm_debug::tx_mock::TxInputESDT;
 use elrond_wasm_debug::{
-    managed_biguint, managed_token_id, rust_biguint, tx_mock::TxResult, DebugApi,
+    managed_address, managed_biguint, managed_token_id, rust_biguint, tx_mock::TxResult, DebugApi,
 };
+use savings_account::auction::AuctionModule;
 use savings_account::common_storage::CommonStorageModule;
-use savings_account::model::{BorrowMetadata, LendMetadata};
+use savings_account::config::ConfigModule;
+use savings_account::interest::InterestModule;
+use savings_account::liquidation::LiquidationModule;
+use savings_account::model::{BorrowMetadata, LendMetadata, TimeBasis};
+use savings_account::position_management::PositionManagementModule;
+use savings_account::price_aggregator_proxy::PriceAggregatorModule;
 use savings_account::staking_positions_mapper::StakingPosition;
 use savings_account::staking_rewards::StakingRewardsModule;
+use savings_account::tokens::TokensModule;
+use savings_account::treasury::TreasuryModule;
+use savings_account::views::ViewsModule;
 use savings_account::SavingsAccount;
 
-impl<SavingsAccountObjBuilder> SavingsAccountSetup<SavingsAccountObjBuilder>
-where
-    SavingsAccountObjBuilder: 'static + Copy + Fn() -> savings_account::ContractObj<DebugApi>,
-{
-    pub fn call_lend(
+/// Checks the indexed fields of the event logged by the transaction,
+/// which follow the event identifier in the log topics
+pub fn check_event_topics(tx_result: &TxResult, identifier: &[u8], expected_topics: &[Vec<u8>]) {
+    let log = tx_result
+        .result_logs
+        .iter()
+        .find(|log| log.topics.first().map(Vec::as_slice) == Some(identifier))
+        .unwrap_or_else(|| panic!("No {} event", String::from_utf8_lossy(identifier)));
+    assert_eq!(&log.topics[1..], expected_topics);
+}
+
+impl<SavingsAccountObjBuilder> SavingsAccountSetup<SavingsAccountObjBuilder>
+where
+    SavingsAccountObjBuilder: 'static + Copy + Fn() -> savings_account::ContractObj<DebugApi>,
+{
+    pub fn call_lend(
+        &mut self,
+        lender: &Address,
+        amount: u64,
+        expected_lend_nonce: u64,
+    ) -> TxResult {
+        self.call_lend_with_lock(lender, amount, None, expected_lend_nonce)
+    }
+
+    pub fn call_lend_with_lock(
+        &mut self,
+        lender: &Address,
+        amount: u64,
+        opt_lock_epochs: Option<u64>,
+        expected_lend_nonce: u64,
+    ) -> TxResult {
+        self.call_lend_with_options(lender, amount, opt_lock_epochs, false, expected_lend_nonce)
+    }
+
+    pub fn call_lend_auto_compound(
+        &mut self,
+        lender: &Address,
+        amount: u64,
+        expected_lend_nonce: u64,
+    ) -> TxResult {
+        self.call_lend_with_options(lender, amount, None, true, expected_lend_nonce)
+    }
+
+    pub fn call_lend_with_options(
+        &mut self,
+        lender: &Address,
+        amount: u64,
+        opt_lock_epochs: Option<u64>,
+        auto_compound: bool,
+        expected_lend_nonce: u64,
+    ) -> TxResult {
+        self.b_mock.execute_esdt_transfer(
+            lender,
+            &self.sa_wrapper,
+            STABLECOIN_TOKEN_ID,
+            0,
+            &rust_biguint!(amount),
+            |sc| {
+                let opt_lock_epochs = match opt_lock_epochs {
+                    Some(lock_epochs) => OptionalValue::Some(lock_epochs),
+                    None => OptionalValue::None,
+                };
+                let lend_tokens = sc.lend(opt_lock_epochs, OptionalValue::Some(auto_compound));
+                assert_eq!(
+                    lend_tokens.token_identifier,
+                    managed_token_id!(LEND_TOKEN_ID)
+                );
+                assert_eq!(lend_tokens.token_nonce, expected_lend_nonce);
+                assert_eq!(lend_tokens.amount, managed_biguint!(amount));
+            },
+        )
+    }
+
+    pub fn call_lend_for_shares(
+        &mut self,
+        lender: &Address,
+        amount: u64,
+        expected_shares: u64,
+    ) -> TxResult {
+        self.b_mock.execute_esdt_transfer(
+            lender,
+            &self.sa_wrapper,
+            STABLECOIN_TOKEN_ID,
+            0,
+            &rust_biguint!(amount),
+            |sc| {
+                let shares = sc.lend_for_shares();
+                assert_eq!(shares.token_identifier, managed_token_id!(SHARE_TOKEN_ID));
+                assert_eq!(shares.amount, managed_biguint!(expected_shares));
+            },
+        )
+    }
+
+    pub fn call_withdraw_shares(
+        &mut self,
+        lender: &Address,
+        shares: u64,
+        expected_withdraw_amount: u64,
+    ) -> TxResult {
+        self.b_mock.execute_esdt_transfer(
+            lender,
+            &self.sa_wrapper,
+            SHARE_TOKEN_ID,
+            0,
+            &rust_biguint!(shares),
+            |sc| {
+                let stablecoin_out = sc.withdraw_shares();
+                assert_eq!(
+                    stablecoin_out.amount,
+                    managed_biguint!(expected_withdraw_amount)
+                );
+            },
+        )
+    }
+
+    pub fn call_lender_claim_rewards(
+        &mut self,
+        lender: &Address,
+        lend_token_nonce: u64,
+        lend_token_amount: u64,
+        expected_new_lend_nonce: u64,
+        expected_rewards_amount: u64,
+        reject_penalty: bool,
+    ) -> TxResult {
+        self.b_mock.execute_esdt_transfer(
+            lender,
+            &self.sa_wrapper,
+            LEND_TOKEN_ID,
+            lend_token_nonce,
+            &rust_biguint!(lend_token_amount),
+            |sc| {
+                let (new_lend_tokens, rewards) = sc
+                    .lender_claim_rewards(OptionalValue::Some(reject_penalty))
+                    .into_tuple();
+
+                assert_eq!(
+                    new_lend_tokens.token_identifier,
+                    managed_token_id!(LEND_TOKEN_ID)
+                );
+                assert_eq!(new_lend_tokens.token_nonce, expected_new_lend_nonce);
+                assert_eq!(new_lend_tokens.amount, managed_biguint!(lend_token_amount));
+
+                assert_eq!(
+                    rewards.token_identifier,
+                    managed_token_id!(STABLECOIN_TOKEN_ID)
+                );
+                assert_eq!(rewards.token_nonce, 0);
+                assert_eq!(rewards.amount, managed_biguint!(expected_rewards_amount));
+            },
+        )
+    }
+
+    pub fn call_compound_rewards(
+        &mut self,
+        lender: &Address,
+        lend_token_nonce: u64,
+        lend_token_amount: u64,
+        expected_new_lend_nonce: u64,
+        expected_new_lend_amount: u64,
+    ) -> TxResult {
+        self.b_mock.execute_esdt_transfer(
+            lender,
+            &self.sa_wrapper,
+            LEND_TOKEN_ID,
+            lend_token_nonce,
+            &rust_biguint!(lend_token_amount),
+            |sc| {
+                let new_lend_tokens = sc.compound_rewards(OptionalValue::Some(true));
+
+                assert_eq!(new_lend_tokens.token_nonce, expected_new_lend_nonce);
+                assert_eq!(
+                    new_lend_tokens.amount,
+                    managed_biguint!(expected_new_lend_amount)
+                );
+            },
+        )
+    }
+
+    pub fn call_lender_claim_compounded_rewards(
+        &mut self,
+        lender: &Address,
+        lend_token_nonce: u64,
+        lend_token_amount: u64,
+        expected_new_lend_nonce: u64,
+        expected_new_lend_amount: u64,
+    ) -> TxResult {
+        self.b_mock.execute_esdt_transfer(
+            lender,
+            &self.sa_wrapper,
+            LEND_TOKEN_ID,
+            lend_token_nonce,
+            &rust_biguint!(lend_token_amount),
+            |sc| {
+                let (new_lend_tokens, rewards) = sc
+                    .lender_claim_rewards(OptionalValue::Some(true))
+                    .into_tuple();
+
+                assert_eq!(new_lend_tokens.token_nonce, expected_new_lend_nonce);
+                assert_eq!(
+                    new_lend_tokens.amount,
+                    managed_biguint!(expected_new_lend_amount)
+                );
+                assert_eq!(rewards.amount, managed_biguint!(0));
+            },
+        )
+    }
+
+    pub fn call_withdraw(
+        &mut self,
+        lender: &Address,
+        lend_token_nonce: u64,
+        lend_token_amount: u64,
+        expected_withdraw_amount: u64,
+    ) -> TxResult {
+        self.b_mock.execute_esdt_transfer(
+            lender,
+            &self.sa_wrapper,
+            LEND_TOKEN_ID,
+            lend_token_nonce,
+            &rust_biguint!(lend_token_amount),
+            |sc| {
+                let stablecoin_out = sc.withdraw(OptionalValue::None);
+                assert_eq!(
+                    stablecoin_out.amount,
+                    managed_biguint!(expected_withdraw_amount)
+                );
+            },
+        )
+    }
+
+    pub fn call_queue_withdraw(
+        &mut self,
+        lender: &Address,
+        lend_token_nonce: u64,
+        lend_token_amount: u64,
+        expected_ticket_id: u64,
+    ) -> TxResult {
+        self.b_mock.execute_esdt_transfer(
+            lender,
+            &self.sa_wrapper,
+            LEND_TOKEN_ID,
+            lend_token_nonce,
+            &rust_biguint!(lend_token_amount),
+            |sc| {
+                let ticket_id = sc.queue_withdraw();
+                assert_eq!(ticket_id, expected_ticket_id);
+            },
+        )
+    }
+
+    pub fn call_cancel_queued_withdraw(
+        &mut self,
+        lender: &Address,
+        ticket_id: u64,
+        expected_lend_token_amount: u64,
+    ) -> TxResult {
+        self.b_mock
+            .execute_tx(lender, &self.sa_wrapper, &rust_biguint!(0), |sc| {
+                let lend_tokens = sc.cancel_queued_withdraw(ticket_id);
+                assert_eq!(
+                    lend_tokens.amount,
+                    managed_biguint!(expected_lend_token_amount)
+                );
+            })
+    }
+
+    pub fn call_process_withdraw_queue(&mut self, caller: &Address) -> TxResult {
+        self.b_mock
+            .execute_tx(caller, &self.sa_wrapper, &rust_biguint!(0), |sc| {
+                let _ = sc.process_withdraw_queue();
+            })
+    }
+
+    pub fn call_claim_withdraw_queue_payout(
+        &mut self,
+        lender: &Address,
+        expected_amount: u64,
+    ) -> TxResult {
+        self.b_mock
+            .execute_tx(lender, &self.sa_wrapper, &rust_biguint!(0), |sc| {
+                let payment = sc.claim_withdraw_queue_payout();
+                assert_eq!(payment.amount, managed_biguint!(expected_amount));
+            })
+    }
+
+    pub fn call_process_auto_compound(&mut self, caller: &Address) -> TxResult {
+        self.b_mock
+            .execute_tx(caller, &self.sa_wrapper, &rust_biguint!(0), |sc| {
+                let _ = sc.process_auto_compound();
+            })
+    }
+
+    pub fn call_borrow(
+        &mut self,
+        borrower: &Address,
+        liq_staking_nonce: u64,
+        liq_staking_amount: &num_bigint::BigUint,
+        expected_borrow_nonce: u64,
+        expected_stablecoin_amount: u64,
+    ) -> TxResult {
+        self.call_borrow_amount(
+            borrower,
+            liq_staking_nonce,
+            liq_staking_amount,
+            None,
+            expected_borrow_nonce,
+            expected_stablecoin_amount,
+        )
+    }
+
+    pub fn call_borrow_amount(
+        &mut self,
+        borrower: &Address,
+        liq_staking_nonce: u64,
+        liq_staking_amount: &num_bigint::BigUint,
+        opt_borrow_amount: Option<u64>,
+        expected_borrow_nonce: u64,
+        expected_stablecoin_amount: u64,
+    ) -> TxResult {
+        self.b_mock.execute_esdt_transfer(
+            borrower,
+            &self.sa_wrapper,
+            LIQUID_STAKING_TOKEN_ID,
+            liq_staking_nonce,
+            liq_staking_amount,
+            |sc| {
+                let opt_borrow_amount = match opt_borrow_amount {
+                    Some(amount) => OptionalValue::Some(managed_biguint!(amount)),
+                    None => OptionalValue::None,
+                };
+                let (borrow_tokens, stablecoins) = sc.borrow(opt_borrow_amount).into_tuple();
+
+                assert_eq!(
+                    borrow_tokens.token_identifier,
+                    managed_token_id!(BORROW_TOKEN_ID)
+                );
+                assert_eq!(borrow_tokens.token_nonce, expected_borrow_nonce);
+                assert_eq!(
+                    borrow_tokens.amount,
+                    elrond_wasm::types::BigUint::from_bytes_be(&liq_staking_amount.to_bytes_be())
+                );
+
+                assert_eq!(
+                    stablecoins.token_identifier,
+                    managed_token_id!(STABLECOIN_TOKEN_ID)
+                );
+                assert_eq!(stablecoins.token_nonce, 0);
+                assert_eq!(
+                    stablecoins.amount,
+                    managed_biguint!(expected_stablecoin_amount)
+                );
+            },
+        )
+    }
+
+    pub fn call_add_collateral(
+        &mut self,
+        borrower: &Address,
+        borrow_token_nonce: u64,
+        borrow_token_amount: &num_bigint::BigUint,
+        liq_staking_nonce: u64,
+        liq_staking_amount: &num_bigint::BigUint,
+        expected_borrow_nonce: u64,
+    ) -> TxResult {
+        let transfers = vec![
+            TxInputESDT {
+                token_identifier: BORROW_TOKEN_ID.to_vec(),
+                nonce: borrow_token_nonce,
+                value: borrow_token_amount.clone(),
+            },
+            TxInputESDT {
+                token_identifier: LIQUID_STAKING_TOKEN_ID.to_vec(),
+                nonce: liq_staking_nonce,
+                value: liq_staking_amount.clone(),
+            },
+        ];
+        self.b_mock
+            .execute_esdt_multi_transfer(borrower, &self.sa_wrapper, &transfers, |sc| {
+                let borrow_tokens = sc.add_collateral();
+
+                assert_eq!(
+                    borrow_tokens.token_identifier,
+                    managed_token_id!(BORROW_TOKEN_ID)
+                );
+                assert_eq!(borrow_tokens.token_nonce, expected_borrow_nonce);
+                assert_eq!(
+                    borrow_tokens.amount,
+                    elrond_wasm::types::BigUint::from_bytes_be(
+                        &(borrow_token_amount + liq_staking_amount).to_bytes_be()
+                    )
+                );
+            })
+    }
+
+    pub fn call_borrow_more(
+        &mut self,
+        borrower: &Address,
+        borrow_token_nonce: u64,
+        borrow_token_amount: &num_bigint::BigUint,
+        opt_borrow_amount: Option<u64>,
+        expected_borrow_nonce: u64,
+        expected_stablecoin_amount: u64,
+    ) -> TxResult {
+        self.b_mock.execute_esdt_transfer(
+            borrower,
+            &self.sa_wrapper,
+            BORROW_TOKEN_ID,
+            borrow_token_nonce,
+            borrow_token_amount,
+            |sc| {
+                let opt_borrow_amount = match opt_borrow_amount {
+                    Some(amount) => OptionalValue::Some(managed_biguint!(amount)),
+                    None => OptionalValue::None,
+                };
+                let (borrow_tokens, stablecoins) = sc.borrow_more(opt_borrow_amount).into_tuple();
+
+                assert_eq!(borrow_tokens.token_nonce, expected_borrow_nonce);
+                assert_eq!(
+                    stablecoins.amount,
+                    managed_biguint!(expected_stablecoin_amount)
+                );
+            },
+        )
+    }
+
+    pub fn call_withdraw_collateral(
+        &mut self,
+        borrower: &Address,
+        borrow_token_nonce: u64,
+        borrow_token_amount: &num_bigint::BigUint,
+        liq_staking_amount: &num_bigint::BigUint,
+        expected_borrow_nonce: u64,
+    ) -> TxResult {
+        self.b_mock.execute_esdt_transfer(
+            borrower,
+            &self.sa_wrapper,
+            BORROW_TOKEN_ID,
+            borrow_token_nonce,
+            borrow_token_amount,
+            |sc| {
+                let (borrow_tokens, liq_staking_tokens) = sc
+                    .withdraw_collateral(elrond_wasm::types::BigUint::from_bytes_be(
+                        &liq_staking_amount.to_bytes_be(),
+                    ))
+                    .into_tuple();
+
+                assert_eq!(borrow_tokens.token_nonce, expected_borrow_nonce);
+                assert_eq!(
+                    borrow_tokens.amount,
+                    elrond_wasm::types::BigUint::from_bytes_be(
+                        &(borrow_token_amount - liq_staking_amount).to_bytes_be()
+                    )
+                );
+                // the collateral may come from several liquid staking nonces
+                let mut total_liq_staking_amount = managed_biguint!(0);
+                for liq_staking_token in liq_staking_tokens.iter() {
+                    assert_eq!(
+                        liq_staking_token.token_identifier,
+                        managed_token_id!(LIQUID_STAKING_TOKEN_ID)
+                    );
+                    total_liq_staking_amount += liq_staking_token.amount;
+                }
+                assert_eq!(
+                    total_liq_staking_amount,
+                    elrond_wasm::types::BigUint::from_bytes_be(&liq_staking_amount.to_bytes_be())
+                );
+            },
+        )
+    }
+
+    pub fn call_repay(
+        &mut self,
+        borrower: &Address,
+        borrow_token_nonce: u64,
+        borrow_token_amount: &num_bigint::BigUint,
+        stablecoin_amount: u64,
+        expected_liq_staking_token_nonce: u64,
+        expected_leftover_stablecoins: u64,
+    ) -> TxResult {
+        self.call_repay_multiple(
+            borrower,
+            &[(borrow_token_nonce, borrow_token_amount.clone())],
+            stablecoin_amount,
+            &[expected_liq_staking_token_nonce],
+            expected_leftover_stablecoins,
+        )
+    }
+
+    pub fn call_repay_multiple(
+        &mut self,
+        borrower: &Address,
+        borrow_tokens: &[(u64, num_bigint::BigUint)],
+        stablecoin_amount: u64,
+        expected_liq_staking_token_nonces: &[u64],
+        expected_leftover_stablecoins: u64,
+    ) -> TxResult {
+        let mut transfers = Vec::new();
+        for (borrow_token_nonce, borrow_token_amount) in borrow_tokens {
+            transfers.push(TxInputESDT {
+                token_identifier: BORROW_TOKEN_ID.to_vec(),
+                nonce: *borrow_token_nonce,
+                value: borrow_token_amount.clone(),
+            });
+        }
+        transfers.push(TxInputESDT {
+            token_identifier: STABLECOIN_TOKEN_ID.to_vec(),
+            nonce: 0,
+            value: rust_biguint!(stablecoin_amount),
+        });
+
+        self.b_mock
+            .execute_esdt_multi_transfer(borrower, &self.sa_wrapper, &transfers, |sc| {
+                let (liq_staking_tokens, leftover_stablecoins) = sc.repay().into_tuple();
+
+                assert_eq!(liq_staking_tokens.len(), borrow_tokens.len());
+                for (i, (_, borrow_token_amount)) in borrow_tokens.iter().enumerate() {
+                    let liq_staking_token = liq_staking_tokens.get(i);
+                    assert_eq!(
+                        liq_staking_token.token_identifier,
+                        managed_token_id!(LIQUID_STAKING_TOKEN_ID)
+                    );
+                    assert_eq!(
+                        liq_staking_token.token_nonce,
+                        expected_liq_staking_token_nonces[i]
+                    );
+                    assert_eq!(
+                        liq_staking_token.amount,
+                        elrond_wasm::types::BigUint::from_bytes_be(
+                            &borrow_token_amount.to_bytes_be()
+                        )
+                    );
+                }
+
+                assert_eq!(
+                    leftover_stablecoins.token_identifier,
+                    managed_token_id!(STABLECOIN_TOKEN_ID)
+                );
+                assert_eq!(leftover_stablecoins.token_nonce, 0);
+                assert_eq!(
+                    leftover_stablecoins.amount,
+                    managed_biguint!(expected_leftover_stablecoins)
+                );
+            })
+    }
+
+    pub fn call_repay_interest(
+        &mut self,
+        borrower: &Address,
+        borrow_token_nonce: u64,
+        borrow_token_amount: &num_bigint::BigUint,
+        stablecoin_amount: u64,
+        expected_borrow_nonce: u64,
+        expected_leftover_stablecoins: u64,
+    ) -> TxResult {
+        let transfers = [
+            TxInputESDT {
+                token_identifier: BORROW_TOKEN_ID.to_vec(),
+                nonce: borrow_token_nonce,
+                value: borrow_token_amount.clone(),
+            },
+            TxInputESDT {
+                token_identifier: STABLECOIN_TOKEN_ID.to_vec(),
+                nonce: 0,
+                value: rust_biguint!(stablecoin_amount),
+            },
+        ];
+
+        self.b_mock
+            .execute_esdt_multi_transfer(borrower, &self.sa_wrapper, &transfers, |sc| {
+                let (borrow_tokens, leftover_stablecoins) = sc.repay_interest().into_tuple();
+
+                assert_eq!(borrow_tokens.token_nonce, expected_borrow_nonce);
+                assert_eq!(
+                    borrow_tokens.amount,
+                    elrond_wasm::types::BigUint::from_bytes_be(&borrow_token_amount.to_bytes_be())
+                );
+                assert_eq!(
+                    leftover_stablecoins.token_identifier,
+                    managed_token_id!(STABLECOIN_TOKEN_ID)
+                );
+                assert_eq!(
+                    leftover_stablecoins.amount,
+                    managed_biguint!(expected_leftover_stablecoins)
+                );
+            })
+    }
+
+    pub fn call_merge_lend_tokens(
+        &mut self,
+        lender: &Address,
+        lend_tokens: &[(u64, u64)],
+        expected_new_lend_nonce: u64,
+        expected_rewards_amount: u64,
+    ) -> TxResult {
+        let mut transfers = Vec::new();
+        let mut total_amount = 0;
+        for (nonce, amount) in lend_tokens {
+            transfers.push(TxInputESDT {
+                token_identifier: LEND_TOKEN_ID.to_vec(),
+                nonce: *nonce,
+                value: rust_biguint!(*amount),
+            });
+            total_amount += amount;
+        }
+
+        self.b_mock
+            .execute_esdt_multi_transfer(lender, &self.sa_wrapper, &transfers, |sc| {
+                let (new_lend_tokens, rewards) =
+                    sc.merge_lend_tokens(OptionalValue::Some(true)).into_tuple();
+
+                assert_eq!(new_lend_tokens.token_nonce, expected_new_lend_nonce);
+                assert_eq!(new_lend_tokens.amount, managed_biguint!(total_amount));
+
+                assert_eq!(
+                    rewards.token_identifier,
+                    managed_token_id!(STABLECOIN_TOKEN_ID)
+                );
+                assert_eq!(rewards.amount, managed_biguint!(expected_rewards_amount));
+            })
+    }
+
+    pub fn call_merge_borrow_tokens(
+        &mut self,
+        borrower: &Address,
+        borrow_tokens: &[(u64, num_bigint::BigUint)],
+        expected_borrow_nonce: u64,
+    ) -> TxResult {
+        let mut transfers = Vec::new();
+        let mut total_amount = rust_biguint!(0);
+        for (nonce, amount) in borrow_tokens {
+            transfers.push(TxInputESDT {
+                token_identifier: BORROW_TOKEN_ID.to_vec(),
+                nonce: *nonce,
+                value: amount.clone(),
+            });
+            total_amount += amount;
+        }
+
+        self.b_mock
+            .execute_esdt_multi_transfer(borrower, &self.sa_wrapper, &transfers, |sc| {
+                let new_borrow_tokens = sc.merge_borrow_tokens();
+
+                assert_eq!(new_borrow_tokens.token_nonce, expected_borrow_nonce);
+                assert_eq!(
+                    new_borrow_tokens.amount,
+                    elrond_wasm::types::BigUint::from_bytes_be(&total_amount.to_bytes_be())
+                );
+            })
+    }
+
+    /// Borrows the way the SC did before positions were tracked,
+    /// minting Borrow SFTs with the old attributes
+    pub fn legacy_borrow(
+        &mut self,
+        borrower: &Address,
+        liq_staking_nonce: u64,
+        liq_staking_amount: &num_bigint::BigUint,
+        borrow_amount: u64,
+    ) -> TxResult {
+        self.b_mock.execute_esdt_transfer(
+            borrower,
+            &self.sa_wrapper,
+            LIQUID_STAKING_TOKEN_ID,
+            liq_staking_nonce,
+            liq_staking_amount,
+            |sc| {
+                let staking_position_id = sc
+                    .staking_positions()
+                    .add_staking_position(liq_staking_nonce);
+                let borrow_token_attributes = BorrowMetadata {
+                    staking_position_id,
+                    borrow_epoch: sc.blockchain().get_block_epoch(),
+                    staked_token_value_in_dollars_at_borrow: sc.get_staked_token_value_in_dollars(),
+                    borrowed_amount: managed_biguint!(0),
+                    borrow_index: managed_biguint!(0),
+                };
+                let _ = sc.borrow_token().nft_create_and_send(
+                    &managed_address!(borrower),
+                    elrond_wasm::types::BigUint::from_bytes_be(&liq_staking_amount.to_bytes_be()),
+                    &borrow_token_attributes,
+                );
+
+                sc.borrowed_amount()
+                    .update(|borrowed_amount| *borrowed_amount += managed_biguint!(borrow_amount));
+                let _ = sc
+                    .send_stablecoins(&managed_address!(borrower), managed_biguint!(borrow_amount));
+            },
+        )
+    }
+
+    pub fn call_migrate_borrow_position(
+        &mut self,
+        caller: &Address,
+        borrow_token_nonce: u64,
+        borrow_token_amount: &num_bigint::BigUint,
+    ) -> TxResult {
+        self.b_mock
+            .execute_tx(caller, &self.sa_wrapper, &rust_biguint!(0), |sc| {
+                sc.migrate_borrow_position(
+                    borrow_token_nonce,
+                    elrond_wasm::types::BigUint::from_bytes_be(&borrow_token_amount.to_bytes_be()),
+                );
+            })
+    }
+
+    pub fn call_split_borrow_tokens(
+        &mut self,
+        borrower: &Address,
+        borrow_token_nonce: u64,
+        borrow_token_amount: &num_bigint::BigUint,
+        expected_borrow_nonce: u64,
+        expected_borrow_token_amount: &num_bigint::BigUint,
+    ) -> TxResult {
+        self.b_mock.execute_esdt_transfer(
+            borrower,
+            &self.sa_wrapper,
+            BORROW_TOKEN_ID,
+            borrow_token_nonce,
+            borrow_token_amount,
+            |sc| {
+                let new_borrow_tokens = sc.split_borrow_tokens();
+
+                assert_eq!(new_borrow_tokens.token_nonce, expected_borrow_nonce);
+                assert_eq!(
+                    new_borrow_tokens.amount,
+                    elrond_wasm::types::BigUint::from_bytes_be(
+                        &expected_borrow_token_amount.to_bytes_be()
+                    )
+                );
+            },
+        )
+    }
+
+    pub fn call_repay_debt_for(
+        &mut self,
+        payer: &Address,
+        borrow_token_nonce: u64,
+        stablecoin_amount: u64,
+        expected_leftover_stablecoins: u64,
+    ) -> TxResult {
+        self.b_mock.execute_esdt_transfer(
+            payer,
+            &self.sa_wrapper,
+            STABLECOIN_TOKEN_ID,
+            0,
+            &rust_biguint!(stablecoin_amount),
+            |sc| {
+                let leftover_stablecoins = sc.repay_debt_for(borrow_token_nonce);
+                assert_eq!(
+                    leftover_stablecoins.token_identifier,
+                    managed_token_id!(STABLECOIN_TOKEN_ID)
+                );
+                assert_eq!(
+                    leftover_stablecoins.amount,
+                    managed_biguint!(expected_leftover_stablecoins)
+                );
+            },
+        )
+    }
+
+    pub fn call_liquidate(
+        &mut self,
+        liquidator: &Address,
+        borrow_token_nonce: u64,
+        stablecoin_amount: u64,
+        expected_liq_staking_token_nonce: u64,
+        expected_liq_staking_token_amount: &num_bigint::BigUint,
+        expected_leftover_stablecoins: u64,
+    ) -> TxResult {
+        self.b_mock.execute_esdt_transfer(
+            liquidator,
+            &self.sa_wrapper,
+            STABLECOIN_TOKEN_ID,
+            0,
+            &rust_biguint!(stablecoin_amount),
+            |sc| {
+                let (liq_staking_tokens, leftover_stablecoins) =
+                    sc.liquidate(borrow_token_nonce).into_tuple();
+
+                assert_eq!(liq_staking_tokens.len(), 1);
+                let liq_staking_tokens = liq_staking_tokens.get(0);
+                assert_eq!(
+                    liq_staking_tokens.token_identifier,
+                    managed_token_id!(LIQUID_STAKING_TOKEN_ID)
+                );
+                assert_eq!(
+                    liq_staking_tokens.token_nonce,
+                    expected_liq_staking_token_nonce
+                );
+                assert_eq!(
+                    liq_staking_tokens.amount,
+                    elrond_wasm::types::BigUint::from_bytes_be(
+                        &expected_liq_staking_token_amount.to_bytes_be()
+                    )
+                );
+
+                assert_eq!(
+                    leftover_stablecoins.token_identifier,
+                    managed_token_id!(STABLECOIN_TOKEN_ID)
+                );
+                assert_eq!(
+                    leftover_stablecoins.amount,
+                    managed_biguint!(expected_leftover_stablecoins)
+                );
+            },
+        )
+    }
+
+    pub fn call_claim_liquidation_surplus(
         &mut self,
-        lender: &Address,
-        amount: u64,
-        expected_lend_nonce: u64,
+        holder: &Address,
+        borrow_token_nonce: u64,
+        borrow_token_amount: &num_bigint::BigUint,
+        expected_liq_staking_token_amount: &num_bigint::BigUint,
     ) -> TxResult {
         self.b_mock.execute_esdt_transfer(
-            lender,
+            holder,
             &self.sa_wrapper,
-            STABLECOIN_TOKEN_ID,
-            0,
-            &rust_biguint!(amount),
+            BORROW_TOKEN_ID,
+            borrow_token_nonce,
+            borrow_token_amount,
             |sc| {
-                let lend_tokens = sc.lend();
+                let liq_staking_tokens = sc.claim_liquidation_surplus();
+                assert_eq!(liq_staking_tokens.len(), 1);
+                let liq_staking_tokens = liq_staking_tokens.get(0);
                 assert_eq!(
-                    lend_tokens.token_identifier,
-                    managed_token_id!(LEND_TOKEN_ID)
+                    liq_staking_tokens.token_identifier,
+                    managed_token_id!(LIQUID_STAKING_TOKEN_ID)
+                );
+                assert_eq!(
+                    liq_staking_tokens.amount,
+                    elrond_wasm::types::BigUint::from_bytes_be(
+                        &expected_liq_staking_token_amount.to_bytes_be()
+                    )
                 );
-                assert_eq!(lend_tokens.token_nonce, expected_lend_nonce);
-                assert_eq!(lend_tokens.amount, managed_biguint!(amount));
             },
         )
     }
 
-    pub fn call_lender_claim_rewards(
+    pub fn call_queue_auction_params(
         &mut self,
-        lender: &Address,
-        lend_token_nonce: u64,
-        lend_token_amount: u64,
-        expected_new_lend_nonce: u64,
-        expected_rewards_amount: u64,
-        reject_penalty: bool,
+        start_price_premium: u64,
+        price_decrease_per_round: u64,
+        min_price_percentage: u64,
+        max_rounds: u64,
+        expected_change_id: u64,
     ) -> TxResult {
-        self.b_mock.execute_esdt_transfer(
-            lender,
+        self.b_mock.execute_tx(
+            &self.owner_address,
             &self.sa_wrapper,
-            LEND_TOKEN_ID,
-            lend_token_nonce,
-            &rust_biguint!(lend_token_amount),
+            &rust_biguint!(0),
             |sc| {
-                let (new_lend_tokens, rewards) = sc
-                    .lender_claim_rewards(OptionalValue::Some(reject_penalty))
-                    .into_tuple();
-
-                assert_eq!(
-                    new_lend_tokens.token_identifier,
-                    managed_token_id!(LEND_TOKEN_ID)
+                let change_id = sc.queue_auction_params(
+                    managed_biguint!(start_price_premium),
+                    managed_biguint!(price_decrease_per_round),
+                    managed_biguint!(min_price_percentage),
+                    max_rounds,
                 );
-                assert_eq!(new_lend_tokens.token_nonce, expected_new_lend_nonce);
-                assert_eq!(new_lend_tokens.amount, managed_biguint!(lend_token_amount));
+                assert_eq!(change_id, expected_change_id);
+            },
+        )
+    }
 
-                assert_eq!(
-                    rewards.token_identifier,
-                    managed_token_id!(STABLECOIN_TOKEN_ID)
-                );
-                assert_eq!(rewards.token_nonce, 0);
-                assert_eq!(rewards.amount, managed_biguint!(expected_rewards_amount));
+    pub fn call_queue_time_basis(
+        &mut self,
+        time_basis: TimeBasis,
+        expected_change_id: u64,
+    ) -> TxResult {
+        self.b_mock.execute_tx(
+            &self.owner_address,
+            &self.sa_wrapper,
+            &rust_biguint!(0),
+            |sc| {
+                let change_id = sc.queue_time_basis(time_basis);
+                assert_eq!(change_id, expected_change_id);
             },
         )
     }
 
-    pub fn call_withdraw(
+    pub fn call_queue_liquidation_params(
         &mut self,
-        lender: &Address,
-        lend_token_nonce: u64,
-        lend_token_amount: u64,
-        expected_withdraw_amount: u64,
+        liquidation_threshold: u64,
+        close_factor: u64,
+        liquidation_bonus: u64,
+        expected_change_id: u64,
     ) -> TxResult {
-        self.b_mock.execute_esdt_transfer(
-            lender,
+        self.b_mock.execute_tx(
+            &self.owner_address,
             &self.sa_wrapper,
-            LEND_TOKEN_ID,
-            lend_token_nonce,
-            &rust_biguint!(lend_token_amount),
+            &rust_biguint!(0),
             |sc| {
-                let stablecoin_out = sc.withdraw(OptionalValue::None);
-                assert_eq!(
-                    stablecoin_out.amount,
-                    managed_biguint!(expected_withdraw_amount)
+                let change_id = sc.queue_liquidation_params(
+                    managed_biguint!(liquidation_threshold),
+                    managed_biguint!(close_factor),
+                    managed_biguint!(liquidation_bonus),
                 );
+                assert_eq!(change_id, expected_change_id);
             },
         )
     }
 
-    pub fn call_borrow(
+    pub fn call_queue_param_change_delay(
         &mut self,
-        borrower: &Address,
-        liq_staking_nonce: u64,
-        liq_staking_amount: &num_bigint::BigUint,
-        expected_borrow_nonce: u64,
-        expected_stablecoin_amount: u64,
+        param_change_delay_epochs: u64,
+        expected_change_id: u64,
     ) -> TxResult {
-        self.b_mock.execute_esdt_transfer(
-            borrower,
+        self.b_mock.execute_tx(
+            &self.owner_address,
             &self.sa_wrapper,
-            LIQUID_STAKING_TOKEN_ID,
-            liq_staking_nonce,
-            liq_staking_amount,
+            &rust_biguint!(0),
             |sc| {
-                let (borrow_tokens, stablecoins) = sc.borrow().into_tuple();
+                let change_id = sc.queue_param_change_delay(param_change_delay_epochs);
+                assert_eq!(change_id, expected_change_id);
+            },
+        )
+    }
 
-                assert_eq!(
-                    borrow_tokens.token_identifier,
-                    managed_token_id!(BORROW_TOKEN_ID)
-                );
-                assert_eq!(borrow_tokens.token_nonce, expected_borrow_nonce);
-                assert_eq!(
-                    borrow_tokens.amount,
-                    elrond_wasm::types::BigUint::from_bytes_be(&liq_staking_amount.to_bytes_be())
+    pub fn call_queue_pool_params(
+        &mut self,
+        base_borrow_rate: u64,
+        borrow_rate_under_opt_factor: u64,
+        borrow_rate_over_opt_factor: u64,
+        optimal_utilisation: u64,
+        reserve_factor: u64,
+        expected_change_id: u64,
+    ) -> TxResult {
+        self.b_mock.execute_tx(
+            &self.owner_address,
+            &self.sa_wrapper,
+            &rust_biguint!(0),
+            |sc| {
+                let change_id = sc.queue_pool_params(
+                    managed_biguint!(base_borrow_rate),
+                    managed_biguint!(borrow_rate_under_opt_factor),
+                    managed_biguint!(borrow_rate_over_opt_factor),
+                    managed_biguint!(optimal_utilisation),
+                    managed_biguint!(reserve_factor),
                 );
+                assert_eq!(change_id, expected_change_id);
+            },
+        )
+    }
 
-                assert_eq!(
-                    stablecoins.token_identifier,
-                    managed_token_id!(STABLECOIN_TOKEN_ID)
-                );
-                assert_eq!(stablecoins.token_nonce, 0);
-                assert_eq!(
-                    stablecoins.amount,
-                    managed_biguint!(expected_stablecoin_amount)
+    pub fn call_queue_loan_to_value_percentage(
+        &mut self,
+        loan_to_value_percentage: u64,
+        expected_change_id: u64,
+    ) -> TxResult {
+        self.b_mock.execute_tx(
+            &self.owner_address,
+            &self.sa_wrapper,
+            &rust_biguint!(0),
+            |sc| {
+                let change_id =
+                    sc.queue_loan_to_value_percentage(managed_biguint!(loan_to_value_percentage));
+                assert_eq!(change_id, expected_change_id);
+            },
+        )
+    }
+
+    pub fn call_execute_param_change(&mut self, change_id: u64) -> TxResult {
+        self.b_mock.execute_tx(
+            &self.owner_address,
+            &self.sa_wrapper,
+            &rust_biguint!(0),
+            |sc| {
+                sc.execute_param_change(change_id);
+            },
+        )
+    }
+
+    pub fn call_cancel_param_change(&mut self, change_id: u64) -> TxResult {
+        self.b_mock.execute_tx(
+            &self.owner_address,
+            &self.sa_wrapper,
+            &rust_biguint!(0),
+            |sc| {
+                sc.cancel_param_change(change_id);
+            },
+        )
+    }
+
+    pub fn call_set_lock_period(&mut self, lock_epochs: u64, boost: u64) -> TxResult {
+        self.b_mock.execute_tx(
+            &self.owner_address,
+            &self.sa_wrapper,
+            &rust_biguint!(0),
+            |sc| {
+                sc.set_lock_period(lock_epochs, boost);
+            },
+        )
+    }
+
+    pub fn call_withdraw_protocol_revenue(
+        &mut self,
+        treasury_address: &Address,
+        amount: u64,
+    ) -> TxResult {
+        self.b_mock.execute_tx(
+            &self.owner_address,
+            &self.sa_wrapper,
+            &rust_biguint!(0),
+            |sc| {
+                let payment = sc.withdraw_protocol_revenue(
+                    managed_address!(treasury_address),
+                    OptionalValue::Some(managed_biguint!(amount)),
                 );
+                assert_eq!(payment.amount, managed_biguint!(amount));
             },
         )
     }
 
-    pub fn call_repay(
+    pub fn call_accrue_interest(&mut self, caller: &Address) -> TxResult {
+        self.b_mock
+            .execute_tx(caller, &self.sa_wrapper, &rust_biguint!(0), |sc| {
+                let _ = sc.accrue_interest();
+            })
+    }
+
+    pub fn call_start_auction(&mut self, caller: &Address, borrow_token_nonce: u64) -> TxResult {
+        self.b_mock
+            .execute_tx(caller, &self.sa_wrapper, &rust_biguint!(0), |sc| {
+                sc.start_auction(borrow_token_nonce);
+            })
+    }
+
+    pub fn call_cancel_auction(&mut self, caller: &Address, borrow_token_nonce: u64) -> TxResult {
+        self.b_mock
+            .execute_tx(caller, &self.sa_wrapper, &rust_biguint!(0), |sc| {
+                sc.cancel_auction(borrow_token_nonce);
+            })
+    }
+
+    pub fn call_bid(
         &mut self,
-        borrower: &Address,
+        bidder: &Address,
         borrow_token_nonce: u64,
-        borrow_token_amount: &num_bigint::BigUint,
         stablecoin_amount: u64,
-        expected_liq_staking_token_nonce: u64,
+        expected_liq_staking_token_amount: &num_bigint::BigUint,
         expected_leftover_stablecoins: u64,
     ) -> TxResult {
-        let transfers = vec![
-            TxInputESDT {
-                token_identifier: BORROW_TOKEN_ID.to_vec(),
-                nonce: borrow_token_nonce,
-                value: borrow_token_amount.clone(),
-            },
-            TxInputESDT {
-                token_identifier: STABLECOIN_TOKEN_ID.to_vec(),
-                nonce: 0,
-                value: rust_biguint!(stablecoin_amount),
-            },
-        ];
-        self.b_mock
-            .execute_esdt_multi_transfer(borrower, &self.sa_wrapper, &transfers, |sc| {
-                let (liq_staking_tokens, leftover_stablecoins) = sc.repay().into_tuple();
+        self.b_mock.execute_esdt_transfer(
+            bidder,
+            &self.sa_wrapper,
+            STABLECOIN_TOKEN_ID,
+            0,
+            &rust_biguint!(stablecoin_amount),
+            |sc| {
+                let (liq_staking_tokens, leftover_stablecoins) =
+                    sc.bid(borrow_token_nonce).into_tuple();
 
-                assert_eq!(
-                    liq_staking_tokens.token_identifier,
-                    managed_token_id!(LIQUID_STAKING_TOKEN_ID)
-                );
-                assert_eq!(
-                    liq_staking_tokens.token_nonce,
-                    expected_liq_staking_token_nonce
-                );
+                assert_eq!(liq_staking_tokens.len(), 1);
+                let liq_staking_tokens = liq_staking_tokens.get(0);
                 assert_eq!(
                     liq_staking_tokens.amount,
-                    elrond_wasm::types::BigUint::from_bytes_be(&borrow_token_amount.to_bytes_be())
-                );
-
-                assert_eq!(
-                    leftover_stablecoins.token_identifier,
-                    managed_token_id!(STABLECOIN_TOKEN_ID)
+                    elrond_wasm::types::BigUint::from_bytes_be(
+                        &expected_liq_staking_token_amount.to_bytes_be()
+                    )
                 );
-                assert_eq!(leftover_stablecoins.token_nonce, 0);
                 assert_eq!(
                     leftover_stablecoins.amount,
                     managed_biguint!(expected_leftover_stablecoins)
                 );
-            })
+            },
+        )
     }
 
     pub fn call_claim_staking_rewards(&mut self) -> TxResult {
@@ -213,6 +1114,32 @@ where
         )
     }
 
+    pub fn call_claim_rewards_iou(&mut self, lender: &Address, expected_amount: u64) -> TxResult {
+        self.b_mock
+            .execute_tx(lender, &self.sa_wrapper, &rust_biguint!(0), |sc| {
+                let payment = sc.claim_rewards_iou();
+                assert_eq!(payment.amount, managed_biguint!(expected_amount));
+            })
+    }
+
+    /// The borrow rate, deposit rate and utilisation logged by most events,
+    /// as they are after the last transaction
+    pub fn get_rates_topics(&mut self) -> Vec<Vec<u8>> {
+        let mut topics = Vec::new();
+        self.b_mock
+            .execute_query(&self.sa_wrapper, |sc| {
+                let market_state = sc.get_market_state();
+                topics.push(top_encode_to_vec_u8_or_panic(&market_state.borrow_rate));
+                topics.push(top_encode_to_vec_u8_or_panic(&market_state.deposit_rate));
+                topics.push(top_encode_to_vec_u8_or_panic(
+                    &market_state.capital_utilisation,
+                ));
+            })
+            .assert_ok();
+
+        topics
+    }
+
     pub fn call_get_penaly_amount(&mut self, lend_amount: u64) -> u64 {
         let mut penalty = 0;
         self.b_mock
@@ -225,18 +1152,48 @@ where
         penalty
     }
 
-    pub fn call_get_lender_claimable_rewards(&mut self, lend_epoch: u64, lend_amount: u64) -> u64 {
+    pub fn call_get_lender_claimable_rewards(
+        &mut self,
+        lend_token_nonce: u64,
+        lend_amount: u64,
+    ) -> u64 {
         let mut rewards = 0;
         self.b_mock
             .execute_query(&self.sa_wrapper, |sc| {
-                let rewards_biguint =
-                    sc.get_lender_claimable_rewards_view(lend_epoch, managed_biguint!(lend_amount));
+                let rewards_biguint = sc.get_lender_claimable_rewards_view(
+                    lend_token_nonce,
+                    managed_biguint!(lend_amount),
+                );
                 rewards = rewards_biguint.to_u64().unwrap();
             })
             .assert_ok();
 
         rewards
     }
+
+    pub fn call_get_auction_price(&mut self, borrow_token_nonce: u64) -> u64 {
+        let mut price = 0;
+        self.b_mock
+            .execute_query(&self.sa_wrapper, |sc| {
+                let price_biguint = sc.get_auction_price(borrow_token_nonce);
+                price = price_biguint.to_u64().unwrap();
+            })
+            .assert_ok();
+
+        price
+    }
+
+    pub fn call_get_health_factor(&mut self, borrow_token_nonce: u64) -> u64 {
+        let mut health_factor = 0;
+        self.b_mock
+            .execute_query(&self.sa_wrapper, |sc| {
+                let health_factor_biguint = sc.get_health_factor(borrow_token_nonce);
+                health_factor = health_factor_biguint.to_u64().unwrap();
+            })
+            .assert_ok();
+
+        health_factor
+    }
 }
 
 impl<SavingsAccountObjBuilder> SavingsAccountSetup<SavingsAccountObjBuilder>
@@ -256,7 +1213,13 @@ where
             LEND_TOKEN_ID,
             1,
             &rust_biguint!(100_000),
-            Some(&LendMetadata { lend_epoch: 20 }),
+            Some(&LendMetadata {
+                lend_epoch: 20,
+                lend_timestamp: 0,
+                lock_end_epoch: 0,
+                boost: 0,
+                auto_compound: false,
+            }),
         );
         self.b_mock
             .execute_query(&self.sa_wrapper, |sc| {
@@ -283,7 +1246,13 @@ where
             LEND_TOKEN_ID,
             2,
             &rust_biguint!(50_000),
-            Some(&LendMetadata { lend_epoch: 21 }),
+            Some(&LendMetadata {
+                lend_epoch: 21,
+                lend_timestamp: 0,
+                lock_end_epoch: 0,
+                boost: 0,
+                auto_compound: false,
+            }),
         );
         self.b_mock
             .execute_query(&self.sa_wrapper, |sc| {
@@ -319,6 +1288,8 @@ where
                     borrow_epoch: 25,
                     staked_token_value_in_dollars_at_borrow: managed_biguint!(100),
                     staking_position_id: i as u64,
+                    borrowed_amount: managed_biguint!(stablecoin_amount_per_borrow),
+                    borrow_index: managed_biguint!(1_000_000_000),
                 }),
             );
 
@@ -381,30 +1352,25 @@ where
 
         self.b_mock.set_block_epoch(50);
 
-        // 100,000 out of total 150,000 => ~66% of 12,250
-        assert_eq!(self.call_get_penaly_amount(100_000), 8_166);
+        // reserves cover all the rewards, so there is no penalty
+        assert_eq!(self.call_get_penaly_amount(100_000), 0);
+        assert_eq!(self.call_get_penaly_amount(50_000), 0);
 
-        // 50,000 out of total 150,000 => ~33% of 12,250
-        assert_eq!(self.call_get_penaly_amount(50_000), 4_083);
-
-        // (50 - 20) * 0.5% * 100,000 - 6,125 = 15,000 - 8,166 = 6,834
-        let first_lender_rewards = 6_834;
+        // no rewards before the first borrow, as utilisation was 0
+        // then ~13,84% deposit rate for 25 epochs, as interest increased the utilisation
+        // from the initial 50%, with a 56,66% borrow rate and 10% reserve factor
+        let first_lender_rewards = 948;
         assert_eq!(
-            self.call_get_lender_claimable_rewards(20, 100_000),
+            self.call_get_lender_claimable_rewards(1, 100_000),
             first_lender_rewards
         );
 
-        // (50 - 21) * 0.5% * 50,000 - 4,083 = 7,250 - 4,083 = 3,167
-        let second_lender_rewards = 3_167;
+        let second_lender_rewards = 474;
         assert_eq!(
-            self.call_get_lender_claimable_rewards(21, 50_000),
+            self.call_get_lender_claimable_rewards(2, 50_000),
             second_lender_rewards
         );
 
-        // lender 1 try claim without penalty
-        self.call_lender_claim_rewards(&first_lender, 1, 100_000, 3, first_lender_rewards, true)
-            .assert_user_error("Rewards have penalty");
-
         // lender 1 claim ok
         self.call_lender_claim_rewards(&first_lender, 1, 100_000, 3, first_lender_rewards, false)
             .assert_ok();
@@ -414,7 +1380,13 @@ where
             LEND_TOKEN_ID,
             3,
             &rust_biguint!(100_000),
-            Some(&LendMetadata { lend_epoch: 50 }),
+            Some(&LendMetadata {
+                lend_epoch: 50,
+                lend_timestamp: 0,
+                lock_end_epoch: 0,
+                boost: 0,
+                auto_compound: false,
+            }),
         );
         self.b_mock.check_esdt_balance(
             &first_lender,
@@ -433,7 +1405,13 @@ where
             LEND_TOKEN_ID,
             3,
             &rust_biguint!(50_000),
-            Some(&LendMetadata { lend_epoch: 50 }),
+            Some(&LendMetadata {
+                lend_epoch: 50,
+                lend_timestamp: 0,
+                lock_end_epoch: 0,
+                boost: 0,
+                auto_compound: false,
+            }),
         );
         self.b_mock.check_esdt_balance(
             &second_lender,
@@ -445,12 +1423,10 @@ where
         self.b_mock
             .execute_query(&self.sa_wrapper, |sc| {
                 assert_eq!(sc.last_rewards_update_epoch().get(), 50);
-                assert_eq!(sc.stablecoin_reserves().get(), managed_biguint!(0));
-                assert_eq!(
-                    sc.total_missed_rewards_by_claim_since_last_calculation()
-                        .get(),
-                    managed_biguint!(8_166 + 4_083)
-                );
+                // 10,000 from staking rewards - 10% reserve factor - 1,422 lender rewards
+                assert_eq!(sc.stablecoin_reserves().get(), managed_biguint!(7_578));
+                assert_eq!(sc.protocol_revenue().get(), managed_biguint!(1_000));
+                assert_eq!(sc.missing_rewards().get(), managed_biguint!(0));
             })
             .assert_ok();
     }
@@ -15,19 +15,25 @@ pub static LIQUID_STAKING_TOKEN_ID: &[u8] = b"LIQ-123456";
 pub static STAKED_TOKEN_ID: &[u8] = b"";
 pub static STAKED_TOKEN_TICKER: &[u8] = b"EGLD";
 pub const LOAN_TO_VALUE_PERCENTAGE: u64 = 750_000_000; // 75%
-pub const LENDER_REWARDS_PERCENTAGE_PER_EPOCH: u64 = 5_000_000; // 0.5%
 pub const BASE_BORROW_RATE: u64 = 500_000_000; // 50%
 pub const BORROW_RATE_UNDER_OPTIMAL_FACTOR: u64 = 100_000_000; // 10%
 pub const BORROW_RATE_OVER_OPTIMAL_FACTOR: u64 = 100_000_000; // 10%
 pub const OPTIMAL_UTILISATION: u64 = 750_000_000; // 75%
+pub const RESERVE_FACTOR: u64 = 100_000_000; // 10%
+pub const LIQUIDATION_THRESHOLD: u64 = 800_000_000; // 80%
+pub const CLOSE_FACTOR: u64 = 500_000_000; // 50%
+pub const LIQUIDATION_BONUS: u64 = 50_000_000; // 5%
+pub const PARAM_CHANGE_DELAY_EPOCHS: u64 = 7;
 
 pub static LEND_TOKEN_ID: &[u8] = b"LEND-123456";
 pub static BORROW_TOKEN_ID: &[u8] = b"BORROW-123456";
+pub static SHARE_TOKEN_ID: &[u8] = b"LENDSHARE-123456";
 pub static NFT_ROLES: &[EsdtLocalRole] = &[
     EsdtLocalRole::NftCreate,
     EsdtLocalRole::NftAddQuantity,
     EsdtLocalRole::NftBurn,
 ];
+pub static FUNGIBLE_ROLES: &[EsdtLocalRole] = &[EsdtLocalRole::Mint, EsdtLocalRole::Burn];
 
 pub const STAKE_PER_POSITION: u64 = 250;
 pub const DECIMALS: u64 = 1_000_000_000_000_000_000;
@@ -95,22 +101,29 @@ where
                     managed_address!(&dex_address),
                     managed_address!(&price_aggregator_address),
                     managed_biguint!(LOAN_TO_VALUE_PERCENTAGE),
-                    managed_biguint!(LENDER_REWARDS_PERCENTAGE_PER_EPOCH),
                     managed_biguint!(BASE_BORROW_RATE),
                     managed_biguint!(BORROW_RATE_UNDER_OPTIMAL_FACTOR),
                     managed_biguint!(BORROW_RATE_OVER_OPTIMAL_FACTOR),
                     managed_biguint!(OPTIMAL_UTILISATION),
+                    managed_biguint!(RESERVE_FACTOR),
+                    managed_biguint!(LIQUIDATION_THRESHOLD),
+                    managed_biguint!(CLOSE_FACTOR),
+                    managed_biguint!(LIQUIDATION_BONUS),
+                    PARAM_CHANGE_DELAY_EPOCHS,
                 );
 
                 sc.lend_token()
                     .set_token_id(&managed_token_id!(LEND_TOKEN_ID));
                 sc.borrow_token()
                     .set_token_id(&managed_token_id!(BORROW_TOKEN_ID));
+                sc.share_token()
+                    .set_token_id(&managed_token_id!(SHARE_TOKEN_ID));
             })
             .assert_ok();
 
         b_mock.set_esdt_local_roles(sa_wrapper.address_ref(), LEND_TOKEN_ID, NFT_ROLES);
         b_mock.set_esdt_local_roles(sa_wrapper.address_ref(), BORROW_TOKEN_ID, NFT_ROLES);
+        b_mock.set_esdt_local_roles(sa_wrapper.address_ref(), SHARE_TOKEN_ID, FUNGIBLE_ROLES);
 
         SavingsAccountSetup {
             b_mock,
@@ -1,11 +1,20 @@
 mod savings_account_interactions;
 mod savings_account_setup;
 
-use elrond_wasm_debug::{managed_biguint, rust_biguint, DebugApi};
+use elrond_wasm::elrond_codec::top_encode_to_vec_u8_or_panic;
+use elrond_wasm_debug::{managed_address, managed_biguint, rust_biguint, DebugApi};
 use savings_account::common_storage::CommonStorageModule;
-use savings_account::model::BorrowMetadata;
+use savings_account::config::ConfigModule;
+use savings_account::lend_shares::LendSharesModule;
+use savings_account::liquidation::LiquidationModule;
+use savings_account::model::{BorrowMetadata, LendMetadata, TimeBasis};
 use savings_account::staking_positions_mapper::StakingPosition;
 use savings_account::staking_rewards::StakingRewardsModule;
+use savings_account::treasury::TreasuryModule;
+use savings_account::views::ViewsModule;
+use savings_account::withdraw_queue::WithdrawQueueModule;
+use savings_account::SavingsAccount;
+use savings_account_interactions::check_event_topics;
 use savings_account_setup::*;
 
 #[test]
@@ -19,6 +28,34 @@ fn lend_test() {
     sa_setup.default_lenders();
 }
 
+#[test]
+fn lend_existing_nonce_test() {
+    let _ = DebugApi::dummy();
+    let mut sa_setup = SavingsAccountSetup::new(savings_account::contract_obj);
+    let owner = sa_setup.owner_address.clone();
+    let second_lender = sa_setup.second_lender_address.clone();
+
+    sa_setup.default_lenders();
+
+    // the rewards index moves while the nonce is still being lent into
+    sa_setup
+        .b_mock
+        .execute_tx(&owner, &sa_setup.sa_wrapper, &rust_biguint!(0), |sc| {
+            sc.lender_rewards_index()
+                .set(&managed_biguint!(1_000_000_000));
+        })
+        .assert_ok();
+    sa_setup.call_lend(&second_lender, 10_000, 2).assert_ok();
+
+    // the tokens already lent keep the index they started from
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            assert_eq!(sc.lend_token_rewards_index(2).get(), managed_biguint!(0));
+        })
+        .assert_ok();
+}
+
 #[test]
 fn borrow_test() {
     let _ = DebugApi::dummy();
@@ -126,6 +163,77 @@ fn claim_rewards_test() {
     sa_setup.default_claim_rewards();
 }
 
+#[test]
+fn late_borrow_rewards_test() {
+    let _ = DebugApi::dummy();
+    let mut sa_setup = SavingsAccountSetup::new(savings_account::contract_obj);
+    let borrower = sa_setup.borrower_address.clone();
+    let liq_staking_amount = rust_biguint!(STAKE_PER_POSITION) * DECIMALS;
+
+    sa_setup.default_lenders();
+
+    // the epochs without borrows are priced at 0 utilisation before the first borrow
+    sa_setup.b_mock.set_block_epoch(50);
+    for i in 1..=4u64 {
+        sa_setup
+            .call_borrow(&borrower, i, &liq_staking_amount, i, 18_750)
+            .assert_ok();
+    }
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            assert_eq!(sc.lender_rewards_index().get(), managed_biguint!(0));
+            assert_eq!(sc.last_rewards_update_epoch().get(), 50);
+        })
+        .assert_ok();
+    sa_setup.call_claim_staking_rewards().assert_ok();
+    sa_setup.call_convert_staking_token().assert_ok();
+
+    // same rewards as 25 epochs after the default borrows
+    sa_setup.b_mock.set_block_epoch(75);
+    assert_eq!(sa_setup.call_get_lender_claimable_rewards(1, 100_000), 948);
+    assert_eq!(sa_setup.call_get_lender_claimable_rewards(2, 50_000), 474);
+}
+
+#[test]
+fn withdraw_protocol_revenue_test() {
+    let _ = DebugApi::dummy();
+    let mut sa_setup = SavingsAccountSetup::new(savings_account::contract_obj);
+    let treasury = sa_setup.b_mock.create_user_account(&rust_biguint!(0));
+
+    sa_setup.default_lenders();
+    sa_setup.default_borrows();
+    sa_setup.call_claim_staking_rewards().assert_ok();
+    sa_setup.call_convert_staking_token().assert_ok();
+
+    // 10% of the 10,000 stablecoins received from staking rewards
+    sa_setup
+        .call_withdraw_protocol_revenue(&treasury, 1_500)
+        .assert_user_error("Not enough protocol revenue");
+    let tx_result = sa_setup.call_withdraw_protocol_revenue(&treasury, 400);
+    tx_result.assert_ok();
+
+    let mut expected_topics = vec![
+        sa_setup.owner_address.to_vec(),
+        treasury.to_vec(),
+        top_encode_to_vec_u8_or_panic(&400u64),
+        top_encode_to_vec_u8_or_panic(&25u64),
+    ];
+    expected_topics.extend(sa_setup.get_rates_topics());
+    check_event_topics(&tx_result, b"withdrawProtocolRevenue", &expected_topics);
+
+    sa_setup
+        .b_mock
+        .check_esdt_balance(&treasury, STABLECOIN_TOKEN_ID, &rust_biguint!(400));
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            assert_eq!(sc.protocol_revenue().get(), managed_biguint!(600));
+            assert_eq!(sc.stablecoin_reserves().get(), managed_biguint!(9_000));
+        })
+        .assert_ok();
+}
+
 #[test]
 fn withdraw_before_claim_rewards_test() {
     let _ = DebugApi::dummy();
@@ -140,15 +248,15 @@ fn withdraw_before_claim_rewards_test() {
 
     sa_setup.b_mock.set_block_epoch(50);
 
-    // withdraw the initial 50,000 lent + 3,167 as rewards (calculate in previous test)
+    // withdraw the initial 50,000 lent + 474 as rewards (calculate in previous test)
     sa_setup
-        .call_withdraw(&second_lender, 2, 50_000, 53_167)
+        .call_withdraw(&second_lender, 2, 50_000, 50_474)
         .assert_ok();
 
     sa_setup.b_mock.check_esdt_balance(
         &second_lender,
         STABLECOIN_TOKEN_ID,
-        &rust_biguint!(50_000 + 53_167),
+        &rust_biguint!(50_000 + 50_474),
     );
 
     sa_setup
@@ -179,7 +287,7 @@ fn withdraw_after_claim_rewards_test() {
     sa_setup.b_mock.set_block_epoch(50);
 
     sa_setup
-        .call_lender_claim_rewards(&second_lender, 2, 50_000, 3, 3_167, false)
+        .call_lender_claim_rewards(&second_lender, 2, 50_000, 3, 474, false)
         .assert_ok();
     sa_setup
         .call_withdraw(&second_lender, 3, 50_000, 50_000)
@@ -188,7 +296,7 @@ fn withdraw_after_claim_rewards_test() {
     sa_setup.b_mock.check_esdt_balance(
         &second_lender,
         STABLECOIN_TOKEN_ID,
-        &rust_biguint!(50_000 + 53_167),
+        &rust_biguint!(50_000 + 50_474),
     );
 }
 
@@ -205,275 +313,2524 @@ fn withdraw_partial_test() {
 
     sa_setup.b_mock.set_block_epoch(50);
 
-    // intial 25_000 + ~(3_167 / 2)
+    // intial 25_000 + ~(474 / 2)
     sa_setup
-        .call_withdraw(&second_lender, 2, 25_000, 26_584)
+        .call_withdraw(&second_lender, 2, 25_000, 25_237)
         .assert_ok();
 
-    // since there is less total lent amount now, the penalty amount per lend token increases,
-    // so less is withdrawn
+    // rewards were already updated for this epoch, so the rest gets the same amount
     sa_setup
-        .call_withdraw(&second_lender, 2, 25_000, 26_175)
+        .call_withdraw(&second_lender, 2, 25_000, 25_237)
         .assert_ok();
 }
 
 #[test]
-fn repay_full_first_pos_test() {
+fn withdraw_with_penalty_test() {
     let _ = DebugApi::dummy();
     let mut sa_setup = SavingsAccountSetup::new(savings_account::contract_obj);
-    let borrower = sa_setup.borrower_address.clone();
-    let borrow_token_amount = rust_biguint!(STAKE_PER_POSITION) * DECIMALS;
+    let second_lender = sa_setup.second_lender_address.clone();
 
     sa_setup.default_lenders();
     sa_setup.default_borrows();
-    sa_setup.call_claim_staking_rewards().assert_ok();
-    sa_setup.call_convert_staking_token().assert_ok();
-    sa_setup.default_claim_rewards();
 
-    // one year after borrow
-    sa_setup.b_mock.set_block_epoch(390);
+    // staking rewards were not converted, so there are no reserves to pay the lenders
+    sa_setup.b_mock.set_block_epoch(50);
 
-    sa_setup
-        .b_mock
-        .check_esdt_balance(&borrower, STABLECOIN_TOKEN_ID, &rust_biguint!(75_000));
+    // 50,000 out of total 150,000 => ~33% of 1,422
+    assert_eq!(sa_setup.call_get_penaly_amount(50_000), 474);
+    assert_eq!(sa_setup.call_get_lender_claimable_rewards(2, 50_000), 0);
 
-    // repay - too few stablecoins
     sa_setup
-        .call_repay(&borrower, 1, &borrow_token_amount, 10_000, 5, 0)
-        .assert_user_error("Not enough stablecoins paid to cover the debt");
+        .call_lender_claim_rewards(&second_lender, 2, 50_000, 3, 0, false)
+        .assert_user_error("No rewards to claim");
 
-    // repay - ok
-    // borrow rate = 50% + 2/3 * 10% = 56,66%, which leads to 25,000 * 56,66% = 14,165 debt,
-    // so ~39,165 as total amount needed
     sa_setup
-        .call_repay(&borrower, 1, &borrow_token_amount, 75_000, 5, 35_834)
+        .call_withdraw(&second_lender, 2, 50_000, 50_000)
         .assert_ok();
 
-    sa_setup.b_mock.check_nft_balance(
-        &borrower,
-        LIQUID_STAKING_TOKEN_ID,
-        5,
-        &borrow_token_amount,
-        Some(&elrond_wasm::elrond_codec::Empty),
-    );
     sa_setup
         .b_mock
-        .check_esdt_balance(&borrower, STABLECOIN_TOKEN_ID, &rust_biguint!(35_834));
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            assert_eq!(sc.missing_rewards().get(), managed_biguint!(948));
+            assert_eq!(
+                sc.total_missed_rewards_by_claim_since_last_calculation()
+                    .get(),
+                managed_biguint!(474)
+            );
+        })
+        .assert_ok();
+}
 
-    // check staking positions list consistency
+#[test]
+fn rewards_iou_test() {
+    let _ = DebugApi::dummy();
+    let mut sa_setup = SavingsAccountSetup::new(savings_account::contract_obj);
+    let first_lender = sa_setup.first_lender_address.clone();
+    let second_lender = sa_setup.second_lender_address.clone();
+
+    sa_setup.default_lenders();
+    sa_setup.default_borrows();
+
+    // the 474 rewards lost to the penalty are owed to the lender
+    sa_setup.b_mock.set_block_epoch(50);
+    sa_setup
+        .call_withdraw(&second_lender, 2, 50_000, 50_000)
+        .assert_ok();
+    sa_setup
+        .call_claim_rewards_iou(&second_lender, 0)
+        .assert_user_error("No rewards IOU to claim");
+
+    // IOUs are funded before the missing rewards: 9,000 - 474 - 948
+    sa_setup.call_claim_staking_rewards().assert_ok();
+    sa_setup.call_convert_staking_token().assert_ok();
     sa_setup
         .b_mock
         .execute_query(&sa_setup.sa_wrapper, |sc| {
-            // check staking positions mapper
-            let mapper = sc.staking_positions();
-            assert_eq!(
-                mapper.get_staking_position(2),
-                StakingPosition {
-                    liquid_staking_nonce: 6,
-                    prev_pos_id: 0,
-                    next_pos_id: 3,
-                }
-            );
-            assert_eq!(
-                mapper.get_staking_position(3),
-                StakingPosition {
-                    liquid_staking_nonce: 7,
-                    prev_pos_id: 2,
-                    next_pos_id: 4,
-                }
-            );
             assert_eq!(
-                mapper.get_staking_position(4),
-                StakingPosition {
-                    liquid_staking_nonce: 8,
-                    prev_pos_id: 3,
-                    next_pos_id: 0,
-                }
+                sc.rewards_iou(&managed_address!(&second_lender)).get(),
+                managed_biguint!(474)
             );
+            assert_eq!(sc.total_rewards_iou().get(), managed_biguint!(474));
+            assert_eq!(sc.rewards_iou_funds().get(), managed_biguint!(474));
+            assert_eq!(sc.missing_rewards().get(), managed_biguint!(0));
+            assert_eq!(sc.stablecoin_reserves().get(), managed_biguint!(7_578));
         })
         .assert_ok();
 
-    // position with ID 1 is now invalid
+    sa_setup
+        .call_claim_rewards_iou(&first_lender, 0)
+        .assert_user_error("No rewards IOU to claim");
+    sa_setup
+        .call_claim_rewards_iou(&second_lender, 474)
+        .assert_ok();
+    sa_setup.b_mock.check_esdt_balance(
+        &second_lender,
+        STABLECOIN_TOKEN_ID,
+        &rust_biguint!(100_474),
+    );
+
     sa_setup
         .b_mock
         .execute_query(&sa_setup.sa_wrapper, |sc| {
-            let _ = sc.staking_positions().get_staking_position(1);
+            assert_eq!(sc.total_rewards_iou().get(), managed_biguint!(0));
+            assert_eq!(sc.rewards_iou_funds().get(), managed_biguint!(0));
         })
-        .assert_user_error("Invalid staking position ID");
+        .assert_ok();
 }
 
 #[test]
-fn repay_other_pos_test() {
+fn borrow_requested_amount_test() {
     let _ = DebugApi::dummy();
     let mut sa_setup = SavingsAccountSetup::new(savings_account::contract_obj);
     let borrower = sa_setup.borrower_address.clone();
-    let borrow_token_amount = rust_biguint!(STAKE_PER_POSITION) * DECIMALS;
+    let liq_staking_amount = rust_biguint!(STAKE_PER_POSITION) * DECIMALS;
 
     sa_setup.default_lenders();
-    sa_setup.default_borrows();
-    sa_setup.call_claim_staking_rewards().assert_ok();
-    sa_setup.call_convert_staking_token().assert_ok();
-    sa_setup.default_claim_rewards();
+    sa_setup.b_mock.set_block_epoch(25);
 
-    // one year after borrow
-    sa_setup.b_mock.set_block_epoch(390);
+    // at most 25,000 * 75% = 18,750 can be borrowed
+    sa_setup
+        .call_borrow_amount(&borrower, 1, &liq_staking_amount, Some(18_751), 1, 0)
+        .assert_user_error("Borrow amount above loan to value limit");
+    sa_setup
+        .call_borrow_amount(&borrower, 1, &liq_staking_amount, Some(0), 1, 0)
+        .assert_user_error("Invalid borrow amount");
 
+    // 40% LTV
     sa_setup
-        .call_repay(&borrower, 3, &borrow_token_amount, 75_000, 7, 35_834)
+        .call_borrow_amount(&borrower, 1, &liq_staking_amount, Some(10_000), 1, 10_000)
         .assert_ok();
-
-    // check staking positions list consistency
     sa_setup
         .b_mock
         .execute_query(&sa_setup.sa_wrapper, |sc| {
-            // check staking positions mapper
-            let mapper = sc.staking_positions();
-            assert_eq!(
-                mapper.get_staking_position(1),
-                StakingPosition {
-                    liquid_staking_nonce: 5,
-                    prev_pos_id: 0,
-                    next_pos_id: 2,
-                }
-            );
-            assert_eq!(
-                mapper.get_staking_position(2),
-                StakingPosition {
-                    liquid_staking_nonce: 6,
-                    prev_pos_id: 1,
-                    next_pos_id: 4,
-                }
-            );
+            let borrow_position = sc.borrow_position(1).get();
+            assert_eq!(borrow_position.borrowed_amount, managed_biguint!(10_000));
             assert_eq!(
-                mapper.get_staking_position(4),
-                StakingPosition {
-                    liquid_staking_nonce: 8,
-                    prev_pos_id: 2,
-                    next_pos_id: 0,
-                }
+                borrow_position.metadata.borrowed_amount,
+                managed_biguint!(10_000)
             );
+            assert_eq!(sc.borrowed_amount().get(), managed_biguint!(10_000));
         })
         .assert_ok();
 
-    // position with ID 3 is now invalid
+    // 25,000 * 80% / 10,000
+    assert_eq!(sa_setup.call_get_health_factor(1), 2_000_000_000);
+
+    // one year after borrow, at 50% + 1/15 * 10% = 50,88% borrow rate
+    sa_setup.b_mock.set_block_epoch(390);
     sa_setup
         .b_mock
         .execute_query(&sa_setup.sa_wrapper, |sc| {
-            let _ = sc.staking_positions().get_staking_position(3);
+            assert_eq!(
+                sc.get_repay_amount(1, managed_biguint!(STAKE_PER_POSITION) * DECIMALS),
+                managed_biguint!(16_628)
+            );
         })
-        .assert_user_error("Invalid staking position ID");
+        .assert_ok();
 }
 
 #[test]
-fn repay_last_pos_test() {
+fn add_collateral_and_borrow_more_test() {
     let _ = DebugApi::dummy();
     let mut sa_setup = SavingsAccountSetup::new(savings_account::contract_obj);
     let borrower = sa_setup.borrower_address.clone();
-    let borrow_token_amount = rust_biguint!(STAKE_PER_POSITION) * DECIMALS;
+    let half_liq_staking_amount = rust_biguint!(STAKE_PER_POSITION) * DECIMALS / 2u32;
+    let liq_staking_amount = rust_biguint!(STAKE_PER_POSITION) * DECIMALS;
 
     sa_setup.default_lenders();
-    sa_setup.default_borrows();
-    sa_setup.call_claim_staking_rewards().assert_ok();
-    sa_setup.call_convert_staking_token().assert_ok();
-    sa_setup.default_claim_rewards();
-
-    // one year after borrow
-    sa_setup.b_mock.set_block_epoch(390);
+    sa_setup.b_mock.set_block_epoch(25);
 
     sa_setup
-        .call_repay(&borrower, 4, &borrow_token_amount, 75_000, 8, 35_834)
+        .call_borrow_amount(
+            &borrower,
+            1,
+            &half_liq_staking_amount,
+            Some(5_000),
+            1,
+            5_000,
+        )
         .assert_ok();
 
-    // check staking positions list consistency
+    sa_setup
+        .call_add_collateral(
+            &borrower,
+            1,
+            &half_liq_staking_amount,
+            1,
+            &half_liq_staking_amount,
+            2,
+        )
+        .assert_ok();
     sa_setup
         .b_mock
         .execute_query(&sa_setup.sa_wrapper, |sc| {
-            // check staking positions mapper
-            let mapper = sc.staking_positions();
+            assert!(sc.borrow_position(1).is_empty());
+
+            let borrow_position = sc.borrow_position(2).get();
             assert_eq!(
-                mapper.get_staking_position(1),
-                StakingPosition {
-                    liquid_staking_nonce: 5,
-                    prev_pos_id: 0,
-                    next_pos_id: 2,
-                }
+                borrow_position.liquid_staking_amount,
+                managed_biguint!(STAKE_PER_POSITION) * DECIMALS
             );
+            assert_eq!(borrow_position.borrowed_amount, managed_biguint!(5_000));
+            assert_eq!(borrow_position.metadata.staking_position_id, 1);
+        })
+        .assert_ok();
+
+    // one year later
+    sa_setup.b_mock.set_block_epoch(390);
+
+    sa_setup
+        .call_borrow_more(
+            &borrower,
+            2,
+            &half_liq_staking_amount,
+            Some(1_000),
+            3,
+            1_000,
+        )
+        .assert_user_error("Must send all the Borrow SFTs of the position");
+    sa_setup
+        .call_borrow_more(&borrower, 2, &liq_staking_amount, Some(10_474), 3, 10_474)
+        .assert_user_error("Borrow amount above loan to value limit");
+
+    // 5,000 * (1 + 50,44% / 365)^365 = 8,277 owed so far, up to 18,750 allowed
+    sa_setup
+        .call_borrow_more(&borrower, 2, &liq_staking_amount, Some(1_000), 3, 1_000)
+        .assert_ok();
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            let borrow_position = sc.borrow_position(3).get();
+            assert_eq!(borrow_position.borrowed_amount, managed_biguint!(6_000));
             assert_eq!(
-                mapper.get_staking_position(2),
-                StakingPosition {
-                    liquid_staking_nonce: 6,
-                    prev_pos_id: 1,
-                    next_pos_id: 3,
-                }
+                borrow_position.metadata.borrow_index,
+                managed_biguint!(1_070_705_132)
             );
+
+            // the interest accrued before is kept
             assert_eq!(
-                mapper.get_staking_position(3),
-                StakingPosition {
-                    liquid_staking_nonce: 7,
-                    prev_pos_id: 2,
-                    next_pos_id: 0,
-                }
+                sc.get_repay_amount(3, managed_biguint!(STAKE_PER_POSITION) * DECIMALS),
+                managed_biguint!(9_277)
             );
+            assert_eq!(sc.borrowed_amount().get(), managed_biguint!(6_000));
+            assert_eq!(sc.total_debt().get(), managed_biguint!(9_277));
         })
         .assert_ok();
 
-    // position with ID 4 is now invalid
     sa_setup
         .b_mock
-        .execute_query(&sa_setup.sa_wrapper, |sc| {
-            let _ = sc.staking_positions().get_staking_position(4);
-        })
-        .assert_user_error("Invalid staking position ID");
+        .check_esdt_balance(&borrower, STABLECOIN_TOKEN_ID, &rust_biguint!(6_000));
 }
 
 #[test]
-fn repay_partial_test() {
+fn add_collateral_after_claim_staking_rewards_test() {
     let _ = DebugApi::dummy();
     let mut sa_setup = SavingsAccountSetup::new(savings_account::contract_obj);
     let borrower = sa_setup.borrower_address.clone();
-    let borrow_token_amount = rust_biguint!(150) * DECIMALS;
+    let half_liq_staking_amount = rust_biguint!(STAKE_PER_POSITION) * DECIMALS / 2u32;
+    let liq_staking_amount = rust_biguint!(STAKE_PER_POSITION) * DECIMALS;
 
     sa_setup.default_lenders();
-    sa_setup.default_borrows();
-    sa_setup.call_claim_staking_rewards().assert_ok();
-    sa_setup.call_convert_staking_token().assert_ok();
-    sa_setup.default_claim_rewards();
+    sa_setup.b_mock.set_block_epoch(25);
 
-    // one year after borrow
-    sa_setup.b_mock.set_block_epoch(390);
+    sa_setup
+        .call_borrow_amount(
+            &borrower,
+            1,
+            &half_liq_staking_amount,
+            Some(5_000),
+            1,
+            5_000,
+        )
+        .assert_ok();
 
-    // repay 150 out of the total 250
+    // the position's tokens are moved to nonce 5
+    sa_setup.call_claim_staking_rewards().assert_ok();
+
+    // the borrower's tokens of nonce 1 get their own staking position
     sa_setup
-        .call_repay(&borrower, 1, &borrow_token_amount, 75_000, 5, 51_501)
+        .call_add_collateral(
+            &borrower,
+            1,
+            &half_liq_staking_amount,
+            1,
+            &half_liq_staking_amount,
+            2,
+        )
         .assert_ok();
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            let borrow_position = sc.borrow_position(2).get();
+            assert_eq!(
+                borrow_position.liquid_staking_amount,
+                managed_biguint!(STAKE_PER_POSITION) * DECIMALS
+            );
+            assert_eq!(borrow_position.metadata.staking_position_id, 1);
+            assert_eq!(borrow_position.collateral.len(), 2);
+            assert_eq!(borrow_position.collateral.get(0).staking_position_id, 1);
+            assert_eq!(borrow_position.collateral.get(1).staking_position_id, 2);
 
+            let mapper = sc.staking_positions();
+            assert_eq!(mapper.get_staking_position(1).liquid_staking_nonce, 5);
+            assert_eq!(mapper.get_staking_position(2).liquid_staking_nonce, 1);
+        })
+        .assert_ok();
+
+    // taken from the first staking position, then from the second one
+    sa_setup
+        .call_withdraw_collateral(
+            &borrower,
+            2,
+            &liq_staking_amount,
+            &(rust_biguint!(150) * DECIMALS),
+            3,
+        )
+        .assert_ok();
+    sa_setup.b_mock.check_nft_balance(
+        &borrower,
+        LIQUID_STAKING_TOKEN_ID,
+        5,
+        &half_liq_staking_amount,
+        Some(&elrond_wasm::elrond_codec::Empty),
+    );
+    sa_setup.b_mock.check_nft_balance(
+        &borrower,
+        LIQUID_STAKING_TOKEN_ID,
+        1,
+        &(rust_biguint!(25) * DECIMALS),
+        Some(&elrond_wasm::elrond_codec::Empty),
+    );
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            let borrow_position = sc.borrow_position(3).get();
+            assert_eq!(
+                borrow_position.liquid_staking_amount,
+                managed_biguint!(100) * DECIMALS
+            );
+            assert_eq!(borrow_position.collateral.len(), 1);
+            assert_eq!(borrow_position.collateral.get(0).staking_position_id, 2);
+        })
+        .assert_ok();
+}
+
+#[test]
+fn withdraw_collateral_test() {
+    let _ = DebugApi::dummy();
+    let mut sa_setup = SavingsAccountSetup::new(savings_account::contract_obj);
+    let borrower = sa_setup.borrower_address.clone();
+    let liq_staking_amount = rust_biguint!(STAKE_PER_POSITION) * DECIMALS;
+
+    sa_setup.default_lenders();
+    sa_setup.b_mock.set_block_epoch(25);
+
+    sa_setup
+        .call_borrow_amount(&borrower, 1, &liq_staking_amount, Some(5_000), 1, 5_000)
+        .assert_ok();
+
+    sa_setup
+        .call_withdraw_collateral(&borrower, 1, &liq_staking_amount, &liq_staking_amount, 2)
+        .assert_user_error("Invalid collateral amount");
+
+    // 5,000 worth of collateral left, so at most 3,750 could be borrowed
+    sa_setup
+        .call_withdraw_collateral(
+            &borrower,
+            1,
+            &liq_staking_amount,
+            &(rust_biguint!(200) * DECIMALS),
+            2,
+        )
+        .assert_user_error("Withdrawal would exceed the loan to value limit");
+
+    // 10,000 worth of collateral left, so at most 7,500 could be borrowed
+    sa_setup
+        .call_withdraw_collateral(
+            &borrower,
+            1,
+            &liq_staking_amount,
+            &(rust_biguint!(150) * DECIMALS),
+            2,
+        )
+        .assert_ok();
+
+    sa_setup.b_mock.check_nft_balance(
+        &borrower,
+        LIQUID_STAKING_TOKEN_ID,
+        1,
+        &(rust_biguint!(150) * DECIMALS),
+        Some(&elrond_wasm::elrond_codec::Empty),
+    );
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            assert!(sc.borrow_position(1).is_empty());
+
+            let borrow_position = sc.borrow_position(2).get();
+            assert_eq!(
+                borrow_position.liquid_staking_amount,
+                managed_biguint!(100) * DECIMALS
+            );
+            assert_eq!(
+                borrow_position.borrow_token_amount,
+                managed_biguint!(100) * DECIMALS
+            );
+            assert_eq!(borrow_position.borrowed_amount, managed_biguint!(5_000));
+
+            assert_eq!(
+                sc.staking_positions().get_staking_position(1),
+                StakingPosition {
+                    liquid_staking_nonce: 1,
+                    prev_pos_id: 0,
+                    next_pos_id: 0,
+                }
+            );
+        })
+        .assert_ok();
+
+    // 10,000 * 80% / 5,000
+    assert_eq!(sa_setup.call_get_health_factor(2), 1_600_000_000);
+}
+
+#[test]
+fn repay_quote_test() {
+    let _ = DebugApi::dummy();
+    let mut sa_setup = SavingsAccountSetup::new(savings_account::contract_obj);
+    let borrower = sa_setup.borrower_address.clone();
+    let borrow_token_amount = rust_biguint!(STAKE_PER_POSITION) * DECIMALS;
+    let half_borrow_token_amount = &borrow_token_amount / 2u32;
+
+    sa_setup.default_lenders();
+    sa_setup.default_borrows();
+
+    // one year after borrow
+    sa_setup.b_mock.set_block_epoch(390);
+
+    // debt = 18,750 * (1 + 56,66% / 365)^365 - 18,750 = 14,280
+    // health factor = 25,000 * 80% / 33,030 = ~0.605
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            let quote =
+                sc.get_repay_quote(1, managed_biguint!(STAKE_PER_POSITION) * DECIMALS / 2u32);
+            assert_eq!(quote.stablecoins_needed, managed_biguint!(16_515));
+            assert_eq!(quote.borrowed_amount, managed_biguint!(9_375));
+            assert_eq!(quote.debt, managed_biguint!(7_140));
+            assert_eq!(
+                quote.liquid_staking_amount,
+                managed_biguint!(STAKE_PER_POSITION) * DECIMALS / 2u32
+            );
+            assert_eq!(quote.liquid_staking_tokens.len(), 1);
+            assert_eq!(quote.liquid_staking_tokens.get(0).token_nonce, 1);
+            assert_eq!(quote.collateral_value, managed_biguint!(12_500));
+            assert_eq!(quote.health_factor, managed_biguint!(605_510_142));
+
+            assert_eq!(
+                sc.get_repay_amount(1, managed_biguint!(STAKE_PER_POSITION) * DECIMALS),
+                managed_biguint!(33_030)
+            );
+        })
+        .assert_ok();
+
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            let _ = sc.get_repay_amount(1, managed_biguint!(STAKE_PER_POSITION + 1) * DECIMALS);
+        })
+        .assert_user_error("Invalid Borrow SFT amount");
+
+    // the quoted amount is exactly enough to repay
+    sa_setup
+        .call_repay(&borrower, 1, &half_borrow_token_amount, 16_515, 1, 0)
+        .assert_ok();
+}
+
+#[test]
+fn repay_full_first_pos_test() {
+    let _ = DebugApi::dummy();
+    let mut sa_setup = SavingsAccountSetup::new(savings_account::contract_obj);
+    let borrower = sa_setup.borrower_address.clone();
+    let borrow_token_amount = rust_biguint!(STAKE_PER_POSITION) * DECIMALS;
+
+    sa_setup.default_lenders();
+    sa_setup.default_borrows();
+    sa_setup.call_claim_staking_rewards().assert_ok();
+    sa_setup.call_convert_staking_token().assert_ok();
+    sa_setup.default_claim_rewards();
+
+    // one year after borrow
+    sa_setup.b_mock.set_block_epoch(390);
+
+    sa_setup
+        .b_mock
+        .check_esdt_balance(&borrower, STABLECOIN_TOKEN_ID, &rust_biguint!(75_000));
+
+    // repay - too few stablecoins
+    sa_setup
+        .call_repay(&borrower, 1, &borrow_token_amount, 10_000, 5, 0)
+        .assert_user_error("Not enough stablecoins paid to cover the debt");
+
+    // repay - ok
+    // borrow rate starts at 50% + 2/3 * 10% = 56,66%, compounded every epoch,
+    // and increases with the utilisation as interest accrues,
+    // which leads to 18,750 * 76,59% = 14,361 debt, so 33,111 as total amount needed
+    sa_setup
+        .call_repay(&borrower, 1, &borrow_token_amount, 75_000, 5, 41_889)
+        .assert_ok();
+
+    sa_setup.b_mock.check_nft_balance(
+        &borrower,
+        LIQUID_STAKING_TOKEN_ID,
+        5,
+        &borrow_token_amount,
+        Some(&elrond_wasm::elrond_codec::Empty),
+    );
+    sa_setup
+        .b_mock
+        .check_esdt_balance(&borrower, STABLECOIN_TOKEN_ID, &rust_biguint!(41_889));
+
+    // check staking positions list consistency
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            // check staking positions mapper
+            let mapper = sc.staking_positions();
+            assert_eq!(
+                mapper.get_staking_position(2),
+                StakingPosition {
+                    liquid_staking_nonce: 6,
+                    prev_pos_id: 0,
+                    next_pos_id: 3,
+                }
+            );
+            assert_eq!(
+                mapper.get_staking_position(3),
+                StakingPosition {
+                    liquid_staking_nonce: 7,
+                    prev_pos_id: 2,
+                    next_pos_id: 4,
+                }
+            );
+            assert_eq!(
+                mapper.get_staking_position(4),
+                StakingPosition {
+                    liquid_staking_nonce: 8,
+                    prev_pos_id: 3,
+                    next_pos_id: 0,
+                }
+            );
+        })
+        .assert_ok();
+
+    // position with ID 1 is now invalid
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            let _ = sc.staking_positions().get_staking_position(1);
+        })
+        .assert_user_error("Invalid staking position ID");
+}
+
+#[test]
+fn repay_other_pos_test() {
+    let _ = DebugApi::dummy();
+    let mut sa_setup = SavingsAccountSetup::new(savings_account::contract_obj);
+    let borrower = sa_setup.borrower_address.clone();
+    let borrow_token_amount = rust_biguint!(STAKE_PER_POSITION) * DECIMALS;
+
+    sa_setup.default_lenders();
+    sa_setup.default_borrows();
+    sa_setup.call_claim_staking_rewards().assert_ok();
+    sa_setup.call_convert_staking_token().assert_ok();
+    sa_setup.default_claim_rewards();
+
+    // one year after borrow
+    sa_setup.b_mock.set_block_epoch(390);
+
+    sa_setup
+        .call_repay(&borrower, 3, &borrow_token_amount, 75_000, 7, 41_889)
+        .assert_ok();
+
+    // check staking positions list consistency
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            // check staking positions mapper
+            let mapper = sc.staking_positions();
+            assert_eq!(
+                mapper.get_staking_position(1),
+                StakingPosition {
+                    liquid_staking_nonce: 5,
+                    prev_pos_id: 0,
+                    next_pos_id: 2,
+                }
+            );
+            assert_eq!(
+                mapper.get_staking_position(2),
+                StakingPosition {
+                    liquid_staking_nonce: 6,
+                    prev_pos_id: 1,
+                    next_pos_id: 4,
+                }
+            );
+            assert_eq!(
+                mapper.get_staking_position(4),
+                StakingPosition {
+                    liquid_staking_nonce: 8,
+                    prev_pos_id: 2,
+                    next_pos_id: 0,
+                }
+            );
+        })
+        .assert_ok();
+
+    // position with ID 3 is now invalid
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            let _ = sc.staking_positions().get_staking_position(3);
+        })
+        .assert_user_error("Invalid staking position ID");
+}
+
+#[test]
+fn repay_multiple_pos_test() {
+    let _ = DebugApi::dummy();
+    let mut sa_setup = SavingsAccountSetup::new(savings_account::contract_obj);
+    let borrower = sa_setup.borrower_address.clone();
+    let borrow_token_amount = rust_biguint!(STAKE_PER_POSITION) * DECIMALS;
+    let borrow_tokens = [
+        (1, borrow_token_amount.clone()),
+        (3, borrow_token_amount.clone()),
+    ];
+
+    sa_setup.default_lenders();
+    sa_setup.default_borrows();
+    sa_setup.call_claim_staking_rewards().assert_ok();
+    sa_setup.call_convert_staking_token().assert_ok();
+    sa_setup.default_claim_rewards();
+
+    // one year after borrow
+    sa_setup.b_mock.set_block_epoch(390);
+
+    // 33,111 needed for each position, nothing is repaid if the total is not covered
+    sa_setup
+        .call_repay_multiple(&borrower, &borrow_tokens, 66_221, &[5, 7], 0)
+        .assert_user_error("Not enough stablecoins paid to cover the debt");
+    sa_setup
+        .call_repay_multiple(&borrower, &borrow_tokens, 75_000, &[5, 7], 8_778)
+        .assert_ok();
+
+    sa_setup.b_mock.check_nft_balance(
+        &borrower,
+        LIQUID_STAKING_TOKEN_ID,
+        5,
+        &borrow_token_amount,
+        Some(&elrond_wasm::elrond_codec::Empty),
+    );
+    sa_setup.b_mock.check_nft_balance(
+        &borrower,
+        LIQUID_STAKING_TOKEN_ID,
+        7,
+        &borrow_token_amount,
+        Some(&elrond_wasm::elrond_codec::Empty),
+    );
+    sa_setup
+        .b_mock
+        .check_esdt_balance(&borrower, STABLECOIN_TOKEN_ID, &rust_biguint!(8_778));
+
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            assert!(sc.borrow_position(1).is_empty());
+            assert!(sc.borrow_position(3).is_empty());
+            assert_eq!(sc.borrowed_amount().get(), managed_biguint!(37_500));
+
+            let mapper = sc.staking_positions();
+            assert_eq!(
+                mapper.get_staking_position(2),
+                StakingPosition {
+                    liquid_staking_nonce: 6,
+                    prev_pos_id: 0,
+                    next_pos_id: 4,
+                }
+            );
+            assert_eq!(
+                mapper.get_staking_position(4),
+                StakingPosition {
+                    liquid_staking_nonce: 8,
+                    prev_pos_id: 2,
+                    next_pos_id: 0,
+                }
+            );
+        })
+        .assert_ok();
+}
+
+#[test]
+fn repay_last_pos_test() {
+    let _ = DebugApi::dummy();
+    let mut sa_setup = SavingsAccountSetup::new(savings_account::contract_obj);
+    let borrower = sa_setup.borrower_address.clone();
+    let borrow_token_amount = rust_biguint!(STAKE_PER_POSITION) * DECIMALS;
+
+    sa_setup.default_lenders();
+    sa_setup.default_borrows();
+    sa_setup.call_claim_staking_rewards().assert_ok();
+    sa_setup.call_convert_staking_token().assert_ok();
+    sa_setup.default_claim_rewards();
+
+    // one year after borrow
+    sa_setup.b_mock.set_block_epoch(390);
+
+    sa_setup
+        .call_repay(&borrower, 4, &borrow_token_amount, 75_000, 8, 41_889)
+        .assert_ok();
+
+    // check staking positions list consistency
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            // check staking positions mapper
+            let mapper = sc.staking_positions();
+            assert_eq!(
+                mapper.get_staking_position(1),
+                StakingPosition {
+                    liquid_staking_nonce: 5,
+                    prev_pos_id: 0,
+                    next_pos_id: 2,
+                }
+            );
+            assert_eq!(
+                mapper.get_staking_position(2),
+                StakingPosition {
+                    liquid_staking_nonce: 6,
+                    prev_pos_id: 1,
+                    next_pos_id: 3,
+                }
+            );
+            assert_eq!(
+                mapper.get_staking_position(3),
+                StakingPosition {
+                    liquid_staking_nonce: 7,
+                    prev_pos_id: 2,
+                    next_pos_id: 0,
+                }
+            );
+        })
+        .assert_ok();
+
+    // position with ID 4 is now invalid
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            let _ = sc.staking_positions().get_staking_position(4);
+        })
+        .assert_user_error("Invalid staking position ID");
+}
+
+#[test]
+fn repay_partial_test() {
+    let _ = DebugApi::dummy();
+    let mut sa_setup = SavingsAccountSetup::new(savings_account::contract_obj);
+    let borrower = sa_setup.borrower_address.clone();
+    let borrow_token_amount = rust_biguint!(150) * DECIMALS;
+
+    sa_setup.default_lenders();
+    sa_setup.default_borrows();
+    sa_setup.call_claim_staking_rewards().assert_ok();
+    sa_setup.call_convert_staking_token().assert_ok();
+    sa_setup.default_claim_rewards();
+
+    // one year after borrow
+    sa_setup.b_mock.set_block_epoch(390);
+
+    // repay 150 out of the total 250
+    // 18,750 * 150 / 250 = 11,250 borrowed, with 8,616 debt
+    sa_setup
+        .call_repay(&borrower, 1, &borrow_token_amount, 75_000, 5, 55_134)
+        .assert_ok();
+
+    sa_setup.b_mock.check_nft_balance(
+        &borrower,
+        BORROW_TOKEN_ID,
+        1,
+        &(rust_biguint!(100) * DECIMALS),
+        Some(&BorrowMetadata::<DebugApi> {
+            borrow_epoch: 25,
+            staking_position_id: 1,
+            staked_token_value_in_dollars_at_borrow: managed_biguint!(100),
+            borrowed_amount: managed_biguint!(18_750),
+            borrow_index: managed_biguint!(1_000_000_000),
+        }),
+    );
+    sa_setup.b_mock.check_nft_balance(
+        &borrower,
+        LIQUID_STAKING_TOKEN_ID,
+        5,
+        &(rust_biguint!(150) * DECIMALS),
+        Option::<&elrond_wasm::elrond_codec::Empty>::None,
+    );
+    sa_setup
+        .b_mock
+        .check_esdt_balance(&borrower, STABLECOIN_TOKEN_ID, &rust_biguint!(55_134));
+
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            assert_eq!(sc.lent_amount().get(), managed_biguint!(150_000));
+            assert_eq!(sc.borrowed_amount().get(), managed_biguint!(63_750));
+        })
+        .assert_ok();
+}
+
+#[test]
+fn repay_interest_test() {
+    let _ = DebugApi::dummy();
+    let mut sa_setup = SavingsAccountSetup::new(savings_account::contract_obj);
+    let borrower = sa_setup.borrower_address.clone();
+    let borrow_token_amount = rust_biguint!(STAKE_PER_POSITION) * DECIMALS;
+
+    sa_setup.default_lenders();
+    sa_setup.default_borrows();
+
+    sa_setup.b_mock.set_block_epoch(50);
+    sa_setup.call_accrue_interest(&borrower).assert_ok();
+
+    // 33,111 - 18,750 = 14,361 interest
+    sa_setup.b_mock.set_block_epoch(390);
+    sa_setup
+        .call_repay_interest(&borrower, 1, &borrow_token_amount, 14_360, 5, 0)
+        .assert_user_error("Not enough stablecoins paid to cover the interest");
+    sa_setup
+        .call_repay_interest(&borrower, 1, &borrow_token_amount, 15_000, 5, 639)
+        .assert_ok();
+
+    // principal and price snapshot are kept, interest accrues from the current index
+    sa_setup.b_mock.check_nft_balance(
+        &borrower,
+        BORROW_TOKEN_ID,
+        5,
+        &borrow_token_amount,
+        Some(&BorrowMetadata::<DebugApi> {
+            borrow_epoch: 390,
+            staking_position_id: 1,
+            staked_token_value_in_dollars_at_borrow: managed_biguint!(100),
+            borrowed_amount: managed_biguint!(18_750),
+            borrow_index: managed_biguint!(1_765_931_770),
+        }),
+    );
+    sa_setup
+        .b_mock
+        .check_esdt_balance(&borrower, STABLECOIN_TOKEN_ID, &rust_biguint!(60_639));
+
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            assert!(sc.borrow_position(1).is_empty());
+            assert_eq!(
+                sc.get_repay_amount(5, managed_biguint!(STAKE_PER_POSITION) * DECIMALS),
+                managed_biguint!(18_750)
+            );
+            assert_eq!(sc.borrowed_amount().get(), managed_biguint!(75_000));
+            assert_eq!(sc.total_debt().get(), managed_biguint!(118_082));
+        })
+        .assert_ok();
+
+    sa_setup
+        .call_repay_interest(&borrower, 5, &borrow_token_amount, 1_000, 6, 0)
+        .assert_user_error("No interest to pay");
+}
+
+#[test]
+fn repay_debt_for_test() {
+    let _ = DebugApi::dummy();
+    let mut sa_setup = SavingsAccountSetup::new(savings_account::contract_obj);
+    let borrower = sa_setup.borrower_address.clone();
+    let borrow_token_amount = rust_biguint!(STAKE_PER_POSITION) * DECIMALS;
+    let payer = sa_setup.b_mock.create_user_account(&rust_biguint!(0));
+    sa_setup
+        .b_mock
+        .set_esdt_balance(&payer, STABLECOIN_TOKEN_ID, &rust_biguint!(50_000));
+
+    sa_setup.default_lenders();
+    sa_setup.default_borrows();
+
+    sa_setup.b_mock.set_block_epoch(50);
+    sa_setup.call_accrue_interest(&borrower).assert_ok();
+
+    // 33,111 debt for each position, 13,111 paid by someone else
+    // principal repaid: 18,750 * 13,111 / 33,111 = 7,424
+    sa_setup.b_mock.set_block_epoch(390);
+    sa_setup
+        .call_repay_debt_for(&payer, 1, 13_111, 0)
+        .assert_ok();
+
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            let borrow_position = sc.borrow_position(1).get();
+            assert_eq!(borrow_position.borrowed_amount, managed_biguint!(11_326));
+            assert_eq!(
+                borrow_position.liquid_staking_amount,
+                managed_biguint!(STAKE_PER_POSITION) * DECIMALS
+            );
+            assert_eq!(
+                sc.get_repay_amount(1, managed_biguint!(STAKE_PER_POSITION) * DECIMALS),
+                managed_biguint!(20_000)
+            );
+
+            assert_eq!(sc.borrowed_amount().get(), managed_biguint!(67_576));
+            assert_eq!(sc.total_debt().get(), managed_biguint!(119_332));
+        })
+        .assert_ok();
+
+    // collateral stays with the Borrow SFT holder
+    sa_setup
+        .call_repay(&borrower, 1, &borrow_token_amount, 25_000, 1, 5_000)
+        .assert_ok();
+
+    // whole debt paid, the extra is sent back
+    sa_setup
+        .call_repay_debt_for(&payer, 2, 40_000, 6_889)
+        .assert_ok();
+    sa_setup
+        .b_mock
+        .check_esdt_balance(&payer, STABLECOIN_TOKEN_ID, &rust_biguint!(3_778));
+
+    sa_setup
+        .call_repay_debt_for(&payer, 2, 1_000, 0)
+        .assert_user_error("Position was liquidated");
+    sa_setup
+        .call_claim_liquidation_surplus(&borrower, 2, &borrow_token_amount, &borrow_token_amount)
+        .assert_ok();
+
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            assert!(sc.borrow_position(2).is_empty());
+            assert_eq!(sc.borrowed_amount().get(), managed_biguint!(37_500));
+        })
+        .assert_ok();
+}
+
+#[test]
+fn migrate_borrow_position_test() {
+    let _ = DebugApi::dummy();
+    let mut sa_setup = SavingsAccountSetup::new(savings_account::contract_obj);
+    let owner = sa_setup.owner_address.clone();
+    let borrower = sa_setup.borrower_address.clone();
+    let borrow_token_amount = rust_biguint!(STAKE_PER_POSITION) * DECIMALS;
+
+    sa_setup.default_lenders();
+    sa_setup.b_mock.set_block_epoch(25);
+    sa_setup
+        .legacy_borrow(&borrower, 1, &borrow_token_amount, 18_750)
+        .assert_ok();
+
+    // no position was saved for the old tokens
+    sa_setup.b_mock.set_block_epoch(50);
+    sa_setup
+        .call_repay(&borrower, 1, &borrow_token_amount, 18_750, 1, 0)
+        .assert_user_error("Invalid borrow token nonce");
+
+    sa_setup
+        .call_migrate_borrow_position(&borrower, 1, &borrow_token_amount)
+        .assert_user_error("Endpoint can only be called by owner");
+    sa_setup
+        .call_migrate_borrow_position(&owner, 1, &borrow_token_amount)
+        .assert_ok();
+    sa_setup
+        .call_migrate_borrow_position(&owner, 1, &borrow_token_amount)
+        .assert_user_error("Position already migrated");
+
+    // the index is set back 25 epochs at the current 50% rate,
+    // so the 18,750 borrowed already owe 19,402
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            let borrow_position = sc.borrow_position(1).get();
+            assert_eq!(borrow_position.borrowed_amount, managed_biguint!(18_750));
+            assert_eq!(
+                borrow_position.metadata.borrow_index,
+                managed_biguint!(1_005_490_720)
+            );
+            assert_eq!(
+                borrow_position.liquid_staking_amount,
+                managed_biguint!(STAKE_PER_POSITION) * DECIMALS
+            );
+            assert_eq!(borrow_position.metadata.staking_position_id, 1);
+            assert_eq!(sc.total_debt().get(), managed_biguint!(19_402));
+            assert_eq!(sc.borrowed_amount().get(), managed_biguint!(18_750));
+        })
+        .assert_ok();
+
+    // the migrated tokens are repaid like any other
+    sa_setup.b_mock.set_block_epoch(390);
+    sa_setup
+        .b_mock
+        .set_esdt_balance(&borrower, STABLECOIN_TOKEN_ID, &rust_biguint!(40_000));
+    sa_setup
+        .call_repay(&borrower, 1, &borrow_token_amount, 40_000, 1, 8_598)
+        .assert_ok();
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            assert!(sc.borrow_position(1).is_empty());
+            assert_eq!(sc.borrowed_amount().get(), managed_biguint!(0));
+        })
+        .assert_ok();
+}
+
+#[test]
+fn locked_lend_test() {
+    let _ = DebugApi::dummy();
+    let mut sa_setup = SavingsAccountSetup::new(savings_account::contract_obj);
+    let second_lender = sa_setup.second_lender_address.clone();
+
+    sa_setup
+        .call_set_lock_period(30, 900_000_000)
+        .assert_user_error("Invalid boost");
+    sa_setup.call_set_lock_period(30, 1_500_000_000).assert_ok();
+
+    sa_setup.default_lenders();
+    sa_setup.default_borrows();
+
+    sa_setup
+        .call_lend_with_lock(&second_lender, 20_000, Some(60), 3)
+        .assert_user_error("Invalid lock period");
+    sa_setup
+        .call_lend_with_lock(&second_lender, 20_000, Some(30), 3)
+        .assert_ok();
+    sa_setup.b_mock.check_nft_balance(
+        &second_lender,
+        LEND_TOKEN_ID,
+        3,
+        &rust_biguint!(20_000),
+        Some(&LendMetadata {
+            lend_epoch: 25,
+            lend_timestamp: 0,
+            lock_end_epoch: 55,
+            boost: 1_500_000_000,
+            auto_compound: false,
+        }),
+    );
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            assert_eq!(sc.lent_amount().get(), managed_biguint!(170_000));
+            assert_eq!(sc.lock_boost_amount().get(), managed_biguint!(10_000));
+        })
+        .assert_ok();
+
+    sa_setup.b_mock.set_block_epoch(40);
+    sa_setup
+        .call_withdraw(&second_lender, 3, 20_000, 20_000)
+        .assert_user_error("Lend tokens are still locked");
+    sa_setup
+        .call_queue_withdraw(&second_lender, 3, 20_000, 1)
+        .assert_user_error("Lend tokens are still locked");
+
+    // 177 rewards, boosted to 265, minus the 187 penalty
+    sa_setup.b_mock.set_block_epoch(55);
+    assert_eq!(sa_setup.call_get_lender_claimable_rewards(3, 20_000), 78);
+    sa_setup
+        .call_withdraw(&second_lender, 3, 20_000, 20_078)
+        .assert_ok();
+
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            assert_eq!(sc.lent_amount().get(), managed_biguint!(150_000));
+            assert_eq!(sc.lock_boost_amount().get(), managed_biguint!(0));
+        })
+        .assert_ok();
+}
+
+#[test]
+fn locked_lend_expired_test() {
+    let _ = DebugApi::dummy();
+    let mut sa_setup = SavingsAccountSetup::new(savings_account::contract_obj);
+    let second_lender = sa_setup.second_lender_address.clone();
+
+    sa_setup.call_set_lock_period(30, 1_500_000_000).assert_ok();
+
+    sa_setup.default_lenders();
+    sa_setup.default_borrows();
+    sa_setup.call_claim_staking_rewards().assert_ok();
+    sa_setup.call_convert_staking_token().assert_ok();
+
+    sa_setup
+        .call_lend_with_lock(&second_lender, 20_000, Some(30), 3)
+        .assert_ok();
+
+    // 390 rewards, only the first 30 of the 60 epochs are boosted
+    sa_setup.b_mock.set_block_epoch(85);
+    assert_eq!(sa_setup.call_get_lender_claimable_rewards(3, 20_000), 487);
+    sa_setup
+        .call_lender_claim_rewards(&second_lender, 3, 20_000, 4, 487, true)
+        .assert_ok();
+
+    // the ended lock is dropped from the reissued tokens
+    sa_setup.b_mock.check_nft_balance(
+        &second_lender,
+        LEND_TOKEN_ID,
+        4,
+        &rust_biguint!(20_000),
+        Some(&LendMetadata {
+            lend_epoch: 85,
+            lend_timestamp: 0,
+            lock_end_epoch: 0,
+            boost: 0,
+            auto_compound: false,
+        }),
+    );
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            assert_eq!(sc.lent_amount().get(), managed_biguint!(170_000));
+            assert_eq!(sc.lock_boost_amount().get(), managed_biguint!(0));
+        })
+        .assert_ok();
+}
+
+#[test]
+fn locked_lend_expired_untouched_test() {
+    let _ = DebugApi::dummy();
+    let mut sa_setup = SavingsAccountSetup::new(savings_account::contract_obj);
+    let second_lender = sa_setup.second_lender_address.clone();
+
+    sa_setup.call_set_lock_period(30, 1_500_000_000).assert_ok();
+
+    sa_setup.default_lenders();
+    sa_setup.default_borrows();
+    sa_setup
+        .call_lend_with_lock(&second_lender, 20_000, Some(30), 3)
+        .assert_ok();
+
+    // the holder never touches the LEND tokens after the lock ends,
+    // the next rewards update drops the lock boost by itself
+    sa_setup.b_mock.set_block_epoch(85);
+    sa_setup.call_lend(&second_lender, 10_000, 4).assert_ok();
+
+    // no reserves, so all the rewards since the borrows are missing
+    // 170,000 lent, plus the 10,000 lock boost for 30 out of the 60 epochs
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            assert_eq!(sc.lock_boost_amount().get(), managed_biguint!(0));
+            assert!(sc.lock_boost_amount_by_end_epoch().is_empty());
+            assert_eq!(sc.missing_rewards().get(), managed_biguint!(3_417));
+            assert_eq!(sc.lent_amount().get(), managed_biguint!(180_000));
+        })
+        .assert_ok();
+
+    // the boost is not taken out a second time when the tokens are withdrawn
+    // 487 rewards, as in the test above, minus the 379 penalty
+    sa_setup
+        .call_withdraw(&second_lender, 3, 20_000, 20_108)
+        .assert_ok();
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            assert_eq!(sc.lock_boost_amount().get(), managed_biguint!(0));
+            assert_eq!(sc.lent_amount().get(), managed_biguint!(160_000));
+        })
+        .assert_ok();
+}
+
+#[test]
+fn lend_for_shares_test() {
+    let _ = DebugApi::dummy();
+    let mut sa_setup = SavingsAccountSetup::new(savings_account::contract_obj);
+    let second_lender = sa_setup.second_lender_address.clone();
+
+    sa_setup.default_lenders();
+    sa_setup.default_borrows();
+    sa_setup.call_claim_staking_rewards().assert_ok();
+    sa_setup.call_convert_staking_token().assert_ok();
+
+    // first shares are minted 1:1
+    sa_setup
+        .call_lend_for_shares(&second_lender, 20_000, 20_000)
+        .assert_ok();
+    sa_setup
+        .b_mock
+        .check_esdt_balance(&second_lender, SHARE_TOKEN_ID, &rust_biguint!(20_000));
+
+    // 177 rewards for the pool, added to it
+    sa_setup.b_mock.set_block_epoch(55);
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            assert_eq!(
+                sc.get_share_price(),
+                managed_biguint!(1_008_850_000_000_000_000)
+            );
+        })
+        .assert_ok();
+
+    sa_setup
+        .call_withdraw_shares(&second_lender, 10_000, 10_088)
+        .assert_ok();
+
+    // new shares are minted at the current price
+    sa_setup
+        .call_lend_for_shares(&second_lender, 10_089, 10_000)
+        .assert_ok();
+
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            assert_eq!(sc.share_supply().get(), managed_biguint!(20_000));
+            assert_eq!(sc.shares_lent_amount().get(), managed_biguint!(20_178));
+            assert_eq!(sc.lent_amount().get(), managed_biguint!(170_178));
+        })
+        .assert_ok();
+}
+
+#[test]
+fn lend_for_shares_penalty_test() {
+    let _ = DebugApi::dummy();
+    let mut sa_setup = SavingsAccountSetup::new(savings_account::contract_obj);
+    let second_lender = sa_setup.second_lender_address.clone();
+
+    sa_setup.default_lenders();
+    sa_setup.default_borrows();
+
+    // no reserves, so 266 rewards are missing
+    sa_setup.b_mock.set_block_epoch(30);
+    sa_setup
+        .call_lend_for_shares(&second_lender, 20_000, 20_000)
+        .assert_ok();
+
+    // the rewards index did not move, so the pool is not charged the penalty again
+    sa_setup
+        .call_lend_for_shares(&second_lender, 10_000, 10_000)
+        .assert_ok();
+    sa_setup
+        .call_withdraw_shares(&second_lender, 10_000, 10_000)
+        .assert_ok();
+
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            assert_eq!(sc.missing_rewards().get(), managed_biguint!(266));
+            assert_eq!(
+                sc.total_missed_rewards_by_claim_since_last_calculation()
+                    .get(),
+                managed_biguint!(0)
+            );
+            assert_eq!(sc.shares_lent_amount().get(), managed_biguint!(20_000));
+            assert_eq!(
+                sc.get_share_price(),
+                managed_biguint!(1_000_000_000_000_000_000)
+            );
+        })
+        .assert_ok();
+}
+
+#[test]
+fn withdraw_queue_test() {
+    let _ = DebugApi::dummy();
+    let mut sa_setup = SavingsAccountSetup::new(savings_account::contract_obj);
+    let first_lender = sa_setup.first_lender_address.clone();
+    let second_lender = sa_setup.second_lender_address.clone();
+    let borrower = sa_setup.borrower_address.clone();
+
+    sa_setup.default_lenders();
+    sa_setup.default_borrows();
+
+    // 150,000 lent, 75,000 borrowed
+    sa_setup
+        .call_withdraw(&first_lender, 1, 100_000, 100_000)
+        .assert_user_error("Cannot withdraw, not enough funds");
+    sa_setup
+        .call_queue_withdraw(&first_lender, 1, 100_000, 1)
+        .assert_ok();
+
+    // the liquidity left is reserved for the queue
+    sa_setup
+        .call_withdraw(&second_lender, 2, 1_000, 1_000)
+        .assert_user_error("Cannot withdraw, not enough funds");
+
+    // anyone may process the queue, the request is served partially,
+    // and the stablecoins wait for the lender to claim them
+    sa_setup.call_process_withdraw_queue(&borrower).assert_ok();
+    sa_setup
+        .b_mock
+        .check_esdt_balance(&first_lender, STABLECOIN_TOKEN_ID, &rust_biguint!(0));
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            assert_eq!(
+                sc.withdraw_queue_payout(&managed_address!(&first_lender))
+                    .get(),
+                managed_biguint!(75_000)
+            );
+            assert_eq!(sc.lent_amount().get(), managed_biguint!(75_000));
+            assert_eq!(sc.queued_withdraw_amount().get(), managed_biguint!(25_000));
+            assert_eq!(
+                sc.get_withdraw_request(1).lend_amount,
+                managed_biguint!(25_000)
+            );
+        })
+        .assert_ok();
+
+    // repaid liquidity goes to the queue
+    let borrow_token_amount = rust_biguint!(STAKE_PER_POSITION) * DECIMALS;
+    sa_setup
+        .call_repay(&borrower, 1, &borrow_token_amount, 18_750, 1, 0)
+        .assert_ok();
+    sa_setup.call_process_withdraw_queue(&borrower).assert_ok();
+    sa_setup
+        .call_claim_withdraw_queue_payout(&first_lender, 93_750)
+        .assert_ok();
+    sa_setup
+        .b_mock
+        .check_esdt_balance(&first_lender, STABLECOIN_TOKEN_ID, &rust_biguint!(93_750));
+    sa_setup
+        .call_claim_withdraw_queue_payout(&first_lender, 0)
+        .assert_user_error("Nothing to claim");
+
+    sa_setup
+        .call_cancel_queued_withdraw(&second_lender, 1, 6_250)
+        .assert_user_error("Only the lender may cancel the request");
+    sa_setup
+        .call_cancel_queued_withdraw(&first_lender, 1, 6_250)
+        .assert_ok();
+    sa_setup.b_mock.check_nft_balance(
+        &first_lender,
+        LEND_TOKEN_ID,
+        1,
+        &rust_biguint!(6_250),
+        Option::<&elrond_wasm::elrond_codec::Empty>::None,
+    );
+
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            assert_eq!(sc.lent_amount().get(), managed_biguint!(56_250));
+            assert_eq!(sc.borrowed_amount().get(), managed_biguint!(56_250));
+            assert_eq!(sc.queued_withdraw_amount().get(), managed_biguint!(0));
+            assert!(sc.withdraw_request(1).is_empty());
+        })
+        .assert_ok();
+}
+
+#[test]
+fn borrow_with_queued_withdraw_test() {
+    let _ = DebugApi::dummy();
+    let mut sa_setup = SavingsAccountSetup::new(savings_account::contract_obj);
+    let first_lender = sa_setup.first_lender_address.clone();
+    let borrower = sa_setup.borrower_address.clone();
+    let liq_staking_amount = rust_biguint!(STAKE_PER_POSITION) * DECIMALS;
+
+    sa_setup.default_lenders();
+    sa_setup.b_mock.set_block_epoch(25);
+
+    sa_setup
+        .call_borrow_amount(&borrower, 1, &liq_staking_amount, Some(18_750), 1, 18_750)
+        .assert_ok();
+    sa_setup
+        .call_queue_withdraw(&first_lender, 1, 100_000, 1)
+        .assert_ok();
+
+    // 150,000 lent, 18,750 borrowed, 100,000 reserved for the queue
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            assert_eq!(sc.get_available_liquidity(), managed_biguint!(31_250));
+            assert_eq!(
+                sc.get_market_state().available_liquidity,
+                managed_biguint!(31_250)
+            );
+        })
+        .assert_ok();
+
+    sa_setup
+        .call_borrow_amount(&borrower, 2, &liq_staking_amount, Some(18_750), 2, 18_750)
+        .assert_ok();
+    sa_setup
+        .call_borrow_amount(&borrower, 3, &liq_staking_amount, Some(18_750), 3, 18_750)
+        .assert_user_error("Not have enough funds to lend");
+
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            assert_eq!(sc.get_available_liquidity(), managed_biguint!(12_500));
+        })
+        .assert_ok();
+}
+
+#[test]
+fn compound_rewards_test() {
+    let _ = DebugApi::dummy();
+    let mut sa_setup = SavingsAccountSetup::new(savings_account::contract_obj);
+    let first_lender = sa_setup.first_lender_address.clone();
+    let second_lender = sa_setup.second_lender_address.clone();
+
+    sa_setup.b_mock.set_block_epoch(20);
+    sa_setup
+        .call_lend_auto_compound(&first_lender, 100_000, 1)
+        .assert_ok();
+    sa_setup.b_mock.set_block_epoch(21);
+    sa_setup.call_lend(&second_lender, 50_000, 2).assert_ok();
+
+    sa_setup.default_borrows();
+    sa_setup.call_claim_staking_rewards().assert_ok();
+    sa_setup.call_convert_staking_token().assert_ok();
+
+    // 474 rewards added to the principal
+    sa_setup.b_mock.set_block_epoch(50);
+    sa_setup
+        .call_compound_rewards(&second_lender, 2, 50_000, 3, 50_474)
+        .assert_ok();
+    sa_setup.b_mock.check_nft_balance(
+        &second_lender,
+        LEND_TOKEN_ID,
+        3,
+        &rust_biguint!(50_474),
+        Some(&LendMetadata {
+            lend_epoch: 50,
+            lend_timestamp: 0,
+            lock_end_epoch: 0,
+            boost: 0,
+            auto_compound: false,
+        }),
+    );
+    sa_setup
+        .b_mock
+        .check_esdt_balance(&second_lender, STABLECOIN_TOKEN_ID, &rust_biguint!(50_000));
+    sa_setup
+        .call_compound_rewards(&second_lender, 3, 50_474, 3, 50_474)
+        .assert_user_error("No rewards to claim");
+
+    // auto-compounding tokens compound when claiming, and keep the flag
+    sa_setup.b_mock.set_block_epoch(60);
+    sa_setup
+        .call_lender_claim_compounded_rewards(&first_lender, 1, 100_000, 4, 101_337)
+        .assert_ok();
+    sa_setup.b_mock.check_nft_balance(
+        &first_lender,
+        LEND_TOKEN_ID,
+        4,
+        &rust_biguint!(101_337),
+        Some(&LendMetadata {
+            lend_epoch: 60,
+            lend_timestamp: 0,
+            lock_end_epoch: 0,
+            boost: 0,
+            auto_compound: true,
+        }),
+    );
+    sa_setup
+        .b_mock
+        .check_esdt_balance(&first_lender, STABLECOIN_TOKEN_ID, &rust_biguint!(0));
+
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            assert_eq!(sc.lent_amount().get(), managed_biguint!(151_811));
+        })
+        .assert_ok();
+
+    // 76,811 available, so the rest of the request is compounded while it waits
+    sa_setup
+        .call_queue_withdraw(&first_lender, 4, 101_337, 1)
+        .assert_ok();
+    sa_setup.b_mock.set_block_epoch(70);
+    sa_setup
+        .call_process_withdraw_queue(&first_lender)
+        .assert_ok();
+    sa_setup
+        .call_claim_withdraw_queue_payout(&first_lender, 77_114)
+        .assert_ok();
+    sa_setup
+        .b_mock
+        .check_esdt_balance(&first_lender, STABLECOIN_TOKEN_ID, &rust_biguint!(77_114));
+    sa_setup.b_mock.check_nft_balance(
+        sa_setup.sa_wrapper.address_ref(),
+        LEND_TOKEN_ID,
+        5,
+        &rust_biguint!(24_622),
+        Some(&LendMetadata {
+            lend_epoch: 70,
+            lend_timestamp: 0,
+            lock_end_epoch: 0,
+            boost: 0,
+            auto_compound: true,
+        }),
+    );
+
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            let request = sc.get_withdraw_request(1);
+            assert_eq!(request.lend_nonce, 5);
+            assert_eq!(request.lend_amount, managed_biguint!(24_622));
+            assert_eq!(sc.queued_withdraw_amount().get(), managed_biguint!(24_622));
+            assert_eq!(sc.lent_amount().get(), managed_biguint!(75_096));
+        })
+        .assert_ok();
+}
+
+#[test]
+fn process_auto_compound_test() {
+    let _ = DebugApi::dummy();
+    let mut sa_setup = SavingsAccountSetup::new(savings_account::contract_obj);
+    let first_lender = sa_setup.first_lender_address.clone();
+    let second_lender = sa_setup.second_lender_address.clone();
+
+    sa_setup.b_mock.set_block_epoch(20);
+    sa_setup
+        .call_lend_auto_compound(&first_lender, 100_000, 1)
+        .assert_ok();
+    sa_setup.b_mock.set_block_epoch(21);
+    sa_setup.call_lend(&second_lender, 50_000, 2).assert_ok();
+
+    sa_setup.default_borrows();
+    sa_setup.call_claim_staking_rewards().assert_ok();
+    sa_setup.call_convert_staking_token().assert_ok();
+
+    // anyone may compound the auto-compounding tokens, which are left in the lender's wallet
+    sa_setup.b_mock.set_block_epoch(50);
+    sa_setup
+        .call_process_auto_compound(&second_lender)
+        .assert_ok();
+    sa_setup
+        .call_process_auto_compound(&second_lender)
+        .assert_ok();
+    sa_setup.b_mock.check_nft_balance::<LendMetadata>(
+        &first_lender,
+        LEND_TOKEN_ID,
+        1,
+        &rust_biguint!(100_000),
+        None,
+    );
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            assert_eq!(
+                sc.auto_compounded_rewards_amount().get(),
+                managed_biguint!(948)
+            );
+            assert_eq!(sc.lent_amount().get(), managed_biguint!(150_000));
+            assert_eq!(sc.stablecoin_reserves().get(), managed_biguint!(7_578));
+        })
+        .assert_ok();
+    assert_eq!(sa_setup.call_get_lender_claimable_rewards(1, 100_000), 948);
+    assert_eq!(sa_setup.call_get_lender_claimable_rewards(2, 50_000), 474);
+
+    // the compounded rewards earn rewards as well, 1,340 without compounding
+    sa_setup.b_mock.set_block_epoch(60);
+    sa_setup
+        .call_lender_claim_compounded_rewards(&first_lender, 1, 100_000, 3, 101_343)
+        .assert_ok();
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            assert_eq!(
+                sc.auto_compounded_rewards_amount().get(),
+                managed_biguint!(0)
+            );
+            assert_eq!(sc.lent_amount().get(), managed_biguint!(151_343));
+            assert_eq!(sc.stablecoin_reserves().get(), managed_biguint!(6_987));
+        })
+        .assert_ok();
+}
+
+#[test]
+fn merge_lend_tokens_test() {
+    let _ = DebugApi::dummy();
+    let mut sa_setup = SavingsAccountSetup::new(savings_account::contract_obj);
+    let second_lender = sa_setup.second_lender_address.clone();
+
+    sa_setup.default_lenders();
+    sa_setup.default_borrows();
+    sa_setup.call_claim_staking_rewards().assert_ok();
+    sa_setup.call_convert_staking_token().assert_ok();
+    sa_setup.call_lend(&second_lender, 10_000, 3).assert_ok();
+
+    sa_setup
+        .call_merge_lend_tokens(&second_lender, &[(2, 50_000)], 4, 0)
+        .assert_user_error("Must send at least 2 LEND payments");
+
+    // 413 + 82 rewards paid out
+    sa_setup.b_mock.set_block_epoch(50);
+    sa_setup
+        .call_merge_lend_tokens(&second_lender, &[(2, 50_000), (3, 10_000)], 4, 495)
+        .assert_ok();
+    sa_setup.b_mock.check_nft_balance(
+        &second_lender,
+        LEND_TOKEN_ID,
+        4,
+        &rust_biguint!(60_000),
+        Some(&LendMetadata {
+            lend_epoch: 50,
+            lend_timestamp: 0,
+            lock_end_epoch: 0,
+            boost: 0,
+            auto_compound: false,
+        }),
+    );
+    sa_setup
+        .b_mock
+        .check_esdt_balance(&second_lender, STABLECOIN_TOKEN_ID, &rust_biguint!(40_495));
+
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            assert_eq!(sc.lent_amount().get(), managed_biguint!(160_000));
+            assert_eq!(
+                sc.get_lender_claimable_rewards_view(4, managed_biguint!(60_000)),
+                managed_biguint!(0)
+            );
+        })
+        .assert_ok();
+
+    sa_setup
+        .call_lend_auto_compound(&second_lender, 10_000, 5)
+        .assert_ok();
+    sa_setup
+        .call_merge_lend_tokens(&second_lender, &[(4, 60_000), (5, 10_000)], 6, 0)
+        .assert_user_error("Can only merge LEND tokens with the same auto-compound setting");
+}
+
+#[test]
+fn merge_borrow_tokens_test() {
+    let _ = DebugApi::dummy();
+    let mut sa_setup = SavingsAccountSetup::new(savings_account::contract_obj);
+    let borrower = sa_setup.borrower_address.clone();
+    let first_amount = rust_biguint!(100) * DECIMALS;
+    let second_amount = rust_biguint!(150) * DECIMALS;
+
+    sa_setup.default_lenders();
+
+    sa_setup.b_mock.set_block_epoch(25);
+    sa_setup
+        .call_borrow(&borrower, 1, &first_amount, 1, 7_500)
+        .assert_ok();
+    sa_setup
+        .call_borrow(&borrower, 1, &second_amount, 2, 11_250)
+        .assert_ok();
+    sa_setup
+        .call_borrow(&borrower, 2, &first_amount, 3, 7_500)
+        .assert_ok();
+
+    sa_setup.b_mock.set_block_epoch(50);
+    sa_setup
+        .call_merge_borrow_tokens(
+            &borrower,
+            &[(1, first_amount.clone()), (3, first_amount.clone())],
+            4,
+        )
+        .assert_user_error("Borrow SFTs must share the same staking position");
+
+    // 7,773 + 11,660 debt is kept
+    sa_setup
+        .call_merge_borrow_tokens(
+            &borrower,
+            &[(1, first_amount.clone()), (2, second_amount.clone())],
+            4,
+        )
+        .assert_ok();
+    sa_setup.b_mock.check_nft_balance(
+        &borrower,
+        BORROW_TOKEN_ID,
+        4,
+        &(rust_biguint!(250) * DECIMALS),
+        Some(&BorrowMetadata::<DebugApi> {
+            borrow_epoch: 50,
+            staking_position_id: 1,
+            staked_token_value_in_dollars_at_borrow: managed_biguint!(100),
+            borrowed_amount: managed_biguint!(18_750),
+            borrow_index: managed_biguint!(1_000_040_177),
+        }),
+    );
+
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            assert!(sc.borrow_position(1).is_empty());
+            assert!(sc.borrow_position(2).is_empty());
+            assert_eq!(
+                sc.get_repay_amount(4, managed_biguint!(250) * DECIMALS),
+                managed_biguint!(19_433)
+            );
+            assert_eq!(sc.borrowed_amount().get(), managed_biguint!(26_250));
+            assert_eq!(sc.total_debt().get(), managed_biguint!(27_207));
+        })
+        .assert_ok();
+}
+
+#[test]
+fn split_borrow_tokens_test() {
+    let _ = DebugApi::dummy();
+    let mut sa_setup = SavingsAccountSetup::new(savings_account::contract_obj);
+    let borrower = sa_setup.borrower_address.clone();
+    let borrow_token_amount = rust_biguint!(STAKE_PER_POSITION) * DECIMALS;
+    let split_amount = rust_biguint!(100) * DECIMALS;
+
+    sa_setup.default_lenders();
+    sa_setup.default_borrows();
+
+    sa_setup
+        .call_split_borrow_tokens(&borrower, 1, &borrow_token_amount, 5, &borrow_token_amount)
+        .assert_user_error("Must keep part of the Borrow SFTs of the position");
+
+    // 100 out of 250 Borrow SFTs take 100 liquid staking tokens and 7,500 of the debt
+    sa_setup
+        .call_split_borrow_tokens(&borrower, 1, &split_amount, 5, &split_amount)
+        .assert_ok();
+    sa_setup.b_mock.check_nft_balance(
+        &borrower,
+        BORROW_TOKEN_ID,
+        5,
+        &split_amount,
+        Some(&BorrowMetadata::<DebugApi> {
+            borrow_epoch: 25,
+            staking_position_id: 1,
+            staked_token_value_in_dollars_at_borrow: managed_biguint!(100),
+            borrowed_amount: managed_biguint!(7_500),
+            borrow_index: managed_biguint!(1_000_000_000),
+        }),
+    );
+    sa_setup.b_mock.check_nft_balance(
+        &borrower,
+        BORROW_TOKEN_ID,
+        1,
+        &(rust_biguint!(150) * DECIMALS),
+        Some(&BorrowMetadata::<DebugApi> {
+            borrow_epoch: 25,
+            staking_position_id: 1,
+            staked_token_value_in_dollars_at_borrow: managed_biguint!(100),
+            borrowed_amount: managed_biguint!(18_750),
+            borrow_index: managed_biguint!(1_000_000_000),
+        }),
+    );
+
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            let borrow_position = sc.borrow_position(1).get();
+            assert_eq!(
+                borrow_position.borrow_token_amount,
+                managed_biguint!(150) * DECIMALS
+            );
+            assert_eq!(
+                borrow_position.liquid_staking_amount,
+                managed_biguint!(150) * DECIMALS
+            );
+            assert_eq!(borrow_position.borrowed_amount, managed_biguint!(11_250));
+
+            let split_position = sc.borrow_position(5).get();
+            assert_eq!(split_position.borrowed_amount, managed_biguint!(7_500));
+            assert_eq!(split_position.collateral.len(), 1);
+            assert_eq!(split_position.collateral.get(0).staking_position_id, 1);
+            assert_eq!(
+                split_position.collateral.get(0).liquid_staking_amount,
+                managed_biguint!(100) * DECIMALS
+            );
+
+            assert_eq!(sc.borrowed_amount().get(), managed_biguint!(75_000));
+        })
+        .assert_ok();
+
+    // one year after borrow, the 33,030 debt of the position is shared between both nonces
+    sa_setup.b_mock.set_block_epoch(390);
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            assert_eq!(
+                sc.get_repay_amount(1, managed_biguint!(150) * DECIMALS),
+                managed_biguint!(19_818)
+            );
+            assert_eq!(
+                sc.get_repay_amount(5, managed_biguint!(100) * DECIMALS),
+                managed_biguint!(13_212)
+            );
+        })
+        .assert_ok();
+
+    // each part is managed on its own
+    sa_setup
+        .call_repay(&borrower, 5, &split_amount, 13_212, 1, 0)
+        .assert_ok();
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            assert!(sc.borrow_position(5).is_empty());
+            assert_eq!(
+                sc.borrow_position(1).get().borrowed_amount,
+                managed_biguint!(11_250)
+            );
+            assert_eq!(sc.borrowed_amount().get(), managed_biguint!(67_500));
+        })
+        .assert_ok();
+}
+
+#[test]
+fn accrue_interest_test() {
+    let _ = DebugApi::dummy();
+    let mut sa_setup = SavingsAccountSetup::new(savings_account::contract_obj);
+    let borrower = sa_setup.borrower_address.clone();
+
+    sa_setup.default_lenders();
+    sa_setup.default_borrows();
+
+    // 25 epochs at 56,66% yearly borrow rate, compounded every epoch
+    sa_setup.b_mock.set_block_epoch(50);
+    sa_setup.call_accrue_interest(&borrower).assert_ok();
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            assert_eq!(sc.borrow_index().get(), managed_biguint!(1_039_544_553));
+            assert_eq!(sc.total_debt().get(), managed_biguint!(77_965));
+            assert_eq!(sc.borrowed_amount().get(), managed_biguint!(75_000));
+        })
+        .assert_ok();
+
+    // accrued interest increases the utilisation, and so the borrow rate
+    sa_setup.b_mock.set_block_epoch(390);
+    sa_setup.call_accrue_interest(&borrower).assert_ok();
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            assert_eq!(sc.borrow_index().get(), managed_biguint!(1_765_931_770));
+            assert_eq!(sc.total_debt().get(), managed_biguint!(132_443));
+        })
+        .assert_ok();
+}
+
+#[test]
+fn market_state_views_test() {
+    let _ = DebugApi::dummy();
+    let mut sa_setup = SavingsAccountSetup::new(savings_account::contract_obj);
+
+    sa_setup.default_lenders();
+    sa_setup.default_borrows();
+
+    // the views project the interest accrued since the last update, without saving it
+    sa_setup.b_mock.set_block_epoch(50);
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            let market_state = sc.get_market_state();
+            assert_eq!(market_state.lent_amount, managed_biguint!(150_000));
+            assert_eq!(market_state.borrowed_amount, managed_biguint!(75_000));
+            assert_eq!(market_state.total_debt, managed_biguint!(77_965));
+            assert_eq!(market_state.available_liquidity, managed_biguint!(75_000));
+            assert_eq!(
+                market_state.capital_utilisation,
+                managed_biguint!(519_766_666)
+            );
+            assert_eq!(market_state.borrow_rate, managed_biguint!(569_302_222));
+            assert_eq!(market_state.deposit_rate, managed_biguint!(138_421_080));
+
+            assert_eq!(sc.total_debt().get(), managed_biguint!(75_000));
+            assert_eq!(sc.get_current_total_debt_view(), managed_biguint!(77_965));
+        })
+        .assert_ok();
+}
+
+#[test]
+fn queue_pool_params_test() {
+    let _ = DebugApi::dummy();
+    let mut sa_setup = SavingsAccountSetup::new(savings_account::contract_obj);
+
+    sa_setup.default_lenders();
+    sa_setup.default_borrows();
+
+    sa_setup
+        .call_queue_pool_params(
+            200_000_000,
+            BORROW_RATE_UNDER_OPTIMAL_FACTOR,
+            BORROW_RATE_OVER_OPTIMAL_FACTOR,
+            0,
+            RESERVE_FACTOR,
+            1,
+        )
+        .assert_user_error("Invalid optimal utilisation");
+    sa_setup
+        .call_queue_pool_params(
+            200_000_000,
+            BORROW_RATE_UNDER_OPTIMAL_FACTOR,
+            BORROW_RATE_OVER_OPTIMAL_FACTOR,
+            OPTIMAL_UTILISATION,
+            1_100_000_000,
+            1,
+        )
+        .assert_user_error("Invalid reserve factor");
+
+    sa_setup.b_mock.set_block_epoch(43);
+    sa_setup
+        .call_queue_pool_params(
+            200_000_000,
+            BORROW_RATE_UNDER_OPTIMAL_FACTOR,
+            BORROW_RATE_OVER_OPTIMAL_FACTOR,
+            OPTIMAL_UTILISATION,
+            RESERVE_FACTOR,
+            1,
+        )
+        .assert_ok();
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            let pending_change = sc.pending_param_changes().get(&1).unwrap();
+            assert_eq!(pending_change.queue_epoch, 43);
+            assert_eq!(pending_change.unlock_epoch, 43 + PARAM_CHANGE_DELAY_EPOCHS);
+        })
+        .assert_ok();
+
+    sa_setup.b_mock.set_block_epoch(49);
+    sa_setup
+        .call_execute_param_change(1)
+        .assert_user_error("Param change is still timelocked");
+
+    // interest up to now is accrued with the old borrow rate
+    sa_setup.b_mock.set_block_epoch(50);
+    sa_setup.call_execute_param_change(1).assert_ok();
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            assert_eq!(sc.borrow_index().get(), managed_biguint!(1_039_544_553));
+            assert_eq!(sc.total_debt().get(), managed_biguint!(77_965));
+            assert_eq!(
+                sc.pool_params().get().base_borrow_rate,
+                managed_biguint!(200_000_000)
+            );
+            assert!(sc.pending_param_changes().is_empty());
+        })
+        .assert_ok();
+
+    sa_setup
+        .call_execute_param_change(1)
+        .assert_user_error("Unknown param change");
+}
+
+#[test]
+fn queue_loan_to_value_percentage_test() {
+    let _ = DebugApi::dummy();
+    let mut sa_setup = SavingsAccountSetup::new(savings_account::contract_obj);
+
+    sa_setup
+        .call_queue_loan_to_value_percentage(LIQUIDATION_THRESHOLD + 1, 1)
+        .assert_user_error("Loan to value percentage above liquidation threshold");
+    sa_setup
+        .call_queue_loan_to_value_percentage(0, 1)
+        .assert_user_error("Invalid loan to value percentage");
+
+    sa_setup
+        .call_queue_loan_to_value_percentage(500_000_000, 1)
+        .assert_ok();
+    sa_setup
+        .call_queue_loan_to_value_percentage(600_000_000, 2)
+        .assert_ok();
+
+    // cancelled changes can not be executed anymore
+    sa_setup.call_cancel_param_change(1).assert_ok();
+    sa_setup
+        .b_mock
+        .set_block_epoch(10 + PARAM_CHANGE_DELAY_EPOCHS);
+    sa_setup
+        .call_execute_param_change(1)
+        .assert_user_error("Unknown param change");
+
+    sa_setup.call_execute_param_change(2).assert_ok();
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            assert_eq!(
+                sc.loan_to_value_percentage().get(),
+                managed_biguint!(600_000_000)
+            );
+        })
+        .assert_ok();
+}
+
+#[test]
+fn queue_liquidation_params_test() {
+    let _ = DebugApi::dummy();
+    let mut sa_setup = SavingsAccountSetup::new(savings_account::contract_obj);
+
+    sa_setup
+        .call_queue_liquidation_params(700_000_000, CLOSE_FACTOR, LIQUIDATION_BONUS, 1)
+        .assert_user_error("Loan to value percentage above liquidation threshold");
+    sa_setup
+        .call_queue_liquidation_params(LIQUIDATION_THRESHOLD, 0, LIQUIDATION_BONUS, 1)
+        .assert_user_error("Invalid close factor");
+    sa_setup
+        .call_queue_liquidation_params(LIQUIDATION_THRESHOLD, CLOSE_FACTOR, 1_000_000_000, 1)
+        .assert_user_error("Invalid liquidation bonus");
+
+    sa_setup
+        .call_queue_liquidation_params(850_000_000, 1_000_000_000, 100_000_000, 1)
+        .assert_ok();
+    sa_setup
+        .call_execute_param_change(1)
+        .assert_user_error("Param change is still timelocked");
+
+    sa_setup
+        .b_mock
+        .set_block_epoch(10 + PARAM_CHANGE_DELAY_EPOCHS);
+    sa_setup.call_execute_param_change(1).assert_ok();
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            let liquidation_params = sc.liquidation_params().get();
+            assert_eq!(
+                liquidation_params.liquidation_threshold,
+                managed_biguint!(850_000_000)
+            );
+            assert_eq!(
+                liquidation_params.close_factor,
+                managed_biguint!(1_000_000_000)
+            );
+            assert_eq!(
+                liquidation_params.liquidation_bonus,
+                managed_biguint!(100_000_000)
+            );
+        })
+        .assert_ok();
+}
+
+#[test]
+fn queue_param_change_delay_test() {
+    let _ = DebugApi::dummy();
+    let mut sa_setup = SavingsAccountSetup::new(savings_account::contract_obj);
+
+    sa_setup
+        .call_queue_param_change_delay(0, 1)
+        .assert_user_error("Invalid param change delay");
+    sa_setup.call_queue_param_change_delay(30, 1).assert_ok();
+
+    // the delay itself is timelocked by the old delay
+    sa_setup
+        .b_mock
+        .set_block_epoch(10 + PARAM_CHANGE_DELAY_EPOCHS);
+    sa_setup.call_execute_param_change(1).assert_ok();
+
+    sa_setup
+        .call_queue_loan_to_value_percentage(600_000_000, 2)
+        .assert_ok();
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            assert_eq!(sc.param_change_delay_epochs().get(), 30);
+
+            let pending_change = sc.pending_param_changes().get(&2).unwrap();
+            assert_eq!(
+                pending_change.unlock_epoch,
+                10 + PARAM_CHANGE_DELAY_EPOCHS + 30
+            );
+        })
+        .assert_ok();
+
+    sa_setup
+        .b_mock
+        .set_block_epoch(10 + 2 * PARAM_CHANGE_DELAY_EPOCHS);
+    sa_setup
+        .call_execute_param_change(2)
+        .assert_user_error("Param change is still timelocked");
+}
+
+#[test]
+fn accrue_interest_by_timestamp_test() {
+    let _ = DebugApi::dummy();
+    let mut sa_setup = SavingsAccountSetup::new(savings_account::contract_obj);
+    let borrower = sa_setup.borrower_address.clone();
+
+    // the switch goes through the timelock as well
+    sa_setup
+        .call_queue_time_basis(TimeBasis::Seconds, 1)
+        .assert_ok();
+    sa_setup
+        .call_execute_param_change(1)
+        .assert_user_error("Param change is still timelocked");
+
+    sa_setup.default_lenders();
+
+    sa_setup.b_mock.set_block_timestamp(1_000);
+    sa_setup.call_execute_param_change(1).assert_ok();
+
+    sa_setup.default_borrows();
+
+    // 10 days later, still in the same epoch
+    sa_setup
+        .b_mock
+        .set_block_timestamp(1_000 + 10 * 24 * 60 * 60);
+    sa_setup.call_accrue_interest(&borrower).assert_ok();
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            assert_eq!(sc.borrow_index().get(), managed_biguint!(1_015_646_254));
+            assert_eq!(sc.total_debt().get(), managed_biguint!(76_173));
+        })
+        .assert_ok();
+}
+
+#[test]
+fn liquidate_partial_test() {
+    let _ = DebugApi::dummy();
+    let mut sa_setup = SavingsAccountSetup::new(savings_account::contract_obj);
+    let liquidator = sa_setup.b_mock.create_user_account(&rust_biguint!(0));
+    let borrower = sa_setup.borrower_address.clone();
+    let borrow_token_amount = rust_biguint!(STAKE_PER_POSITION) * DECIMALS;
+    let seized_amount = rust_biguint!(17_340) * DECIMALS / 100u32;
+
+    sa_setup
+        .b_mock
+        .set_esdt_balance(&liquidator, STABLECOIN_TOKEN_ID, &rust_biguint!(30_000));
+
+    sa_setup.default_lenders();
+    sa_setup.default_borrows();
+
+    // 25,000 * 80% / 18,750, no interest accrued yet
+    assert_eq!(sa_setup.call_get_health_factor(1), 1_066_666_666);
+    sa_setup
+        .call_liquidate(&liquidator, 1, 30_000, 1, &seized_amount, 0)
+        .assert_user_error("Position is healthy");
+
+    // one year after borrow
+    sa_setup.b_mock.set_block_epoch(390);
+
+    // debt = 18,750 * (1 + 56,66% / 365)^365 = 33,030
+    // health factor = 25,000 * 80% / 33,030 = ~0.605
+    assert_eq!(sa_setup.call_get_health_factor(1), 605_510_142);
+
+    // only 50% of the debt can be covered, i.e. 16,515
+    // liquidator receives 16,515 + 5% bonus = 17,340 worth of liquid staking tokens
+    let tx_result = sa_setup.call_liquidate(&liquidator, 1, 30_000, 1, &seized_amount, 13_485);
+    tx_result.assert_ok();
+
+    let mut expected_topics = vec![
+        liquidator.to_vec(),
+        top_encode_to_vec_u8_or_panic(&1u64),
+        top_encode_to_vec_u8_or_panic(&16_515u64),
+        seized_amount.to_bytes_be(),
+        top_encode_to_vec_u8_or_panic(&0u64),
+        top_encode_to_vec_u8_or_panic(&390u64),
+    ];
+    expected_topics.extend(sa_setup.get_rates_topics());
+    check_event_topics(&tx_result, b"liquidate", &expected_topics);
+
+    sa_setup.b_mock.check_nft_balance(
+        &liquidator,
+        LIQUID_STAKING_TOKEN_ID,
+        1,
+        &seized_amount,
+        Some(&elrond_wasm::elrond_codec::Empty),
+    );
+    sa_setup
+        .b_mock
+        .check_esdt_balance(&liquidator, STABLECOIN_TOKEN_ID, &rust_biguint!(13_485));
+
+    // half of the principal was repaid, the rest of the payment is interest
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            assert_eq!(sc.borrowed_amount().get(), managed_biguint!(65_625));
+            assert_eq!(sc.stablecoin_reserves().get(), managed_biguint!(6_426));
+            assert_eq!(sc.protocol_revenue().get(), managed_biguint!(714));
+            assert_eq!(sc.total_debt().get(), managed_biguint!(115_605));
+
+            let borrow_position = sc.borrow_position(1).get();
+            assert_eq!(borrow_position.borrowed_amount, managed_biguint!(9_375));
+            assert_eq!(
+                borrow_position.borrow_token_amount,
+                managed_biguint!(STAKE_PER_POSITION) * DECIMALS
+            );
+            assert_eq!(
+                borrow_position.liquid_staking_amount,
+                managed_biguint!(25_000 - 17_340) * DECIMALS / 100u32
+            );
+
+            // the staking position still holds the rest of the collateral
+            assert_eq!(
+                sc.staking_positions().get_staking_position(1),
+                StakingPosition {
+                    liquid_staking_nonce: 1,
+                    prev_pos_id: 0,
+                    next_pos_id: 2,
+                }
+            );
+        })
+        .assert_ok();
+
+    // Borrow SFTs were not touched
     sa_setup.b_mock.check_nft_balance(
         &borrower,
         BORROW_TOKEN_ID,
         1,
-        &(rust_biguint!(100) * DECIMALS),
+        &borrow_token_amount,
         Some(&BorrowMetadata::<DebugApi> {
             borrow_epoch: 25,
             staking_position_id: 1,
             staked_token_value_in_dollars_at_borrow: managed_biguint!(100),
+            borrowed_amount: managed_biguint!(18_750),
+            borrow_index: managed_biguint!(1_000_000_000),
         }),
     );
+}
+
+#[test]
+fn liquidate_bad_debt_test() {
+    let _ = DebugApi::dummy();
+    let mut sa_setup = SavingsAccountSetup::new(savings_account::contract_obj);
+    let liquidator = sa_setup.b_mock.create_user_account(&rust_biguint!(0));
+    let second_lender = sa_setup.second_lender_address.clone();
+    let first_seized_amount = rust_biguint!(17_340) * DECIMALS / 100u32;
+    let second_seized_amount = rust_biguint!(25_000 - 17_340) * DECIMALS / 100u32;
+
+    sa_setup
+        .b_mock
+        .set_esdt_balance(&liquidator, STABLECOIN_TOKEN_ID, &rust_biguint!(30_000));
+
+    sa_setup.default_lenders();
+    sa_setup.default_borrows();
+
+    sa_setup.b_mock.set_block_epoch(390);
+    sa_setup
+        .call_liquidate(&liquidator, 1, 30_000, 1, &first_seized_amount, 13_485)
+        .assert_ok();
+
+    // the lender rewards owed for the past year use up the reserves
+    sa_setup.call_lend(&second_lender, 1_000, 3).assert_ok();
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            assert_eq!(sc.stablecoin_reserves().get(), managed_biguint!(0));
+        })
+        .assert_ok();
+
+    // 8,257 out of the 16,515 debt are covered, but the 7,660 worth of collateral left is
+    // less than 8,257 + 5% bonus, so the rest of the 9,375 principal is written off
+    sa_setup
+        .call_liquidate(&liquidator, 1, 10_000, 1, &second_seized_amount, 1_743)
+        .assert_ok();
+
+    // 4,688 principal lost, 3,213 of it covered by the reserves from the 3,570 interest
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            assert!(sc.borrow_position(1).is_empty());
+            assert_eq!(sc.borrowed_amount().get(), managed_biguint!(56_250));
+            assert_eq!(sc.total_debt().get(), managed_biguint!(99_090));
+            assert_eq!(sc.bad_debt().get(), managed_biguint!(1_475));
+            assert_eq!(sc.stablecoin_reserves().get(), managed_biguint!(0));
+            assert_eq!(sc.protocol_revenue().get(), managed_biguint!(1_071));
+            assert_eq!(sc.lent_amount().get(), managed_biguint!(151_000));
+            assert_eq!(sc.get_available_liquidity(), managed_biguint!(93_275));
+        })
+        .assert_ok();
+
+    // the bad debt is paid back first from the 6,750 staking rewards added to the reserves
+    sa_setup.call_claim_staking_rewards().assert_ok();
+    sa_setup.call_convert_staking_token().assert_ok();
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            assert_eq!(sc.bad_debt().get(), managed_biguint!(0));
+            assert_eq!(sc.protocol_revenue().get(), managed_biguint!(1_821));
+            assert_eq!(sc.get_available_liquidity(), managed_biguint!(94_750));
+        })
+        .assert_ok();
+}
+
+#[test]
+fn auction_test() {
+    let _ = DebugApi::dummy();
+    let mut sa_setup = SavingsAccountSetup::new(savings_account::contract_obj);
+    let bidder = sa_setup.b_mock.create_user_account(&rust_biguint!(0));
+    let borrower = sa_setup.borrower_address.clone();
+    let borrow_token_amount = rust_biguint!(STAKE_PER_POSITION) * DECIMALS;
+    let bought_amount = rust_biguint!(20_998) * DECIMALS / 99u32;
+    let surplus_amount = &borrow_token_amount - &bought_amount;
+
+    sa_setup
+        .b_mock
+        .set_esdt_balance(&bidder, STABLECOIN_TOKEN_ID, &rust_biguint!(25_000));
+
+    sa_setup.default_lenders();
+    sa_setup.default_borrows();
+
+    // start at 10% above the oracle price, decrease by 1% of the start price each round,
+    // and allow cancelling after 100 rounds
+    sa_setup
+        .call_queue_auction_params(100_000_000, 10_000_000, 500_000_000, 0, 1)
+        .assert_user_error("Invalid max rounds");
+    sa_setup
+        .call_queue_auction_params(100_000_000, 10_000_000, 500_000_000, 100, 1)
+        .assert_ok();
+
+    // the params only apply once the timelock has passed
+    sa_setup
+        .call_start_auction(&bidder, 1)
+        .assert_user_error("Auction params not set");
+    sa_setup
+        .call_execute_param_change(1)
+        .assert_user_error("Param change is still timelocked");
+    sa_setup
+        .b_mock
+        .set_block_epoch(25 + PARAM_CHANGE_DELAY_EPOCHS);
+    sa_setup.call_execute_param_change(1).assert_ok();
+
+    sa_setup.b_mock.set_block_round(10);
+    sa_setup
+        .call_start_auction(&bidder, 1)
+        .assert_user_error("Position is healthy");
+
+    // 0.2 years after borrow
+    // debt = 18,750 * (1 + 56,66% / 365)^73 = 20,998
+    // health factor = 25,000 * 80% / 20,998 = ~0.952
+    sa_setup.b_mock.set_block_epoch(98);
+    assert_eq!(sa_setup.call_get_health_factor(1), 952_471_663);
+
+    sa_setup.call_start_auction(&bidder, 1).assert_ok();
+    sa_setup.call_start_auction(&bidder, 2).assert_ok();
+    assert_eq!(sa_setup.call_get_auction_price(1), 110);
+
+    sa_setup
+        .call_liquidate(&bidder, 1, 25_000, 1, &bought_amount, 0)
+        .assert_user_error("Position is being auctioned");
+
+    // 10 rounds later, price = 110 - 10 * 1.1 = 99
+    sa_setup.b_mock.set_block_round(20);
+    assert_eq!(sa_setup.call_get_auction_price(1), 99);
+    sa_setup
+        .call_cancel_auction(&borrower, 2)
+        .assert_user_error("Position is still unhealthy");
+
+    // bid covers the whole debt, the extra stablecoins are returned
+    let tx_result = sa_setup.call_bid(&bidder, 1, 25_000, &bought_amount, 4_002);
+    tx_result.assert_ok();
+
+    let mut expected_topics = vec![
+        bidder.to_vec(),
+        top_encode_to_vec_u8_or_panic(&1u64),
+        top_encode_to_vec_u8_or_panic(&20_998u64),
+        bought_amount.to_bytes_be(),
+        top_encode_to_vec_u8_or_panic(&0u64),
+        top_encode_to_vec_u8_or_panic(&98u64),
+    ];
+    expected_topics.extend(sa_setup.get_rates_topics());
+    check_event_topics(&tx_result, b"bid", &expected_topics);
+
+    sa_setup.b_mock.check_nft_balance(
+        &bidder,
+        LIQUID_STAKING_TOKEN_ID,
+        1,
+        &bought_amount,
+        Some(&elrond_wasm::elrond_codec::Empty),
+    );
+    sa_setup
+        .b_mock
+        .check_esdt_balance(&bidder, STABLECOIN_TOKEN_ID, &rust_biguint!(4_002));
+
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            assert_eq!(sc.borrowed_amount().get(), managed_biguint!(56_250));
+            assert_eq!(sc.stablecoin_reserves().get(), managed_biguint!(2_024));
+            assert_eq!(sc.protocol_revenue().get(), managed_biguint!(224));
+            assert!(sc.auction(1).is_empty());
+            assert_eq!(
+                sc.borrow_position(1).get().borrowed_amount,
+                managed_biguint!(0)
+            );
+        })
+        .assert_ok();
+
+    // borrower gets back the unsold collateral
+    sa_setup
+        .call_claim_liquidation_surplus(&borrower, 1, &borrow_token_amount, &surplus_amount)
+        .assert_ok();
+
     sa_setup.b_mock.check_nft_balance(
         &borrower,
         LIQUID_STAKING_TOKEN_ID,
-        5,
-        &(rust_biguint!(150) * DECIMALS),
-        Option::<&elrond_wasm::elrond_codec::Empty>::None,
+        1,
+        &surplus_amount,
+        Some(&elrond_wasm::elrond_codec::Empty),
     );
     sa_setup
         .b_mock
-        .check_esdt_balance(&borrower, STABLECOIN_TOKEN_ID, &rust_biguint!(51_501));
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            assert!(sc.borrow_position(1).is_empty());
+        })
+        .assert_ok();
 
+    // no bids for position 2 during the 100 rounds, so it can be cancelled
+    sa_setup.b_mock.set_block_round(111);
+    sa_setup.call_cancel_auction(&borrower, 2).assert_ok();
     sa_setup
         .b_mock
         .execute_query(&sa_setup.sa_wrapper, |sc| {
-            assert_eq!(sc.lent_amount().get(), managed_biguint!(150_000));
-            assert_eq!(sc.borrowed_amount().get(), managed_biguint!(60_000));
+            assert!(sc.auction(2).is_empty());
+        })
+        .assert_ok();
+    sa_setup
+        .call_cancel_auction(&borrower, 2)
+        .assert_user_error("No auction for position");
+}
+
+#[test]
+fn auction_bad_debt_test() {
+    let _ = DebugApi::dummy();
+    let mut sa_setup = SavingsAccountSetup::new(savings_account::contract_obj);
+    let bidder = sa_setup.b_mock.create_user_account(&rust_biguint!(0));
+    let borrow_token_amount = rust_biguint!(STAKE_PER_POSITION) * DECIMALS;
+
+    sa_setup
+        .b_mock
+        .set_esdt_balance(&bidder, STABLECOIN_TOKEN_ID, &rust_biguint!(25_000));
+
+    sa_setup.default_lenders();
+    sa_setup.default_borrows();
+    sa_setup
+        .call_queue_auction_params(100_000_000, 10_000_000, 500_000_000, 100, 1)
+        .assert_ok();
+    sa_setup
+        .b_mock
+        .set_block_epoch(25 + PARAM_CHANGE_DELAY_EPOCHS);
+    sa_setup.call_execute_param_change(1).assert_ok();
+
+    sa_setup.b_mock.set_block_round(10);
+    sa_setup.b_mock.set_block_epoch(98);
+    sa_setup.call_start_auction(&bidder, 1).assert_ok();
+
+    // the price reaches the min price of 50, so all the collateral is worth only 12,500
+    sa_setup.b_mock.set_block_round(70);
+    assert_eq!(sa_setup.call_get_auction_price(1), 50);
+    sa_setup
+        .call_bid(&bidder, 1, 25_000, &borrow_token_amount, 12_500)
+        .assert_ok();
+
+    // 11,161 principal and 1,339 interest repaid out of the 20,998 debt
+    // the 7,589 principal left is written off, 1,206 of it covered by the reserves
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            assert!(sc.auction(1).is_empty());
+            assert!(sc.borrow_position(1).is_empty());
+            assert_eq!(sc.borrowed_amount().get(), managed_biguint!(56_250));
+            assert_eq!(sc.total_debt().get(), managed_biguint!(62_995));
+            assert_eq!(sc.bad_debt().get(), managed_biguint!(6_383));
+            assert_eq!(sc.stablecoin_reserves().get(), managed_biguint!(0));
+            assert_eq!(sc.get_available_liquidity(), managed_biguint!(87_367));
+        })
+        .assert_ok();
+}
+
+#[test]
+fn auction_partial_bid_test() {
+    let _ = DebugApi::dummy();
+    let mut sa_setup = SavingsAccountSetup::new(savings_account::contract_obj);
+    let bidder = sa_setup.b_mock.create_user_account(&rust_biguint!(0));
+    let first_bought_amount = rust_biguint!(10_000) * DECIMALS / 99u32;
+    let second_bought_amount = rust_biguint!(12_186) * DECIMALS / 99u32;
+
+    sa_setup
+        .b_mock
+        .set_esdt_balance(&bidder, STABLECOIN_TOKEN_ID, &rust_biguint!(25_000));
+
+    sa_setup.default_lenders();
+    sa_setup.default_borrows();
+    sa_setup
+        .call_queue_auction_params(100_000_000, 10_000_000, 500_000_000, 100, 1)
+        .assert_ok();
+    sa_setup
+        .b_mock
+        .set_block_epoch(25 + PARAM_CHANGE_DELAY_EPOCHS);
+    sa_setup.call_execute_param_change(1).assert_ok();
+
+    sa_setup.b_mock.set_block_round(10);
+    sa_setup.b_mock.set_block_epoch(98);
+    sa_setup.call_start_auction(&bidder, 1).assert_ok();
+
+    // interest keeps accruing during the auction, the debt is now 22,186
+    // 8,451 principal and 1,549 interest repaid
+    sa_setup.b_mock.set_block_round(20);
+    sa_setup.b_mock.set_block_epoch(133);
+    sa_setup
+        .call_bid(&bidder, 1, 10_000, &first_bought_amount, 0)
+        .assert_ok();
+
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            assert!(!sc.auction(1).is_empty());
+
+            let borrow_position = sc.borrow_position(1).get();
+            assert_eq!(borrow_position.borrowed_amount, managed_biguint!(10_299));
+            assert_eq!(
+                sc.get_borrow_position_total_debt(&borrow_position),
+                managed_biguint!(12_186)
+            );
+            assert_eq!(sc.total_debt().get(), managed_biguint!(78_747));
+        })
+        .assert_ok();
+
+    // the rest of the debt is covered, and removed from the total debt
+    // the reserves from the first bid went to the lender rewards missing since the borrows
+    sa_setup
+        .call_bid(&bidder, 1, 15_000, &second_bought_amount, 2_814)
+        .assert_ok();
+
+    sa_setup
+        .b_mock
+        .execute_query(&sa_setup.sa_wrapper, |sc| {
+            assert!(sc.auction(1).is_empty());
+            assert_eq!(
+                sc.borrow_position(1).get().borrowed_amount,
+                managed_biguint!(0)
+            );
+            assert_eq!(sc.borrowed_amount().get(), managed_biguint!(56_250));
+            assert_eq!(sc.total_debt().get(), managed_biguint!(66_561));
+            assert_eq!(sc.protocol_revenue().get(), managed_biguint!(342));
+            assert_eq!(sc.stablecoin_reserves().get(), managed_biguint!(1_699));
         })
         .assert_ok();
 }
@@ -8,32 +8,104 @@ elrond_wasm_node::wasm_endpoints! {
     savings_account
     (
         callBack
+        accrueInterest
+        addCollateral
+        bid
         borrow
+        borrowMore
+        cancelAuction
+        cancelParamChange
+        cancelQueuedWithdraw
+        claimLiquidationSurplus
+        claimRewardsIou
         claimStakingRewards
+        claimWithdrawQueuePayout
+        compoundRewards
         convertStakingTokenToStablecoin
+        executeParamChange
         getAggregatorAddress
+        getAuction
+        getAuctionParams
+        getAuctionPrice
+        getAutoCompoundedRewardsAmount
+        getAvailableLiquidity
+        getBadDebt
         getBorowedAmount
+        getBorrowIndex
+        getBorrowPosition
+        getBorrowRate
         getBorrowTokenId
+        getCapitalUtilisation
+        getCurrentTotalDebt
         getDelegationScAddress
+        getDepositRate
         getDexSwapScAddress
+        getHealthFactor
         getLastStakingRewardsClaimEpoch
         getLastStakingTokenConvertEpoch
         getLendTokenId
         getLenderClaimableRewards
-        getLenderRewardsPercentagePerEpoch
+        getLenderRewardsIndex
         getLentAmount
         getLiquidStakingTokenId
         getLoadToValuePercentage
+        getLockBoostAmount
+        getLockPeriods
+        getMarketState
+        getParamChangeDelayEpochs
         getPenaltyAmount
+        getPendingParamChanges
+        getProtocolRevenue
+        getQueuedWithdrawAmount
+        getRepayAmount
+        getRepayQuote
+        getRewardsIou
+        getRewardsIouFunds
+        getSharePrice
+        getShareSupply
+        getShareTokenId
+        getSharesLentAmount
         getStablecoinReserves
         getStablecoinTokenId
         getStakedTokenId
+        getTimeBasis
+        getTotalDebt
+        getTotalRewardsIou
+        getWithdrawQueue
+        getWithdrawQueuePayout
+        getWithdrawRequest
         issueBorrowToken
         issueLendToken
+        issueShareToken
         lend
+        lendForShares
         lenderClaimRewards
+        liquidate
+        mergeBorrowTokens
+        mergeLendTokens
+        migrateBorrowPosition
+        processAutoCompound
+        processWithdrawQueue
+        queueAuctionParams
+        queueDelegationScAddress
+        queueDexSwapScAddress
+        queueLiquidationParams
+        queueLoanToValuePercentage
+        queueParamChangeDelay
+        queuePoolParams
+        queuePriceAggregatorAddress
+        queueTimeBasis
+        queueWithdraw
+        removeLockPeriod
         repay
-        setPriceAggregatorAddress
+        repayDebtFor
+        repayInterest
+        setLockPeriod
+        splitBorrowTokens
+        startAuction
         withdraw
+        withdrawCollateral
+        withdrawProtocolRevenue
+        withdrawShares
     )
 }